@@ -0,0 +1,187 @@
+#ifndef TETRIS_BANE_H
+#define TETRIS_BANE_H
+
+/* Generated by cbindgen from src/ffi.rs, don't edit. */
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+#define TB_INPUT_NONE 0
+
+#define TB_INPUT_LEFT 1
+
+#define TB_INPUT_RIGHT 2
+
+#define TB_INPUT_ROTATE 3
+
+#define TB_INPUT_DROP 4
+
+#define TB_INPUT_RELEASE 5
+
+#define TB_PLAYING 0
+
+#define TB_WON 1
+
+#define TB_LOST 2
+
+#define TB_ERROR 3
+
+#define TB_EMPTY -1
+
+typedef struct TbGame TbGame;
+
+/**
+ * Reads the blocks from the assets directory at the path, e.g.
+ * "/opt/tetris-bane/assets", rather than from the working directory.
+ * Returns false, changing nothing, if it has no pieces directory.
+ *
+ * # Safety
+ *
+ * `path` has to be null or a 0 terminated string.
+ */
+bool tb_set_assets_dir(const char *path);
+
+/**
+ * The number of built in modes, numbered from 0: Bane, Classic, Chill,
+ * Metal, Marathon, Sprint, Ultra, Dig and Survival.
+ */
+uint32_t tb_mode_count(void);
+
+/**
+ * Copies the mode's name into the buffer, ending with a 0 and cut
+ * short if the buffer is too small. Returns the length of the whole
+ * name, 0 if there's no such mode.
+ *
+ * # Safety
+ *
+ * `buffer` has to be null or have room for `size` bytes.
+ */
+size_t tb_mode_name(uint32_t mode, char *buffer, size_t size);
+
+/**
+ * Returns null if there's no such mode or its pieces can't be read.
+ */
+TbGame *tb_game_create(uint32_t mode, uint64_t seed);
+
+/**
+ * # Safety
+ *
+ * `game` has to be null or a handle from tb_game_create, which isn't
+ * used again.
+ */
+void tb_game_destroy(TbGame *game);
+
+/**
+ * Starts the game again from the seed, in the same mode. Returns
+ * false if the game couldn't be started.
+ *
+ * # Safety
+ *
+ * `game` has to be null or a live handle from tb_game_create.
+ */
+bool tb_game_reset(TbGame *game, uint64_t seed);
+
+/**
+ * Plays a frame with the input, one of TB_INPUT_*. Returns the status
+ * after the frame, one of TB_PLAYING, TB_WON, TB_LOST or TB_ERROR.
+ *
+ * # Safety
+ *
+ * `game` has to be null or a live handle from tb_game_create.
+ */
+uint32_t tb_game_step(TbGame *game, uint32_t input);
+
+/**
+ * Turns the block in play to the orientation, moves it to the leftmost
+ * column given and drops it, playing frames until it has locked.
+ * Returns false, having played nothing, if it can't be placed there.
+ *
+ * # Safety
+ *
+ * `game` has to be null or a live handle from tb_game_create.
+ */
+bool tb_game_place(TbGame *game, uint32_t orientation, int32_t column);
+
+/**
+ * # Safety
+ *
+ * `game` has to be null or a live handle from tb_game_create.
+ */
+uint32_t tb_game_status(const TbGame *game);
+
+/**
+ * # Safety
+ *
+ * `game` has to be null or a live handle from tb_game_create.
+ */
+int32_t tb_game_width(const TbGame *game);
+
+/**
+ * # Safety
+ *
+ * `game` has to be null or a live handle from tb_game_create.
+ */
+int32_t tb_game_height(const TbGame *game);
+
+/**
+ * The colour of the cell of the board, without the block in play.
+ * TB_EMPTY if it's empty or off the board.
+ *
+ * # Safety
+ *
+ * `game` has to be null or a live handle from tb_game_create.
+ */
+int32_t tb_game_cell(const TbGame *game, int32_t x, int32_t y);
+
+/**
+ * The number of cells of the block in play.
+ *
+ * # Safety
+ *
+ * `game` has to be null or a live handle from tb_game_create.
+ */
+int32_t tb_game_block_size(const TbGame *game);
+
+/**
+ * Sets x and y to the position of a cell of the block in play. Returns
+ * false if there's no such cell.
+ *
+ * # Safety
+ *
+ * `game` has to be null or a live handle from tb_game_create, and `x`
+ * and `y` null or pointers to an int32_t each.
+ */
+bool tb_game_block_cell(const TbGame *game, int32_t index, int32_t *x, int32_t *y);
+
+/**
+ * # Safety
+ *
+ * `game` has to be null or a live handle from tb_game_create.
+ */
+int32_t tb_game_block_color(const TbGame *game);
+
+/**
+ * # Safety
+ *
+ * `game` has to be null or a live handle from tb_game_create.
+ */
+int32_t tb_game_score(const TbGame *game);
+
+/**
+ * # Safety
+ *
+ * `game` has to be null or a live handle from tb_game_create.
+ */
+int32_t tb_game_lines(const TbGame *game);
+
+/**
+ * # Safety
+ *
+ * `game` has to be null or a live handle from tb_game_create.
+ */
+int32_t tb_game_level(const TbGame *game);
+
+#endif /* TETRIS_BANE_H */
@@ -0,0 +1,269 @@
+/*
+
+Garbage attacks for versus play. Clearing lines works out how many
+garbage rows to send with the attack table below. Rows sent to a
+player first wait in their incoming meter, where their own attacks
+cancel them, and are pushed in from the bottom of the board when they
+next lock a block without clearing a line.
+
+An attack is the rows for the lines cleared at once, or for a spin,
+plus a row for back to back difficult clears and the rows for the
+combo. Any block can spin, a spin is a block that was rotated into a
+spot where it can't move left, right or up. Difficult clears are four
+or more lines at once and spins that clear lines.
+
+The attack state is kept with each Game so local and networked versus
+work the same way. Rows sent by a game collect in outgoing until the
+match takes them.
+
+*/
+
+use std::time;
+
+use crate::block;
+use crate::block::Position;
+use crate::game;
+use crate::garbage;
+
+// Rows sent for clearing 0, 1, 2, ... lines at once. Clearing five, on
+// the Bane board, sends more than a four.
+const LINE_ATTACK: [usize; 6] = [0, 0, 1, 2, 4, 6];
+// Rows sent for a spin clearing 0, 1, 2, ... lines.
+const SPIN_ATTACK: [usize; 6] = [0, 2, 4, 6, 8, 10];
+// Rows added for each clear in a row after the first.
+const COMBO_ATTACK: [usize; 12] = [0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 4, 5];
+const BACK_TO_BACK_ATTACK: usize = 1;
+
+// How long garbage waits in the meter before it can be pushed in.
+const GARBAGE_DELAY: time::Duration = time::Duration::from_millis(500);
+// Garbage pushed in at once, the rest waits for the next block.
+const MAX_ROWS_PER_LOCK: usize = 8;
+
+pub struct Attack {
+    pub incoming: Vec<Incoming>, // oldest first
+    pub outgoing: usize,         // rows sent but not yet taken by the match
+    pub combo: usize,            // clears in a row, 0 after a block clears nothing
+    pub back_to_back: bool,      // whether the last clear was difficult
+    pub rotated: bool,           // whether the block last moved by rotating
+}
+
+pub struct Incoming {
+    pub rows: usize,
+    pub hole: usize,
+    pub received_at: time::Duration, // time played when it was sent
+}
+
+pub fn initialise() -> Attack {
+    Attack {
+        incoming: vec![],
+        outgoing: 0,
+        combo: 0,
+        back_to_back: false,
+        rotated: false,
+    }
+}
+
+// Rows waiting in the meter.
+pub fn incoming_rows(attack: &Attack) -> usize {
+    attack.incoming.iter().map(|incoming| incoming.rows).sum()
+}
+
+// Queues rows sent by the other player.
+pub fn receive(game: &mut game::Game, rows: usize, hole: usize) {
+    if rows > 0 {
+        game.attack.incoming.push(Incoming {
+            rows: rows,
+            hole: hole,
+            received_at: game.time_played,
+        });
+    }
+}
+
+// Returns the rows sent since the last call.
+pub fn take_outgoing(game: &mut game::Game) -> usize {
+    std::mem::replace(&mut game.attack.outgoing, 0)
+}
+
+// A spin is a rotated block that can't move left, right or up. Checked
+// before the block is painted on the board.
+pub fn is_spin(game: &game::Game) -> bool {
+    let stuck = |dx: i32, dy: i32| {
+        game.block.positions.iter().any(|p| {
+            let p = Position {
+                x: p.x + dx,
+                y: p.y + dy,
+            };
+            !is_empty(&game.board, p)
+        })
+    };
+    game.attack.rotated && stuck(-1, 0) && stuck(1, 0) && stuck(0, -1)
+}
+
+fn is_empty(board: &game::Board, p: Position) -> bool {
+    p.y >= 0
+        && (p.y as usize) < board.len()
+        && p.x >= 0
+        && (p.x as usize) < board[0].len()
+        && board[p.y as usize][p.x as usize].is_none()
+}
+
+// Rows for clearing lines at once, before combos and back to back.
+pub fn clear_attack(lines: i32, spin: bool) -> usize {
+    let table: &[usize] = match spin {
+        true => &SPIN_ATTACK,
+        false => &LINE_ATTACK,
+    };
+    let lines = lines.max(0) as usize;
+    match table.get(lines) {
+        Some(&rows) => rows,
+        // Longer clears than the table knows keep adding two a line.
+        None => table[table.len() - 1] + 2 * (lines + 1 - table.len()),
+    }
+}
+
+fn combo_attack(combo: usize) -> usize {
+    COMBO_ATTACK[combo.min(COMBO_ATTACK.len() - 1)]
+}
+
+// Works out the attack of a block that has locked and cleared lines.
+// The attack cancels incoming garbage first and the rest is sent.
+// Returns false if garbage pushed in topped the player out.
+pub fn on_lock(game: &mut game::Game, lines: i32, spin: bool) -> bool {
+    if lines == 0 {
+        game.attack.combo = 0;
+        return insert_due_garbage(game);
+    }
+
+    let difficult = lines >= 4 || spin;
+    let mut rows = clear_attack(lines, spin) + combo_attack(game.attack.combo);
+    if difficult && game.attack.back_to_back {
+        rows += BACK_TO_BACK_ATTACK;
+    }
+    game.attack.back_to_back = difficult;
+    game.attack.combo += 1;
+
+    let incoming = &mut game.attack.incoming;
+    while rows > 0 && !incoming.is_empty() {
+        let cancelled = rows.min(incoming[0].rows);
+        incoming[0].rows -= cancelled;
+        rows -= cancelled;
+        if incoming[0].rows == 0 {
+            incoming.remove(0);
+        }
+    }
+    game.attack.outgoing += rows;
+    true
+}
+
+// Pushes in the garbage that has waited long enough, oldest first.
+fn insert_due_garbage(game: &mut game::Game) -> bool {
+    let width = game.board[0].len();
+    let mut rows: Vec<Vec<Option<block::Color>>> = vec![];
+    while let Some(incoming) = game.attack.incoming.first_mut() {
+        let waited = game.time_played.saturating_sub(incoming.received_at);
+        let space = MAX_ROWS_PER_LOCK - rows.len();
+        if space == 0 || waited < GARBAGE_DELAY {
+            break;
+        }
+        let n = incoming.rows.min(space);
+        rows.extend((0..n).map(|_| garbage::row(width, incoming.hole)));
+        incoming.rows -= n;
+        if incoming.rows == 0 {
+            game.attack.incoming.remove(0);
+        }
+    }
+    rows.is_empty() || game::insert_garbage(game, rows)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn classic_game() -> game::Game {
+        game::played_game(1, 0, |_| None)
+    }
+
+    fn garbage_rows(game: &game::Game) -> usize {
+        game.board
+            .iter()
+            .filter(|row| row.iter().any(|cell| cell.is_some()))
+            .count()
+    }
+
+    #[test]
+    fn clears_follow_the_tables() {
+        assert_eq!(clear_attack(0, false), 0);
+        assert_eq!(clear_attack(1, false), 0);
+        assert_eq!(clear_attack(4, false), 4);
+        // Five lines at once, only possible on the Bane board.
+        assert_eq!(clear_attack(5, false), 6);
+        assert_eq!(clear_attack(6, false), 8);
+        assert_eq!(clear_attack(7, false), 10);
+        assert_eq!(clear_attack(1, true), 2);
+        assert_eq!(clear_attack(6, true), 12);
+        assert_eq!(clear_attack(-1, false), 0);
+    }
+
+    #[test]
+    fn combos_and_back_to_back_add_up() {
+        let mut game = classic_game();
+        assert!(on_lock(&mut game, 4, false));
+        assert_eq!(take_outgoing(&mut game), 4);
+        // Back to back, and the second clear in a row.
+        assert!(on_lock(&mut game, 4, false));
+        assert_eq!(take_outgoing(&mut game), 4 + 1);
+        // Not difficult, so back to back ends, the third clear in a row.
+        assert!(on_lock(&mut game, 2, false));
+        assert_eq!(take_outgoing(&mut game), 1 + 1);
+        assert!(!game.attack.back_to_back);
+        assert!(on_lock(&mut game, 0, false));
+        assert_eq!(game.attack.combo, 0);
+        assert!(on_lock(&mut game, 2, true));
+        assert_eq!(take_outgoing(&mut game), 4);
+        for _ in 0..20 {
+            on_lock(&mut game, 1, false);
+        }
+        assert_eq!(combo_attack(game.attack.combo), 5);
+    }
+
+    #[test]
+    fn attacks_cancel_incoming_first() {
+        let mut game = classic_game();
+        receive(&mut game, 2, 0);
+        receive(&mut game, 3, 1);
+        assert_eq!(incoming_rows(&game.attack), 5);
+        assert!(on_lock(&mut game, 4, false));
+        assert_eq!(take_outgoing(&mut game), 0);
+        assert_eq!(game.attack.incoming.len(), 1);
+        assert_eq!(game.attack.incoming[0].hole, 1);
+        assert_eq!(incoming_rows(&game.attack), 1);
+
+        assert!(on_lock(&mut game, 4, false));
+        assert_eq!(incoming_rows(&game.attack), 0);
+        assert_eq!(take_outgoing(&mut game), 4 + 1 - 1);
+    }
+
+    #[test]
+    fn garbage_waits_and_comes_in_a_few_rows_at_a_time() {
+        let mut game = classic_game();
+        receive(&mut game, 10, 3);
+        game.time_played = GARBAGE_DELAY / 2;
+        assert!(on_lock(&mut game, 0, false));
+        assert_eq!(garbage_rows(&game), 0);
+
+        game.time_played = GARBAGE_DELAY;
+        assert!(on_lock(&mut game, 0, false));
+        assert_eq!(garbage_rows(&game), MAX_ROWS_PER_LOCK);
+        assert_eq!(incoming_rows(&game.attack), 10 - MAX_ROWS_PER_LOCK);
+        let bottom = game.board.last().unwrap();
+        assert!(bottom[3].is_none());
+        assert_eq!(
+            bottom.iter().filter(|cell| cell.is_some()).count(),
+            bottom.len() - 1
+        );
+
+        assert!(on_lock(&mut game, 0, false));
+        assert_eq!(garbage_rows(&game), 10);
+        assert!(game.attack.incoming.is_empty());
+    }
+}
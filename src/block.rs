@@ -1,1310 +1,591 @@
-use crate::game::Mode;
-use rand::seq::SliceRandom;
-use std::collections::HashMap;
-pub type Delta = Position;
+/*
 
-type RotationMap = HashMap<Label, Vec<Vec<Delta>>>;
+The blocks of each mode are defined in text files rather than in code,
+see assets/pieces. A file in the pieces directory of the config
+directory with the same name replaces the built in one, so new shapes
+can be tried without rebuilding the game.
 
-const RED: Color = Color {
-    r: 205,
-    g: 31,
-    b: 72,
-};
+Each block is a single drawing, a bounding box matrix of cells, and a
+pivot. The orientations a block can be rotated to are worked out when
+the file is loaded by turning the matrix clockwise around the pivot
+until it is back where it started. Symmetric blocks such as the O end
+up with fewer than four orientations.
 
-const BLUE: Color = Color {
-    r: 54,
-    g: 97,
-    b: 254,
-};
+Rotating swaps the cells of the current orientation for the next one.
+All orientations are relative to the top left corner of the drawing,
+so the block keeps its place on the board as it turns.
 
-const GREEN: Color = Color {
-    r: 0,
-    g: 140,
-    b: 54,
-};
-
-const OFF_WHITE: Color = Color {
-    r: 243,
-    g: 238,
-    b: 217,
-};
+*/
 
-const PINK: Color = Color {
-    r: 231,
-    g: 141,
-    b: 213,
-};
+use std::fs;
+use std::path::Path;
 
-const TURQUOISE: Color = Color {
-    r: 0,
-    g: 195,
-    b: 218,
-};
+use crate::config;
+use crate::mode::Randomizer;
+use crate::random;
+use rand::seq::SliceRandom;
+pub type Delta = Position;
 
-const ORANGE: Color = Color {
-    r: 208,
-    g: 49,
-    b: 3,
-};
+const PIECES_DIR: &str = "pieces"; // in the assets directory
+const USER_PIECES_DIR: &str = "pieces";
 
-#[derive(Copy, Clone)]
+#[derive(Copy, Clone, PartialEq, Eq)]
 pub struct Color {
     pub r: u8,
     pub g: u8,
     pub b: u8,
 }
 
+// Cells of garbage rows. Piece files shouldn't use it for a block.
+pub const GARBAGE_COLOR: Color = Color {
+    r: 107,
+    g: 107,
+    b: 107,
+};
+
+impl Color {
+    // Stable text form used in save and piece files, e.g. "cd1f48".
+    pub fn to_hex(&self) -> String {
+        format!("{:02x}{:02x}{:02x}", self.r, self.g, self.b)
+    }
+
+    pub fn from_hex(hex: &str) -> Option<Color> {
+        if hex.len() != 6 {
+            return None;
+        }
+        let channel = |i: usize| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok();
+        Some(Color {
+            r: channel(0)?,
+            g: channel(2)?,
+            b: channel(4)?,
+        })
+    }
+}
+
+// The name a block is given in its piece file.
 #[derive(Clone, PartialEq, Eq, Hash)]
-enum Label {
-    T,
-    // XXX
-    //  X
-    S,
-    //  XX
-    // XX
-    Z,
-    // XX
-    //  XX
-    L,
-    // XXX
-    // X
-    J,
-    // XXX
-    //   X
-    O,
-    // XX
-    // XX
-    I,
-    // XXXX
-    BaneT,
-    // XXX
-    //  X
-    //  X
-    BaneO,
-    // XXX
-    // XXX
-    // XXX
-    BaneS,
-    //  XX
-    // XX
-    // X
-    BaneX,
-    //  X
-    // XXX
-    //  X
-    BaneI,
-    // XXXXX
-    BaneL,
-    // XXX
-    // X
-    // X
-    BaneN,
-    // XXX
-    // X X
-    MetalM,
-    // X X
-    // XXX
-    // X X
-    // X X
-    MetalE,
-    // XXX
-    // XX
-    // X
-    // XXX
-    MetalT,
-    // XXX
-    //  X
-    //  X
-    //  X
-    MetalA,
-    //  X
-    // X X
-    // XXX
-    // X X
-    MetalL,
-    // X
-    // X
-    // X
-    // XXX
+pub struct Label(String);
+
+impl Label {
+    pub fn new(name: &str) -> Label {
+        Label(name.to_string())
+    }
+
+    pub fn name(&self) -> &str {
+        &self.0
+    }
 }
 
 #[derive(Clone)]
 pub struct Block {
     pub positions: Vec<Position>,
     pub color: Color,
-    label: Label,
+    pub label: Label,
+    // Cells of each orientation relative to the top left corner of the
+    // block's drawing.
+    orientations: Vec<Vec<Position>>,
 }
 
-#[derive(Copy, Clone)]
+#[derive(Copy, Clone, PartialEq, Eq)]
 pub struct Position {
     pub x: i32,
     pub y: i32,
 }
 
-pub fn spawn(mode: &Mode) -> Block {
-    let start_offset = match mode {
-        Mode::Bane { .. } => 7,
-        _ => 3,
-    };
-
-    let i = Block {
-        label: Label::I,
-        positions: vec![
-            Position {
-                y: 0,
-                x: 0 + start_offset,
-            },
-            Position {
-                y: 0,
-                x: 1 + start_offset,
-            },
-            Position {
-                y: 0,
-                x: 2 + start_offset,
-            },
-            Position {
-                y: 0,
-                x: 3 + start_offset,
-            },
-        ],
-        color: BLUE,
-    };
-    let t = Block {
-        label: Label::T,
-        positions: vec![
-            Position {
-                y: 0,
-                x: 0 + start_offset,
-            },
-            Position {
-                y: 0,
-                x: 1 + start_offset,
-            },
-            Position {
-                y: 0,
-                x: 2 + start_offset,
-            },
-            Position {
-                y: 1,
-                x: 1 + start_offset,
-            },
-        ],
-        color: GREEN,
-    };
-    let o = Block {
-        label: Label::O,
-        positions: vec![
-            Position {
-                y: 0,
-                x: 0 + start_offset,
-            },
-            Position {
-                y: 0,
-                x: 1 + start_offset,
-            },
-            Position {
-                y: 1,
-                x: 0 + start_offset,
-            },
-            Position {
-                y: 1,
-                x: 1 + start_offset,
-            },
-        ],
-        color: TURQUOISE,
-    };
-    let l = Block {
-        label: Label::L,
-        positions: vec![
-            Position {
-                y: 0,
-                x: 0 + start_offset,
-            },
-            Position {
-                y: 0,
-                x: 1 + start_offset,
-            },
-            Position {
-                y: 0,
-                x: 2 + start_offset,
-            },
-            Position {
-                y: 1,
-                x: 0 + start_offset,
-            },
-        ],
-        color: PINK,
-    };
-    let j = Block {
-        label: Label::J,
-        positions: vec![
-            Position {
-                y: 0,
-                x: 0 + start_offset,
-            },
-            Position {
-                y: 0,
-                x: 1 + start_offset,
-            },
-            Position {
-                y: 0,
-                x: 2 + start_offset,
-            },
-            Position {
-                y: 1,
-                x: 2 + start_offset,
-            },
-        ],
-        color: ORANGE,
-    };
-    let s = Block {
-        label: Label::S,
-        positions: vec![
-            Position {
-                y: 1,
-                x: 0 + start_offset,
-            },
-            Position {
-                y: 1,
-                x: 1 + start_offset,
-            },
-            Position {
-                y: 0,
-                x: 1 + start_offset,
-            },
-            Position {
-                y: 0,
-                x: 2 + start_offset,
-            },
-        ],
-        color: RED,
-    };
-    let z = Block {
-        label: Label::Z,
-        positions: vec![
-            Position {
-                y: 0,
-                x: 0 + start_offset,
-            },
-            Position {
-                y: 0,
-                x: 1 + start_offset,
-            },
-            Position {
-                y: 1,
-                x: 1 + start_offset,
-            },
-            Position {
-                y: 1,
-                x: 2 + start_offset,
-            },
-        ],
-        color: OFF_WHITE,
-    };
-    let bane_o = Block {
-        label: Label::BaneO,
-        positions: vec![
-            Position {
-                y: 0,
-                x: 0 + start_offset,
-            },
-            Position {
-                y: 0,
-                x: 1 + start_offset,
-            },
-            Position {
-                y: 0,
-                x: 2 + start_offset,
-            },
-            Position {
-                y: 1,
-                x: 0 + start_offset,
-            },
-            Position {
-                y: 1,
-                x: 1 + start_offset,
-            },
-            Position {
-                y: 1,
-                x: 2 + start_offset,
-            },
-            Position {
-                y: 2,
-                x: 0 + start_offset,
-            },
-            Position {
-                y: 2,
-                x: 1 + start_offset,
-            },
-            Position {
-                y: 2,
-                x: 2 + start_offset,
-            },
-        ],
-        color: TURQUOISE,
-    };
+pub fn spawn(
+    blocks: &[Block],
+    randomizer: Randomizer,
+    bag: &mut Vec<usize>,
+    rng: &mut random::Rng,
+) -> Block {
+    match randomizer {
+        Randomizer::Random => blocks.choose(rng).unwrap().clone(),
+        Randomizer::Bag => {
+            if bag.is_empty() {
+                *bag = (0..blocks.len()).collect();
+                bag.shuffle(rng);
+            }
+            blocks[bag.pop().unwrap()].clone()
+        }
+    }
+}
 
-    let bane_t = Block {
-        label: Label::BaneT,
-        positions: vec![
-            Position {
-                y: 0,
-                x: 0 + start_offset,
-            },
-            Position {
-                y: 0,
-                x: 1 + start_offset,
-            },
-            Position {
-                y: 0,
-                x: 2 + start_offset,
-            },
-            Position {
-                y: 1,
-                x: 1 + start_offset,
-            },
-            Position {
-                y: 2,
-                x: 1 + start_offset,
-            },
-        ],
-        color: GREEN,
-    };
+// Returns the labels of the blocks in a set.
+pub fn labels(blocks: &[Block]) -> Vec<Label> {
+    blocks.iter().map(|b| b.label.clone()).collect()
+}
 
-    let bane_s = Block {
-        label: Label::BaneS,
-        positions: vec![
-            Position {
-                y: 2,
-                x: 0 + start_offset,
-            },
-            Position {
-                y: 1,
-                x: 0 + start_offset,
-            },
-            Position {
-                y: 1,
-                x: 1 + start_offset,
-            },
-            Position {
-                y: 0,
-                x: 1 + start_offset,
-            },
-            Position {
-                y: 0,
-                x: 2 + start_offset,
-            },
-        ],
-        color: RED,
-    };
+// Returns the set of blocks used by the mode, in their spawn positions.
+pub fn load_blocks(pieces: &str) -> Vec<Block> {
+    find_blocks(pieces).unwrap_or_else(|error| panic!("{}", error))
+}
 
-    let bane_x = Block {
-        label: Label::BaneX,
-        positions: vec![
-            Position {
-                y: 0,
-                x: 1 + start_offset,
-            },
-            Position {
-                y: 1,
-                x: 0 + start_offset,
-            },
-            Position {
-                y: 1,
-                x: 1 + start_offset,
-            },
-            Position {
-                y: 2,
-                x: 1 + start_offset,
-            },
-            Position {
-                y: 1,
-                x: 2 + start_offset,
-            },
-        ],
-        color: OFF_WHITE,
-    };
+// As load_blocks but a missing or broken piece file is an error rather
+// than a crash, for files named by the player.
+pub fn find_blocks(pieces: &str) -> Result<Vec<Block>, String> {
+    let file = format!("{}.txt", pieces);
+
+    let user_file = config::file_path(USER_PIECES_DIR).map(|dir| dir.join(&file));
+    if let Some(path) = user_file.filter(|path| path.exists()) {
+        match load_blocks_file(&path) {
+            Ok(blocks) => return Ok(blocks),
+            Err(error) => eprintln!("Ignoring {}: {}", path.display(), error),
+        }
+    }
 
-    let bane_i = Block {
-        label: Label::BaneI,
-        positions: vec![
-            Position {
-                y: 0,
-                x: 0 + start_offset,
-            },
-            Position {
-                y: 0,
-                x: 1 + start_offset,
-            },
-            Position {
-                y: 0,
-                x: 2 + start_offset,
-            },
-            Position {
-                y: 0,
-                x: 3 + start_offset,
-            },
-            Position {
-                y: 0,
-                x: 4 + start_offset,
-            },
-        ],
-        color: BLUE,
-    };
+    let path = config::asset_path(PIECES_DIR).join(file);
+    load_blocks_file(&path).map_err(|error| format!("{}: {}", path.display(), error))
+}
 
-    let bane_l = Block {
-        label: Label::BaneL,
-        positions: vec![
-            Position {
-                y: 0,
-                x: 0 + start_offset,
-            },
-            Position {
-                y: 0,
-                x: 1 + start_offset,
-            },
-            Position {
-                y: 0,
-                x: 2 + start_offset,
-            },
-            Position {
-                y: 1,
-                x: 0 + start_offset,
-            },
-            Position {
-                y: 2,
-                x: 0 + start_offset,
-            },
-        ],
-        color: PINK,
-    };
+fn load_blocks_file(path: &Path) -> Result<Vec<Block>, String> {
+    let contents = fs::read_to_string(path).map_err(|error| error.to_string())?;
+    parse_blocks(&contents)
+}
 
-    let bane_n = Block {
-        label: Label::BaneN,
-        positions: vec![
-            Position {
-                y: 0,
-                x: 0 + start_offset,
-            },
-            Position {
-                y: 0,
-                x: 1 + start_offset,
-            },
-            Position {
-                y: 0,
-                x: 2 + start_offset,
-            },
-            Position {
-                y: 1,
-                x: 0 + start_offset,
-            },
-            Position {
-                y: 1,
-                x: 2 + start_offset,
-            },
-        ],
-        color: ORANGE,
-    };
+// A block as it is drawn in a piece file.
+struct Shape {
+    // matrix[y][x] is true where the block has a cell.
+    matrix: Vec<Vec<bool>>,
+    // In doubled coordinates so it can sit on a cell corner. The centre
+    // of cell (x, y) is (2x + 1, 2y + 1).
+    pivot: Option<Position>,
+    spawn: Position,
+}
 
-    let metal_m = Block {
-        label: Label::MetalM,
-        positions: vec![
-            Position {
-                y: 0,
-                x: 0 + start_offset,
-            },
-            Position {
-                y: 0,
-                x: 2 + start_offset,
-            },
-            Position {
-                y: 1,
-                x: 0 + start_offset,
-            },
-            Position {
-                y: 1,
-                x: 1 + start_offset,
-            },
-            Position {
-                y: 1,
-                x: 2 + start_offset,
-            },
-            Position {
-                y: 2,
-                x: 0 + start_offset,
-            },
-            Position {
-                y: 2,
-                x: 2 + start_offset,
-            },
-            Position {
-                y: 3,
-                x: 0 + start_offset,
-            },
-            Position {
-                y: 3,
-                x: 2 + start_offset,
-            },
-        ],
-        color: Color {
-            r: 100,
-            g: 100,
-            b: 100,
-        },
-    };
+fn empty_shape() -> Shape {
+    Shape {
+        matrix: vec![],
+        pivot: None,
+        spawn: Position { x: 0, y: 0 },
+    }
+}
 
-    let metal_e = Block {
-        label: Label::MetalE,
-        positions: vec![
-            Position {
-                y: 0,
-                x: 0 + start_offset,
-            },
-            Position {
-                y: 0,
-                x: 1 + start_offset,
-            },
-            Position {
-                y: 0,
-                x: 2 + start_offset,
-            },
-            Position {
-                y: 1,
-                x: 0 + start_offset,
-            },
-            Position {
-                y: 1,
-                x: 1 + start_offset,
-            },
-            Position {
-                y: 2,
-                x: 0 + start_offset,
-            },
-            Position {
-                y: 3,
-                x: 0 + start_offset,
-            },
-            Position {
-                y: 3,
-                x: 1 + start_offset,
-            },
-            Position {
-                y: 3,
-                x: 2 + start_offset,
-            },
-        ],
-        color: Color {
-            r: 150,
-            g: 150,
-            b: 150,
-        },
-    };
+// Parses a piece file. Errors name the line they were found on.
+pub fn parse_blocks(contents: &str) -> Result<Vec<Block>, String> {
+    let mut blocks: Vec<Block> = vec![];
+    let mut shape = empty_shape();
+    // Rows of the drawing being read.
+    let mut drawing: Vec<&str> = vec![];
+
+    for (i, line) in contents.lines().enumerate() {
+        let line = line.trim();
+        let error = |message: String| format!("line {}: {}", i + 1, message);
+
+        if line.starts_with('#') {
+            continue;
+        }
+        // A blank line or a new setting ends the drawing being read.
+        if (line.is_empty() || line.contains('=')) && !drawing.is_empty() {
+            if !shape.matrix.is_empty() {
+                return Err(error(
+                    "only one drawing per piece, rotations are worked out".to_string(),
+                ));
+            }
+            shape.matrix = parse_matrix(&drawing).map_err(error)?;
+            drawing.clear();
+        }
+        if line.is_empty() {
+            continue;
+        }
+
+        if let Some((key, value)) = config::parse_pairs(line).pop() {
+            match key.as_str() {
+                "piece" => {
+                    if let Some(block) = blocks.last_mut() {
+                        finish_block(block, &shape).map_err(error)?;
+                    }
+                    blocks.push(Block {
+                        positions: vec![],
+                        color: Color { r: 0, g: 0, b: 0 },
+                        label: Label::new(&value),
+                        orientations: vec![],
+                    });
+                    shape = empty_shape();
+                }
+                _ if blocks.is_empty() => {
+                    return Err(error("expected \"piece = <name>\" first".to_string()))
+                }
+                "color" => match Color::from_hex(&value) {
+                    Some(color) => blocks.last_mut().unwrap().color = color,
+                    None => return Err(error("color should be 6 hex digits".to_string())),
+                },
+                "spawn" => match parse_position(&value) {
+                    Some(position) => shape.spawn = position,
+                    None => return Err(error("spawn should be \"x, y\"".to_string())),
+                },
+                "pivot" => match parse_pivot(&value) {
+                    Some(pivot) => shape.pivot = Some(pivot),
+                    None => {
+                        return Err(error(
+                            "pivot should be \"x, y\" on a cell centre or corner".to_string(),
+                        ))
+                    }
+                },
+                _ => return Err(error(format!("unknown key \"{}\"", key))),
+            }
+        } else if line.chars().all(|c| c == 'X' || c == '.') {
+            if blocks.is_empty() {
+                return Err(error("drawing before \"piece = <name>\"".to_string()));
+            }
+            drawing.push(line);
+        } else {
+            return Err(error("drawings can only contain 'X' and '.'".to_string()));
+        }
+    }
 
-    let metal_t = Block {
-        label: Label::MetalT,
-        positions: vec![
-            Position {
-                y: 0,
-                x: 0 + start_offset,
-            },
-            Position {
-                y: 0,
-                x: 1 + start_offset,
-            },
-            Position {
-                y: 0,
-                x: 2 + start_offset,
-            },
-            Position {
-                y: 1,
-                x: 1 + start_offset,
-            },
-            Position {
-                y: 2,
-                x: 1 + start_offset,
-            },
-            Position {
-                y: 3,
-                x: 1 + start_offset,
-            },
-        ],
-        color: Color {
-            r: 120,
-            g: 120,
-            b: 120,
-        },
-    };
+    if !drawing.is_empty() {
+        if !shape.matrix.is_empty() {
+            return Err("only one drawing per piece, rotations are worked out".to_string());
+        }
+        shape.matrix = parse_matrix(&drawing)?;
+    }
+    match blocks.last_mut() {
+        Some(block) => finish_block(block, &shape)?,
+        None => return Err("no pieces defined".to_string()),
+    }
+    Ok(blocks)
+}
 
-    let metal_a = Block {
-        label: Label::MetalA,
-        positions: vec![
-            Position {
-                y: 0,
-                x: 1 + start_offset,
-            },
-            Position {
-                y: 1,
-                x: 0 + start_offset,
-            },
-            Position {
-                y: 1,
-                x: 2 + start_offset,
-            },
-            Position {
-                y: 2,
-                x: 0 + start_offset,
-            },
-            Position {
-                y: 2,
-                x: 1 + start_offset,
-            },
-            Position {
-                y: 2,
-                x: 2 + start_offset,
-            },
-            Position {
-                y: 3,
-                x: 0 + start_offset,
-            },
-            Position {
-                y: 3,
-                x: 2 + start_offset,
-            },
-        ],
-        color: Color {
-            r: 80,
-            g: 80,
-            b: 80,
-        },
-    };
+fn parse_position(value: &str) -> Option<Position> {
+    let mut parts = value.split(',').map(|v| v.trim().parse::<i32>());
+    match (parts.next(), parts.next(), parts.next()) {
+        (Some(Ok(x)), Some(Ok(y)), None) => Some(Position { x: x, y: y }),
+        _ => None,
+    }
+}
 
-    let metal_l = Block {
-        label: Label::MetalL,
-        positions: vec![
-            Position {
-                y: 0,
-                x: 0 + start_offset,
-            },
-            Position {
-                y: 1,
-                x: 0 + start_offset,
-            },
-            Position {
-                y: 2,
-                x: 0 + start_offset,
-            },
-            Position {
-                y: 3,
-                x: 0 + start_offset,
-            },
-            Position {
-                y: 3,
-                x: 1 + start_offset,
-            },
-            Position {
-                y: 3,
-                x: 2 + start_offset,
-            },
-        ],
-        color: Color {
-            r: 10,
-            g: 10,
-            b: 10,
-        },
+// Halves are allowed so the pivot can be on a cell corner.
+fn parse_pivot(value: &str) -> Option<Position> {
+    let mut parts = value.split(',').map(|v| v.trim().parse::<f32>());
+    let (x, y) = match (parts.next(), parts.next(), parts.next()) {
+        (Some(Ok(x)), Some(Ok(y)), None) => (x * 2. + 1., y * 2. + 1.),
+        _ => return None,
     };
+    if x.fract() != 0. || y.fract() != 0. || (x as i32 - y as i32) % 2 != 0 {
+        return None;
+    }
+    Some(Position {
+        x: x as i32,
+        y: y as i32,
+    })
+}
 
-    let classic_blocks = vec![
-        i.clone(),
-        t.clone(),
-        o.clone(),
-        s.clone(),
-        z.clone(),
-        j.clone(),
-        l.clone(),
-    ];
+fn parse_matrix(drawing: &[&str]) -> Result<Vec<Vec<bool>>, String> {
+    let width = drawing[0].len();
+    if drawing.iter().any(|row| row.len() != width) {
+        return Err("every row of a drawing needs the same width".to_string());
+    }
+    Ok(drawing
+        .iter()
+        .map(|row| row.chars().map(|c| c == 'X').collect())
+        .collect())
+}
 
-    let chill_blocks = vec![o.clone(), i.clone()];
+// Sets the block's orientations and spawn positions from its shape.
+fn finish_block(block: &mut Block, shape: &Shape) -> Result<(), String> {
+    let cells = matrix_cells(&shape.matrix);
+    if cells.is_empty() {
+        return Err(format!("{} has no drawing", block.label.name()));
+    }
+    let pivot = shape.pivot.unwrap_or_else(|| default_pivot(&shape.matrix));
 
-    let bane_blocks = vec![
-        bane_n.clone(),
-        bane_x.clone(),
-        bane_s.clone(),
-        bane_o.clone(),
-        bane_t.clone(),
-        bane_i.clone(),
-        bane_l.clone(),
-    ];
+    block.orientations = vec![cells.clone()];
+    let mut rotated = rotate_cells(&cells, pivot);
+    while !same_cells(&rotated, &cells) && block.orientations.len() < 4 {
+        block.orientations.push(rotated.clone());
+        rotated = rotate_cells(&rotated, pivot);
+    }
+    block.positions = cells
+        .iter()
+        .map(|p| Position {
+            x: p.x + shape.spawn.x,
+            y: p.y + shape.spawn.y,
+        })
+        .collect();
+    check_block(block, &shape.matrix, pivot)
+}
 
-    let metal_blocks = vec![
-        metal_m.clone(),
-        metal_e.clone(),
-        metal_t.clone(),
-        metal_a.clone(),
-        metal_l.clone(),
-    ];
+fn matrix_cells(matrix: &[Vec<bool>]) -> Vec<Position> {
+    matrix
+        .iter()
+        .enumerate()
+        .flat_map(|(y, row)| {
+            row.iter()
+                .enumerate()
+                .filter(|&(_, &filled)| filled)
+                .map(move |(x, _)| Position {
+                    x: x as i32,
+                    y: y as i32,
+                })
+        })
+        .collect()
+}
 
-    match mode {
-        Mode::Chill { .. } => chill_blocks
-            .choose(&mut rand::thread_rng())
-            .unwrap()
-            .clone(),
-        Mode::Classic { .. } => classic_blocks
-            .choose(&mut rand::thread_rng())
-            .unwrap()
-            .clone(),
-        Mode::Bane { .. } => bane_blocks.choose(&mut rand::thread_rng()).unwrap().clone(),
-        Mode::Metal { .. } => metal_blocks
-            .choose(&mut rand::thread_rng())
-            .unwrap()
-            .clone(),
+// The centre of the matrix, moved up and left onto a cell centre when it
+// is on neither a centre nor a corner.
+fn default_pivot(matrix: &[Vec<bool>]) -> Position {
+    let width = matrix.first().map_or(0, |row| row.len()) as i32;
+    let height = matrix.len() as i32;
+    if (width - height) % 2 == 0 {
+        return Position {
+            x: width,
+            y: height,
+        };
+    }
+    let to_centre = |v: i32| if v % 2 == 1 { v } else { v - 1 };
+    Position {
+        x: to_centre(width),
+        y: to_centre(height),
     }
 }
 
-// This method of rotating the block relys on the order of the positions
-// defined in the block. It also needs to ensure that after 4 rotations all
-// positions are returned to their origional position. An alternative solution
-// would be to use a 2d array to represent the blocks and rotate the array.
-fn rotation_vectors() -> RotationMap {
-    let mut vectors: RotationMap = HashMap::new();
+// Turns the cells a quarter clockwise around the pivot (in doubled
+// coordinates). With y pointing down (x, y) becomes (-y, x).
+fn rotate_cells(cells: &[Position], pivot: Position) -> Vec<Position> {
+    cells
+        .iter()
+        .map(|p| {
+            let dx = 2 * p.x + 1 - pivot.x;
+            let dy = 2 * p.y + 1 - pivot.y;
+            Position {
+                x: (pivot.x - dy - 1) / 2,
+                y: (pivot.y + dx - 1) / 2,
+            }
+        })
+        .collect()
+}
 
-    vectors.insert(
-        Label::T,
-        vec![
-            vec![
-                Delta { y: 0, x: 0 },
-                Delta { y: 0, x: 0 },
-                Delta { y: -1, x: -1 },
-                Delta { y: 0, x: 0 },
-            ],
-            vec![
-                Delta { y: 0, x: 0 },
-                Delta { y: 0, x: 0 },
-                Delta { y: 0, x: 0 },
-                Delta { y: -1, x: 1 },
-            ],
-            vec![
-                Delta { y: 1, x: 1 },
-                Delta { y: 0, x: 0 },
-                Delta { y: 0, x: 0 },
-                Delta { y: 0, x: 0 },
-            ],
-            vec![
-                Delta { y: -1, x: -1 },
-                Delta { y: 0, x: 0 },
-                Delta { y: 1, x: 1 },
-                Delta { y: 1, x: -1 },
-            ],
-        ],
-    );
+fn same_cells(a: &[Position], b: &[Position]) -> bool {
+    a.len() == b.len() && a.iter().all(|p| b.iter().any(|q| p.x == q.x && p.y == q.y))
+}
 
-    vectors.insert(
-        Label::I,
-        vec![
-            vec![
-                Delta { y: -2, x: 2 },
-                Delta { y: -1, x: 1 },
-                Delta { y: 0, x: 0 },
-                Delta { y: 1, x: -1 },
-            ],
-            vec![
-                Delta { y: 2, x: -2 },
-                Delta { y: 1, x: -1 },
-                Delta { y: 0, x: 0 },
-                Delta { y: -1, x: 1 },
-            ],
-        ],
-    );
+// Catches blocks that can't be played as drawn. Four turns have to bring
+// a block back to its drawing, or it would wander as it turns. Blocks
+// spawn in every orientation's columns as they turn, so none may reach
+// past the left edge of the board. The rows above the board are fine,
+// the block turns once it has fallen far enough.
+fn check_block(block: &Block, matrix: &[Vec<bool>], pivot: Position) -> Result<(), String> {
+    let name = block.label.name();
+    let cells = matrix_cells(matrix);
+    let turned = (0..4).fold(cells.clone(), |turned, _| rotate_cells(&turned, pivot));
+    if !same_cells(&turned, &cells) {
+        return Err(format!(
+            "{} doesn't turn back to its drawing in four turns",
+            name
+        ));
+    }
+    if !is_connected(&cells) {
+        return Err(format!("{}'s drawing is in more than one piece", name));
+    }
+    let spawn = spawn_corner(block);
+    let leftmost = block
+        .orientations
+        .iter()
+        .flatten()
+        .map(|p| p.x + spawn.x)
+        .min()
+        .unwrap_or(0);
+    if leftmost < 0 {
+        return Err(format!(
+            "{} turns past the left edge of the board, spawn it {} further right",
+            name, -leftmost
+        ));
+    }
+    Ok(())
+}
 
-    vectors.insert(
-        Label::L,
-        vec![
-            vec![
-                Delta { y: -1, x: 0 },
-                Delta { y: 0, x: 0 },
-                Delta { y: -1, x: -1 },
-                Delta { y: 0, x: 1 },
-            ],
-            vec![
-                Delta { y: 1, x: 0 },
-                Delta { y: 0, x: 0 },
-                Delta { y: 0, x: 1 },
-                Delta { y: -1, x: 1 },
-            ],
-            vec![
-                Delta { y: 1, x: 1 },
-                Delta { y: 0, x: 0 },
-                Delta { y: 0, x: -1 },
-                Delta { y: 1, x: 0 },
-            ],
-            vec![
-                Delta { y: -1, x: -1 },
-                Delta { y: 0, x: 0 },
-                Delta { y: 1, x: 1 },
-                Delta { y: 0, x: -2 },
-            ],
-        ],
-    );
+// Where the top left corner of the block's drawing is when it spawns.
+fn spawn_corner(block: &Block) -> Position {
+    let corner = min_corner(&block.positions);
+    let drawing = min_corner(&block.orientations[0]);
+    Position {
+        x: corner.x - drawing.x,
+        y: corner.y - drawing.y,
+    }
+}
 
-    vectors.insert(
-        Label::J,
-        vec![
-            vec![
-                Delta { y: -1, x: 1 },
-                Delta { y: 0, x: 0 },
-                Delta { y: 1, x: -1 },
-                Delta { y: 0, x: -2 },
-            ],
-            vec![
-                Delta { y: 0, x: -1 },
-                Delta { y: 0, x: 0 },
-                Delta { y: -1, x: 1 },
-                Delta { y: -1, x: 0 },
-            ],
-            vec![
-                Delta { y: 0, x: 1 },
-                Delta { y: 0, x: 0 },
-                Delta { y: -1, x: 0 },
-                Delta { y: 1, x: 1 },
-            ],
-            vec![
-                Delta { y: 1, x: -1 },
-                Delta { y: 0, x: 0 },
-                Delta { y: 1, x: 0 },
-                Delta { y: 0, x: 1 },
-            ],
-        ],
-    );
+// True if every cell can be reached from the first through cells that
+// touch, at a side or a corner as the top of Metal's A does.
+fn is_connected(cells: &[Position]) -> bool {
+    let mut reached = vec![false; cells.len()];
+    let mut todo = vec![0];
+    while let Some(i) = todo.pop() {
+        if reached[i] {
+            continue;
+        }
+        reached[i] = true;
+        let p = cells[i];
+        cells
+            .iter()
+            .enumerate()
+            .filter(|(_, q)| (p.x - q.x).abs().max((p.y - q.y).abs()) == 1)
+            .for_each(|(j, _)| todo.push(j));
+    }
+    reached.iter().all(|&r| r)
+}
 
-    vectors.insert(
-        Label::S,
-        vec![
-            vec![
-                Delta { y: 0, x: 2 },
-                Delta { y: -2, x: 0 },
-                Delta { y: 0, x: 0 },
-                Delta { y: 0, x: 0 },
-            ],
-            vec![
-                Delta { y: 0, x: -2 },
-                Delta { y: 2, x: 0 },
-                Delta { y: 0, x: 0 },
-                Delta { y: 0, x: 0 },
-            ],
-        ],
-    );
-    vectors.insert(
-        Label::Z,
-        vec![
-            vec![
-                Delta { y: 2, x: 1 },
-                Delta { y: 0, x: 1 },
-                Delta { y: 0, x: 0 },
-                Delta { y: 0, x: 0 },
-            ],
-            vec![
-                Delta { y: -2, x: -1 },
-                Delta { y: 0, x: -1 },
-                Delta { y: 0, x: 0 },
-                Delta { y: 0, x: 0 },
-            ],
-        ],
-    );
+// The columns and rows of the board the blocks need, spawning and
+// turning in any orientation.
+pub fn spawn_area(blocks: &[Block]) -> Position {
+    let cells = blocks.iter().flat_map(|block| {
+        let spawn = spawn_corner(block);
+        block
+            .orientations
+            .iter()
+            .flatten()
+            .map(move |p| *p + spawn)
+            .chain(block.positions.iter().copied())
+    });
+    cells.fold(Position { x: 0, y: 0 }, |area, p| Position {
+        x: area.x.max(p.x + 1),
+        y: area.y.max(p.y + 1),
+    })
+}
 
-    vectors.insert(
-        Label::BaneT,
-        vec![
-            vec![
-                Delta { y: 0, x: 0 },
-                Delta { y: 1, x: -1 },
-                Delta { y: 1, x: 0 },
-                Delta { y: 0, x: 0 },
-                Delta { y: 0, x: -1 },
-            ],
-            vec![
-                Delta { y: 0, x: 1 },
-                Delta { y: 1, x: 1 },
-                Delta { y: 1, x: 0 },
-                Delta { y: 0, x: 0 },
-                Delta { y: 0, x: 0 },
-            ],
-            vec![
-                Delta { y: 0, x: 1 },
-                Delta { y: -1, x: 1 },
-                Delta { y: 0, x: 0 },
-                Delta { y: 0, x: 0 },
-                Delta { y: -1, x: 0 },
-            ],
-            vec![
-                Delta { y: 0, x: -2 },
-                Delta { y: -1, x: -1 },
-                Delta { y: -2, x: 0 },
-                Delta { y: 0, x: 0 },
-                Delta { y: 1, x: 1 },
-            ],
-        ],
-    );
-    vectors.insert(
-        Label::BaneS,
-        vec![
-            vec![
-                Delta { y: 0, x: 2 },
-                Delta { y: 1, x: 1 },
-                Delta { y: 0, x: 0 },
-                Delta { y: 1, x: -1 },
-                Delta { y: 0, x: -2 },
-            ],
-            vec![
-                Delta { y: -2, x: 0 },
-                Delta { y: -1, x: 1 },
-                Delta { y: 0, x: 0 },
-                Delta { y: 1, x: 1 },
-                Delta { y: 2, x: 0 },
-            ],
-            vec![
-                Delta { y: 0, x: -2 },
-                Delta { y: -1, x: -1 },
-                Delta { y: 0, x: 0 },
-                Delta { y: -1, x: 1 },
-                Delta { y: 0, x: 2 },
-            ],
-            vec![
-                Delta { y: 2, x: 0 },
-                Delta { y: 1, x: -1 },
-                Delta { y: 0, x: 0 },
-                Delta { y: -1, x: -1 },
-                Delta { y: -2, x: 0 },
-            ],
-        ],
-    );
+pub fn next_orientation(block: &Block, orientation: u8) -> u8 {
+    ((orientation as usize + 1) % block.orientations.len()) as u8
+}
 
-    vectors.insert(
-        Label::BaneI,
-        vec![
-            vec![
-                Delta { y: -2, x: 2 },
-                Delta { y: -1, x: 1 },
-                Delta { y: 0, x: 0 },
-                Delta { y: 1, x: -1 },
-                Delta { y: 2, x: -2 },
-            ],
-            vec![
-                Delta { y: 2, x: -2 },
-                Delta { y: 1, x: -1 },
-                Delta { y: 0, x: 0 },
-                Delta { y: -1, x: 1 },
-                Delta { y: -2, x: 2 },
-            ],
-        ],
-    );
-    vectors.insert(
-        Label::BaneL,
-        vec![
-            vec![
-                Delta { y: 0, x: 0 },
-                Delta { y: 2, x: 0 },
-                Delta { y: 2, x: 0 },
-                Delta { y: 0, x: 0 },
-                Delta { y: 0, x: 0 },
-            ],
-            vec![
-                Delta { y: 0, x: 2 },
-                Delta { y: 0, x: 0 },
-                Delta { y: 0, x: 0 },
-                Delta { y: 0, x: 2 },
-                Delta { y: 0, x: 0 },
-            ],
-            vec![
-                Delta { y: 0, x: 0 },
-                Delta { y: -2, x: 0 },
-                Delta { y: 0, x: 0 },
-                Delta { y: 0, x: 0 },
-                Delta { y: -2, x: 0 },
-            ],
-            vec![
-                Delta { y: 0, x: -2 },
-                Delta { y: 0, x: 0 },
-                Delta { y: -2, x: 0 },
-                Delta { y: 0, x: -2 },
-                Delta { y: 2, x: 0 },
-            ],
-        ],
-    );
-    vectors.insert(
-        Label::BaneN,
-        vec![
-            vec![
-                Delta { y: 1, x: 1 },
-                Delta { y: 0, x: 0 },
-                Delta { y: -1, x: -1 },
-                Delta { y: 0, x: 2 },
-                Delta { y: -2, x: 0 },
-            ],
-            vec![
-                Delta { y: -1, x: 1 },
-                Delta { y: 0, x: 0 },
-                Delta { y: 1, x: -1 },
-                Delta { y: -2, x: 0 },
-                Delta { y: 0, x: -2 },
-            ],
-            vec![
-                Delta { y: -1, x: -1 },
-                Delta { y: 0, x: 0 },
-                Delta { y: 1, x: 1 },
-                Delta { y: 0, x: -2 },
-                Delta { y: 2, x: 0 },
-            ],
-            vec![
-                Delta { y: 1, x: -1 },
-                Delta { y: 0, x: 0 },
-                Delta { y: -1, x: 1 },
-                Delta { y: 2, x: 0 },
-                Delta { y: 0, x: 2 },
-            ],
-        ],
-    );
-    vectors.insert(
-        Label::MetalM,
-        vec![
-            vec![
-                Delta { y: 2, x: 0 },
-                Delta { y: 0, x: -2 },
-                Delta { y: 1, x: 1 },
-                Delta { y: 0, x: 0 },
-                Delta { y: -1, x: -1 },
-                Delta { y: 0, x: 2 },
-                Delta { y: -2, x: 0 },
-                Delta { y: -1, x: 3 },
-                Delta { y: -3, x: 1 },
-            ],
-            vec![
-                Delta { y: 0, x: 2 },
-                Delta { y: 2, x: 0 },
-                Delta { y: -1, x: 1 },
-                Delta { y: 0, x: 0 },
-                Delta { y: 1, x: -1 },
-                Delta { y: -2, x: 0 },
-                Delta { y: 0, x: -2 },
-                Delta { y: -3, x: -1 },
-                Delta { y: -1, x: -3 },
-            ],
-            vec![
-                Delta { y: -2, x: 0 },
-                Delta { y: 0, x: 2 },
-                Delta { y: -1, x: -1 },
-                Delta { y: 0, x: 0 },
-                Delta { y: 1, x: 1 },
-                Delta { y: 0, x: -2 },
-                Delta { y: 2, x: 0 },
-                Delta { y: 1, x: -3 },
-                Delta { y: 3, x: -1 },
-            ],
-            vec![
-                Delta { y: 0, x: -2 },
-                Delta { y: -2, x: 0 },
-                Delta { y: 1, x: -1 },
-                Delta { y: 0, x: 0 },
-                Delta { y: -1, x: 1 },
-                Delta { y: 2, x: 0 },
-                Delta { y: 0, x: 2 },
-                Delta { y: 3, x: 1 },
-                Delta { y: 1, x: 3 },
-            ],
-        ],
-    );
+pub fn orientation_count(block: &Block) -> usize {
+    block.orientations.len()
+}
+
+// Top left corner of the smallest box containing the positions.
+fn min_corner(positions: &[Position]) -> Position {
+    Position {
+        x: positions.iter().map(|p| p.x).min().unwrap_or(0),
+        y: positions.iter().map(|p| p.y).min().unwrap_or(0),
+    }
+}
+
+// Returns a new set of positions for the rotated block
+pub fn rotate_block(block: &Block, orientation: u8) -> Vec<Position> {
+    let current = &block.orientations[orientation as usize % block.orientations.len()];
+    let next = &block.orientations[next_orientation(block, orientation) as usize];
+
+    // Where the shared corner of the drawings is on the board.
+    let block_corner = min_corner(&block.positions);
+    let drawing_corner = min_corner(current);
+
+    next.iter()
+        .map(|p| Position {
+            x: p.x + block_corner.x - drawing_corner.x,
+            y: p.y + block_corner.y - drawing_corner.y,
+        })
+        .collect()
+}
 
-    vectors.insert(
-        Label::MetalE,
-        vec![
-            vec![
-                Delta { y: 2, x: 0 },
-                Delta { y: 1, x: -1 },
-                Delta { y: 0, x: -2 },
-                Delta { y: 1, x: 1 },
-                Delta { y: 0, x: 0 },
-                Delta { y: 0, x: 2 },
-                Delta { y: -1, x: 3 },
-                Delta { y: -2, x: 2 },
-                Delta { y: -3, x: 1 },
-            ],
-            vec![
-                Delta { y: 0, x: 2 },
-                Delta { y: 1, x: 1 },
-                Delta { y: 2, x: 0 },
-                Delta { y: -1, x: 1 },
-                Delta { y: 0, x: 0 },
-                Delta { y: -2, x: 0 },
-                Delta { y: -3, x: -1 },
-                Delta { y: -2, x: -2 },
-                Delta { y: -1, x: -3 },
-            ],
-            vec![
-                Delta { y: -2, x: 0 },
-                Delta { y: -1, x: 1 },
-                Delta { y: 0, x: 2 },
-                Delta { y: -1, x: -1 },
-                Delta { y: 0, x: 0 },
-                Delta { y: 0, x: -2 },
-                Delta { y: 1, x: -3 },
-                Delta { y: 2, x: -2 },
-                Delta { y: 3, x: -1 },
-            ],
-            vec![
-                Delta { y: 0, x: -2 },
-                Delta { y: -1, x: -1 },
-                Delta { y: -2, x: 0 },
-                Delta { y: 1, x: -1 },
-                Delta { y: 0, x: 0 },
-                Delta { y: 2, x: 0 },
-                Delta { y: 3, x: 1 },
-                Delta { y: 2, x: 2 },
-                Delta { y: 1, x: 3 },
-            ],
-        ],
-    );
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const T_PIECE: &str = "
+# A comment
+piece = T
+color = 008c36
+spawn = 3, 0
+
+XXX
+.X.
+";
+
+    #[test]
+    fn parses_a_piece() {
+        let blocks = parse_blocks(T_PIECE).unwrap();
+        assert_eq!(blocks.len(), 1);
+        let t = &blocks[0];
+        assert_eq!(t.label.name(), "T");
+        assert!(t.color == Color::from_hex("008c36").unwrap());
+        let spawned: Vec<(i32, i32)> = t.positions.iter().map(|p| (p.x, p.y)).collect();
+        assert_eq!(spawned, vec![(3, 0), (4, 0), (5, 0), (4, 1)]);
+        assert_eq!(orientation_count(t), 4);
+    }
 
-    vectors.insert(
-        Label::MetalT,
-        vec![
-            vec![
-                Delta { y: 2, x: 0 },
-                Delta { y: 1, x: -1 },
-                Delta { y: 0, x: -2 },
-                Delta { y: 0, x: 0 },
-                Delta { y: -1, x: 1 },
-                Delta { y: -2, x: 2 },
-            ],
-            vec![
-                Delta { y: 0, x: 2 },
-                Delta { y: 1, x: 1 },
-                Delta { y: 2, x: 0 },
-                Delta { y: 0, x: 0 },
-                Delta { y: -1, x: -1 },
-                Delta { y: -2, x: -2 },
-            ],
-            vec![
-                Delta { y: -2, x: 0 },
-                Delta { y: -1, x: 1 },
-                Delta { y: 0, x: 2 },
-                Delta { y: 0, x: 0 },
-                Delta { y: 1, x: -1 },
-                Delta { y: 2, x: -2 },
-            ],
-            vec![
-                Delta { y: 0, x: -2 },
-                Delta { y: -1, x: -1 },
-                Delta { y: -2, x: 0 },
-                Delta { y: 0, x: 0 },
-                Delta { y: 1, x: 1 },
-                Delta { y: 2, x: 2 },
-            ],
-        ],
-    );
+    #[test]
+    fn symmetric_pieces_have_fewer_orientations() {
+        let o = parse_blocks("piece = O\ncolor = 00c3da\nXX\nXX\n").unwrap();
+        assert_eq!(orientation_count(&o[0]), 1);
+        let line = parse_blocks("piece = I3\ncolor = cd1f48\nXXX\n").unwrap();
+        assert_eq!(orientation_count(&line[0]), 2);
+    }
 
-    vectors.insert(
-        Label::MetalA,
-        vec![
-            vec![
-                Delta { y: 2, x: -2 },
-                Delta { y: 2, x: 0 },
-                Delta { y: 0, x: -2 },
-                Delta { y: 1, x: 1 },
-                Delta { y: 0, x: 0 },
-                Delta { y: -1, x: -1 },
-                Delta { y: 0, x: 2 },
-                Delta { y: -2, x: 0 },
-            ],
-            vec![
-                Delta { y: 2, x: 2 },
-                Delta { y: 0, x: 2 },
-                Delta { y: 2, x: 0 },
-                Delta { y: -1, x: 1 },
-                Delta { y: 0, x: 0 },
-                Delta { y: 1, x: -1 },
-                Delta { y: -2, x: 0 },
-                Delta { y: 0, x: -2 },
-            ],
-            vec![
-                Delta { y: -2, x: 2 },
-                Delta { y: -2, x: 0 },
-                Delta { y: 0, x: 2 },
-                Delta { y: -1, x: -1 },
-                Delta { y: 0, x: 0 },
-                Delta { y: 1, x: 1 },
-                Delta { y: 0, x: -2 },
-                Delta { y: 2, x: 0 },
-            ],
-            vec![
-                Delta { y: -2, x: -2 },
-                Delta { y: 0, x: -2 },
-                Delta { y: -2, x: 0 },
-                Delta { y: 1, x: -1 },
-                Delta { y: 0, x: 0 },
-                Delta { y: -1, x: 1 },
-                Delta { y: 2, x: 0 },
-                Delta { y: 0, x: 2 },
-            ],
-        ],
-    );
+    #[test]
+    fn errors_name_the_line() {
+        let error = parse_blocks("piece = T\ncolor = green\n").err().unwrap();
+        assert!(error.starts_with("line 2:"), "{}", error);
+        let error = parse_blocks("color = 008c36\n").err().unwrap();
+        assert!(error.starts_with("line 1:"), "{}", error);
+        let error = parse_blocks("piece = T\nXXX\nXX\n").err().unwrap();
+        assert!(error.contains("same width"), "{}", error);
+        let error = parse_blocks("piece = T\nXOX\n").err().unwrap();
+        assert!(error.contains("'X' and '.'"), "{}", error);
+        let error = parse_blocks("piece = T\ncolor = 008c36\n").err().unwrap();
+        assert!(error.contains("no drawing"), "{}", error);
+        assert!(parse_blocks("# nothing\n").is_err());
+    }
 
-    vectors.insert(
-        Label::MetalL,
-        vec![
-            vec![
-                Delta { y: 3, x: -1 },
-                Delta { y: 2, x: 0 },
-                Delta { y: 1, x: 1 },
-                Delta { y: 0, x: 2 },
-                Delta { y: -1, x: 1 },
-                Delta { y: -2, x: 0 },
-            ],
-            vec![
-                Delta { y: 1, x: 3 },
-                Delta { y: 0, x: 2 },
-                Delta { y: -1, x: 1 },
-                Delta { y: -2, x: 0 },
-                Delta { y: -1, x: -1 },
-                Delta { y: 0, x: -2 },
-            ],
-            vec![
-                Delta { y: -3, x: 1 },
-                Delta { y: -2, x: 0 },
-                Delta { y: -1, x: -1 },
-                Delta { y: 0, x: -2 },
-                Delta { y: 1, x: -1 },
-                Delta { y: 2, x: 0 },
-            ],
-            vec![
-                Delta { y: -1, x: -3 },
-                Delta { y: 0, x: -2 },
-                Delta { y: 1, x: -1 },
-                Delta { y: 2, x: 0 },
-                Delta { y: 1, x: 1 },
-                Delta { y: 0, x: 2 },
-            ],
-        ],
-    );
+    #[test]
+    fn rejects_unplayable_pieces() {
+        let error = parse_blocks("piece = U\nX.X\nX.X\n").err().unwrap();
+        assert!(error.contains("more than one piece"), "{}", error);
+        let error = parse_blocks("piece = L\nspawn = 0, 0\npivot = 0.5, 0.5\nXXX\n")
+            .err()
+            .unwrap();
+        assert!(error.contains("left edge"), "{}", error);
+        let error = parse_blocks("piece = I\npivot = 1, 0.5\nXXXX\n")
+            .err()
+            .unwrap();
+        assert!(error.contains("pivot"), "{}", error);
+        // Moved right far enough it's fine.
+        assert!(parse_blocks("piece = L\nspawn = 2, 0\npivot = 0.5, 0.5\nXXX\n").is_ok());
+    }
 
-    vectors
-}
+    #[test]
+    fn spawn_area_covers_every_orientation() {
+        let blocks = parse_blocks("piece = L\nspawn = 2, 0\npivot = 0.5, 0.5\nXXX\n").unwrap();
+        assert_eq!(spawn_area(&blocks).x, 5);
+        let blocks = parse_blocks("piece = I\nspawn = 1, 3\npivot = 0.5, 0.5\nX\nX\nX\n").unwrap();
+        let area = spawn_area(&blocks);
+        assert_eq!((area.x, area.y), (4, 6));
+    }
 
-// Returns a new set of positions for the rotated block
-pub fn rotate_block(block: &Block, orientation: u8) -> Vec<Position> {
-    let mut orientation = orientation;
-    let rotation_map: RotationMap = rotation_vectors();
+    #[test]
+    fn built_in_pieces_load() {
+        for pieces in &["bane", "chill", "classic", "metal"] {
+            let path = config::asset_path(PIECES_DIR).join(format!("{}.txt", pieces));
+            assert!(load_blocks_file(&path).is_ok(), "{}", pieces);
+        }
+    }
 
-    // special cases
-    match block.label {
-        // I, S and Z blocks only have 2 orientations
-        Label::I | Label::S | Label::Z | Label::BaneI => orientation %= 2,
-        // No point rotating the O, baneO, baneX square blocks.
-        Label::O | Label::BaneO | Label::BaneX => return block.positions.clone(),
-        _ => {}
+    #[test]
+    fn built_in_pieces_turn_back_in_four_turns() {
+        for pieces in &["bane", "chill", "classic", "metal"] {
+            let path = config::asset_path(PIECES_DIR).join(format!("{}.txt", pieces));
+            for mut block in load_blocks_file(&path).unwrap() {
+                let start = block.positions.clone();
+                for orientation in 0..4 {
+                    block.positions = rotate_block(&block, orientation);
+                }
+                assert!(
+                    same_cells(&block.positions, &start),
+                    "{} in {}",
+                    block.label.name(),
+                    pieces
+                );
+            }
+        }
     }
 
-    block
-        .positions
-        .iter()
-        .enumerate()
-        .map(|(i, &p)| p + rotation_map[&block.label][orientation as usize][i])
-        .collect()
+    #[test]
+    fn colors_round_trip() {
+        let color = Color {
+            r: 205,
+            g: 31,
+            b: 72,
+        };
+        assert_eq!(color.to_hex(), "cd1f48");
+        assert!(Color::from_hex(&color.to_hex()) == Some(color));
+        assert!(Color::from_hex("cd1f4").is_none());
+        assert!(Color::from_hex("cd1f4g").is_none());
+    }
 }
@@ -0,0 +1,479 @@
+/*
+
+Programs outside the game can play it over a socket, e.g. bots being
+trained or tested, turned on with the --bot command line flag, see
+main.rs. One bot at a time connects to the TCP port and plays the
+single player game, the game started with its start action or from the
+menu. While it plays the keyboard can only pause the game.
+
+The bot sends actions as lines of text, one played each frame:
+
+    left, right, rotate        move the block in play
+    drop, release              hold and let go of soft drop
+    wait                       do nothing for a frame
+    place 1 4                  turn the block to the orientation and
+                               move it to the leftmost column given,
+                               then drop it, taking as many frames as
+                               needed
+    start 1234 Sprint          a new game dealt from the seed, in the
+                               mode named or the menu's mode
+
+Games started by the bot skip the countdown and show the next block.
+
+The game sends observations as lines of JSON, the format of the
+spectator stream, see spectator.rs:
+
+    {"state":"Play","mode":"Classic","score":12,"lines":3,"level":1,
+     "pieces":9,"time_played_ms":40123,
+     "block":{"name":"T","color":"a000f0",
+              "cells":[[4,1],[5,1],[6,1],[5,2]]},
+     "orientation":0,
+     "next":{"name":"I",...},
+     "placements":[[0,0],[0,1],...,[1,8]],
+     "board":[[null,"ff0000",...],...]}
+
+next is null without a preview, and placements are the orientations
+and columns place can take the block to. Off the game, e.g. in the
+menu, only the state is sent. Actions that can't be read or played are
+answered with {"error":"..."}.
+
+An observation is sent after every action is played, and otherwise
+whenever what it shows changes, apart from the time played. Placements
+are answered once the block has locked.
+
+With --lock-step the game waits for the bot: time only passes during
+play when the bot acts, a frame for each action, so a slow bot plays
+the same as a fast one. Each frame is a sixtieth of a second of the
+game. If the bot goes the game carries on in real time.
+
+*/
+
+use std::collections::VecDeque;
+use std::io::{ErrorKind, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::time;
+
+use crate::cpu;
+use crate::game;
+use crate::gym;
+use crate::mode;
+use crate::sound;
+use crate::spectator::{block_json, board_json, json_string, state_name};
+use crate::stats;
+
+pub const DEFAULT_PORT: u16 = 7779;
+// A bot is dropped once this much text is waiting for the end of an
+// action, or for the bot to read it. Observations are a few KB.
+const MAX_RECEIVED: usize = 1 << 20;
+const MAX_UNSENT: usize = 1 << 20;
+
+pub enum Action {
+    Input(game::Input),
+    Wait,
+    Place(cpu::Target),
+    Start { seed: u64, mode: Option<String> },
+}
+
+struct Connection {
+    stream: TcpStream,
+    // Text of an action that hasn't fully arrived yet.
+    received: String,
+    // Bytes the bot wasn't ready to take yet.
+    unsent: Vec<u8>,
+}
+
+pub struct Bot {
+    listener: TcpListener,
+    connection: Option<Connection>,
+    lock_step: bool,
+    actions: VecDeque<Action>, // received and waiting for their frame
+    placing: Option<cpu::Target>,
+    pieces: u32,    // dealt when the placement was asked for
+    dropping: bool, // soft drop is held for the placement
+    // An action was played since the last observation.
+    acted: bool,
+    // The last observation sent, without the time played.
+    last_observation: String,
+}
+
+// Listens on "port" or "address:port".
+pub fn listen(address: &str, lock_step: bool) -> Result<Bot, String> {
+    let address = match address.parse::<u16>() {
+        Ok(port) => format!("127.0.0.1:{}", port),
+        Err(_) => address.to_string(),
+    };
+    let listener = TcpListener::bind(&address)
+        .map_err(|error| format!("Can't listen for bots on {}: {}", address, error))?;
+    listener
+        .set_nonblocking(true)
+        .map_err(|error| error.to_string())?;
+    Ok(Bot {
+        listener: listener,
+        connection: None,
+        lock_step: lock_step,
+        actions: VecDeque::new(),
+        placing: None,
+        pieces: 0,
+        dropping: false,
+        acted: false,
+        last_observation: "".to_string(),
+    })
+}
+
+fn accept(bot: &mut Bot) {
+    while let Ok((stream, _)) = bot.listener.accept() {
+        let opened = stream.set_nonblocking(true).is_ok() && stream.set_nodelay(true).is_ok();
+        let mut connection = Connection {
+            stream: stream,
+            received: "".to_string(),
+            unsent: vec![],
+        };
+        if bot.connection.is_some() {
+            send(&mut connection, &error_json("Another bot is playing"));
+        } else if opened {
+            bot.connection = Some(connection);
+            bot.actions.clear();
+            bot.placing = None;
+            // The new bot is sent what it sees straight away.
+            bot.last_observation = "".to_string();
+        }
+    }
+}
+
+// Returns false once the bot has gone.
+fn send(connection: &mut Connection, line: &str) -> bool {
+    connection.unsent.extend(line.bytes());
+    connection.unsent.push(b'\n');
+    while !connection.unsent.is_empty() {
+        match connection.stream.write(&connection.unsent) {
+            Ok(0) => return false,
+            Ok(n) => {
+                connection.unsent.drain(..n);
+            }
+            Err(error) if error.kind() == ErrorKind::WouldBlock => {
+                return connection.unsent.len() < MAX_UNSENT;
+            }
+            Err(_) => return false,
+        }
+    }
+    true
+}
+
+// The lines that have arrived since the last call, None once the bot
+// has gone or sent a line too long to be an action.
+fn receive(connection: &mut Connection) -> Option<Vec<String>> {
+    let mut buffer = [0; 4096];
+    // The rest is read next time, once the lines so far are taken.
+    while connection.received.len() <= MAX_RECEIVED {
+        match connection.stream.read(&mut buffer) {
+            Ok(0) => return None,
+            Ok(n) => connection
+                .received
+                .push_str(&String::from_utf8_lossy(&buffer[..n])),
+            Err(error) if error.kind() == ErrorKind::WouldBlock => break,
+            Err(_) => return None,
+        }
+    }
+    let mut lines = vec![];
+    while let Some(end) = connection.received.find('\n') {
+        let line: String = connection.received.drain(..=end).collect();
+        lines.push(line.trim().to_string());
+    }
+    if connection.received.len() > MAX_RECEIVED {
+        return None;
+    }
+    Some(lines)
+}
+
+pub fn parse_action(line: &str) -> Result<Action, String> {
+    let error = || format!("Can't read action: {}", line);
+    let words: Vec<&str> = line.split_whitespace().collect();
+    let number = |i: usize| -> Result<i64, String> {
+        words
+            .get(i)
+            .and_then(|word| word.parse().ok())
+            .ok_or_else(error)
+    };
+    match words.first().copied() {
+        Some("left") => Ok(Action::Input(game::Input::LeftKeyDown)),
+        Some("right") => Ok(Action::Input(game::Input::RightKeyDown)),
+        Some("rotate") => Ok(Action::Input(game::Input::UpKeyDown)),
+        Some("drop") => Ok(Action::Input(game::Input::DownKeyDown)),
+        Some("release") => Ok(Action::Input(game::Input::DownKeyUp)),
+        Some("wait") => Ok(Action::Wait),
+        Some("place") => Ok(Action::Place(cpu::Target {
+            orientation: number(1)? as u8,
+            x: number(2)? as i32,
+        })),
+        Some("start") => Ok(Action::Start {
+            seed: words
+                .get(1)
+                .and_then(|word| word.parse().ok())
+                .ok_or_else(error)?,
+            mode: match words.len() {
+                2 => None,
+                _ => Some(words[2..].join(" ")),
+            },
+        }),
+        _ => Err(error()),
+    }
+}
+
+// A new game in the named mode, or the menu's mode if there's no name.
+fn start(
+    bot: &Bot,
+    world: &mut game::World,
+    seed: u64,
+    name: &Option<String>,
+) -> Result<(), String> {
+    let modes = mode::initialise_modes();
+    let mode_selected = world.game.mode_selected;
+    let mode = match name {
+        Some(name) => modes
+            .iter()
+            .find(|mode| mode.label().eq_ignore_ascii_case(name))
+            .cloned()
+            .ok_or(format!("There's no mode called {}", name))?,
+        None => modes[mode_selected].clone(),
+    };
+    let mut game = gym::new_game(modes, mode_selected, mode, seed);
+    if bot.lock_step {
+        game::advance_clock(&mut game, time::Duration::from_millis(0));
+    }
+    world.music_file = sound::music_path(&game.mode);
+    world.ghost = None;
+    world.game = game;
+    world.state = game::State::Play;
+    Ok(())
+}
+
+// The input played this frame, the bot's while it's playing, otherwise
+// the keyboard's. Called before the frame is updated. In lock step the
+// game's clock is also moved on, unless play is waiting for the bot.
+pub fn update(
+    bot: &mut Bot,
+    event: &Option<game::Input>,
+    world: &mut game::World,
+) -> Option<game::Input> {
+    accept(bot);
+    let connection = match &mut bot.connection {
+        Some(connection) => connection,
+        // Without a bot nothing would move the clock on, e.g. once
+        // observe has dropped one that stopped reading.
+        None => {
+            game::release_clock(&mut world.game);
+            return event.clone();
+        }
+    };
+    match receive(connection) {
+        Some(lines) => {
+            for line in lines.iter().filter(|line| !line.is_empty()) {
+                match parse_action(line) {
+                    Ok(action) => bot.actions.push_back(action),
+                    Err(error) => {
+                        send(connection, &error_json(&error));
+                    }
+                }
+            }
+        }
+        None => {
+            bot.connection = None;
+            // Nothing would move the clock on for whoever is left.
+            game::release_clock(&mut world.game);
+            return event.clone();
+        }
+    }
+
+    let playing = world.state == game::State::Play;
+    // Only pausing is left to the keyboard.
+    if playing {
+        if let Some(game::Input::EscKeyDown) | Some(game::Input::PKeyDown) = event {
+            return event.clone();
+        }
+    }
+    let (input, waiting) = next_input(bot, world);
+    if bot.lock_step {
+        let frame = match playing && waiting {
+            true => time::Duration::from_millis(0),
+            false => game::FRAME,
+        };
+        game::advance_clock(&mut world.game, frame);
+    }
+    match playing {
+        true => input,
+        false => event.clone(),
+    }
+}
+
+// The bot's input this frame, and whether it has nothing to play.
+fn next_input(bot: &mut Bot, world: &mut game::World) -> (Option<game::Input>, bool) {
+    let playing = world.state == game::State::Play;
+    if let Some(target) = bot.placing {
+        if playing && stats::pieces(&world.game.stats) == bot.pieces {
+            return (cpu::steer(&world.game, target, &mut bot.dropping), false);
+        }
+        bot.placing = None;
+        bot.acted = true;
+        // Let go of soft drop so the next block isn't rushed.
+        if bot.dropping {
+            bot.dropping = false;
+            return (Some(game::Input::DownKeyUp), false);
+        }
+    }
+
+    let action = match bot.actions.pop_front() {
+        Some(action) => action,
+        None => return (None, true),
+    };
+    let result = match action {
+        Action::Start { seed, mode } => start(bot, world, seed, &mode).map(|_| None),
+        _ if !playing => Err("No game is being played".to_string()),
+        Action::Input(input) => Ok(Some(input)),
+        Action::Wait => Ok(None),
+        Action::Place(target) => match cpu::targets(&world.game).contains(&target) {
+            true => {
+                bot.placing = Some(target);
+                bot.pieces = stats::pieces(&world.game.stats);
+                bot.dropping = false;
+                Ok(cpu::steer(&world.game, target, &mut bot.dropping))
+            }
+            false => Err(format!(
+                "The block can't be placed in orientation {} at column {}",
+                target.orientation, target.x
+            )),
+        },
+    };
+    match result {
+        Ok(input) => {
+            bot.acted = true;
+            (input, false)
+        }
+        Err(error) => {
+            if let Some(connection) = &mut bot.connection {
+                send(connection, &error_json(&error));
+            }
+            // A wasted action still takes its frame.
+            (None, false)
+        }
+    }
+}
+
+// Sends the bot what it sees, called after the frame is updated.
+pub fn observe(bot: &mut Bot, world: &game::World) {
+    let connection = match &mut bot.connection {
+        Some(connection) => connection,
+        None => return,
+    };
+    if bot.placing.is_some() {
+        return;
+    }
+    let observation = observation_json(world);
+    if !bot.acted && observation == bot.last_observation {
+        return;
+    }
+    bot.acted = false;
+    let line = match in_game(world) {
+        // The time played changes every frame, so it isn't compared.
+        true => format!(
+            "{},\"time_played_ms\":{}}}",
+            &observation[..observation.len() - 1],
+            world.game.time_played.as_millis()
+        ),
+        false => observation.clone(),
+    };
+    bot.last_observation = observation;
+    if !send(connection, &line) {
+        bot.connection = None;
+    }
+}
+
+fn in_game(world: &game::World) -> bool {
+    matches!(
+        world.state,
+        game::State::Play
+            | game::State::Countdown
+            | game::State::Paused
+            | game::State::GameOver
+            | game::State::Victory
+    )
+}
+
+pub fn observation_json(world: &game::World) -> String {
+    let state = json_string(state_name(&world.state));
+    if !in_game(world) {
+        return format!("{{\"state\":{}}}", state);
+    }
+    let game = &world.game;
+    let placements: Vec<String> = cpu::targets(game)
+        .iter()
+        .map(|target| format!("[{},{}]", target.orientation, target.x))
+        .collect();
+    format!(
+        "{{\"state\":{},\"mode\":{},\"score\":{},\"lines\":{},\"level\":{},\"pieces\":{},\"block\":{},\"orientation\":{},\"next\":{},\"placements\":[{}],\"board\":{}}}",
+        state,
+        json_string(game.mode.label()),
+        game.score,
+        game.lines,
+        game.level,
+        stats::pieces(&game.stats),
+        block_json(&game.block),
+        game.block_orientation,
+        match &game.next {
+            Some(next) => block_json(next),
+            None => "null".to_string(),
+        },
+        placements.join(","),
+        board_json(&game.board)
+    )
+}
+
+fn error_json(error: &str) -> String {
+    format!("{{\"error\":{}}}", json_string(error))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_actions() {
+        assert!(matches!(
+            parse_action("left"),
+            Ok(Action::Input(game::Input::LeftKeyDown))
+        ));
+        assert!(matches!(
+            parse_action("  release "),
+            Ok(Action::Input(game::Input::DownKeyUp))
+        ));
+        assert!(matches!(parse_action("wait"), Ok(Action::Wait)));
+        assert!(matches!(
+            parse_action("place 1 -2"),
+            Ok(Action::Place(cpu::Target {
+                orientation: 1,
+                x: -2
+            }))
+        ));
+        assert!(matches!(
+            parse_action("start 7"),
+            Ok(Action::Start {
+                seed: 7,
+                mode: None
+            })
+        ));
+        match parse_action("start 7 Bane Metal") {
+            Ok(Action::Start { seed, mode }) => {
+                assert_eq!(seed, 7);
+                assert_eq!(mode.as_deref(), Some("Bane Metal"));
+            }
+            _ => panic!("start with a mode wasn't read"),
+        }
+    }
+
+    #[test]
+    fn rejects_bad_actions() {
+        for line in &["", "jump", "place 1", "place a 2", "start", "start x"] {
+            let error = parse_action(line).err().unwrap();
+            assert!(error.contains("Can't read action"));
+        }
+    }
+}
@@ -0,0 +1,229 @@
+/*
+
+Settings that survive between sessions are stored in a plain text file
+in the XDG config directory, e.g. ~/.config/tetris-bane/settings.cfg.
+
+Each line is a "key = value" pair. Blank lines and lines starting with
+'#' are ignored, as are unknown keys, so older versions of the game can
+read files written by newer ones.
+
+*/
+
+use std::env;
+use std::fs;
+use std::io::Write;
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+const APP_DIR: &str = "tetris-bane";
+const ASSETS_DIR: &str = "assets";
+const CONFIG_FILE: &str = "settings.cfg";
+
+pub const DEFAULT_MUSIC_TOGGLE: bool = true;
+pub const DEFAULT_MUSIC_VOLUME: i32 = 70;
+pub const MAX_MUSIC_VOLUME: i32 = 128;
+
+// Set by set_assets_dir, until then assets are read from the working
+// directory.
+static ASSETS: Mutex<Option<PathBuf>> = Mutex::new(None);
+
+#[derive(Clone, PartialEq, Eq)]
+pub struct Config {
+    pub music_toggle: bool,
+    pub music_volume: i32,
+    // The label of the mode last played, as the modes on offer change.
+    pub mode: String,
+    pub show_stats: bool,
+    pub show_ghost: bool,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            music_toggle: DEFAULT_MUSIC_TOGGLE,
+            music_volume: DEFAULT_MUSIC_VOLUME,
+            mode: String::new(),
+            show_stats: false,
+            show_ghost: true,
+        }
+    }
+}
+
+// Returns the directory used for all files the game writes. Follows the
+// XDG base directory spec, falling back to ~/.config and then %APPDATA%.
+pub fn config_dir() -> Option<PathBuf> {
+    let base = match env::var_os("XDG_CONFIG_HOME") {
+        Some(dir) if !dir.is_empty() => PathBuf::from(dir),
+        _ => match env::var_os("HOME") {
+            Some(home) => PathBuf::from(home).join(".config"),
+            None => PathBuf::from(env::var_os("APPDATA")?),
+        },
+    };
+    Some(base.join(APP_DIR))
+}
+
+// Reads the assets from the directory rather than the working
+// directory, for programs using the library from elsewhere, see ffi.rs.
+pub fn set_assets_dir(dir: PathBuf) {
+    *ASSETS.lock().unwrap() = Some(dir);
+}
+
+// Returns the path of a file inside the assets directory.
+pub fn asset_path(file_name: &str) -> PathBuf {
+    match &*ASSETS.lock().unwrap() {
+        Some(dir) => dir.join(file_name),
+        None => PathBuf::from(ASSETS_DIR).join(file_name),
+    }
+}
+
+// Returns the path of a file inside the config directory.
+pub fn file_path(file_name: &str) -> Option<PathBuf> {
+    config_dir().map(|dir| dir.join(file_name))
+}
+
+// Writes contents to a file in the config directory, creating the
+// directory if needed. Failing to save is not fatal to the game so the
+// error is only reported.
+pub fn write_file(file_name: &str, contents: &str) {
+    let path = match file_path(file_name) {
+        Some(path) => path,
+        None => return,
+    };
+    let result = path
+        .parent()
+        .map_or(Ok(()), fs::create_dir_all)
+        .and_then(|_| fs::write(&path, contents));
+    if let Err(error) = result {
+        eprintln!("Unable to write {}: {}", path.display(), error);
+    }
+}
+
+// Appends contents to a file in the config directory, creating it if
+// needed.
+pub fn append_file(file_name: &str, contents: &str) {
+    let path = match file_path(file_name) {
+        Some(path) => path,
+        None => return,
+    };
+    let result = path
+        .parent()
+        .map_or(Ok(()), fs::create_dir_all)
+        .and_then(|_| fs::OpenOptions::new().create(true).append(true).open(&path))
+        .and_then(|mut file| file.write_all(contents.as_bytes()));
+    if let Err(error) = result {
+        eprintln!("Unable to write {}: {}", path.display(), error);
+    }
+}
+
+// Reads a file from the config directory. Returns None if it doesn't exist.
+pub fn read_file(file_name: &str) -> Option<String> {
+    fs::read_to_string(file_path(file_name)?).ok()
+}
+
+// Splits "key = value" lines, skipping blanks and comments.
+pub fn parse_pairs(contents: &str) -> Vec<(String, String)> {
+    contents
+        .lines()
+        .map(|line| line.trim())
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .filter_map(|line| {
+            let mut parts = line.splitn(2, '=');
+            let key = parts.next()?.trim();
+            let value = parts.next()?.trim();
+            Some((key.to_string(), value.to_string()))
+        })
+        .collect()
+}
+
+pub fn load() -> Config {
+    match read_file(CONFIG_FILE) {
+        Some(contents) => parse(&contents),
+        None => Config::default(),
+    }
+}
+
+// Reads settings from the text of a settings file. Values that can't be
+// read keep their defaults.
+pub fn parse(contents: &str) -> Config {
+    let mut config = Config::default();
+    for (key, value) in parse_pairs(contents) {
+        match key.as_str() {
+            "music" => {
+                if let Ok(toggle) = value.parse() {
+                    config.music_toggle = toggle;
+                }
+            }
+            "music_volume" => {
+                if let Ok(volume) = value.parse::<i32>() {
+                    config.music_volume = volume.clamp(0, MAX_MUSIC_VOLUME);
+                }
+            }
+            "mode" => config.mode = value,
+            "show_stats" => {
+                if let Ok(toggle) = value.parse() {
+                    config.show_stats = toggle;
+                }
+            }
+            "show_ghost" => {
+                if let Ok(toggle) = value.parse() {
+                    config.show_ghost = toggle;
+                }
+            }
+            _ => {}
+        }
+    }
+    config
+}
+
+pub fn save(config: &Config) {
+    write_file(CONFIG_FILE, &to_text(config));
+}
+
+// The text of a settings file, as read by parse.
+pub fn to_text(config: &Config) -> String {
+    format!(
+        "# Tetris Bane settings\n\
+         music = {}\n\
+         music_volume = {}\n\
+         mode = {}\n\
+         show_stats = {}\n\
+         show_ghost = {}\n",
+        config.music_toggle, config.music_volume, config.mode, config.show_stats, config.show_ghost
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_pairs_skips_blanks_and_comments() {
+        let pairs = parse_pairs("# comment\n\n  a = 1 \nb=two = 2\nno separator\n");
+        assert_eq!(
+            pairs,
+            vec![
+                ("a".to_string(), "1".to_string()),
+                ("b".to_string(), "two = 2".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn settings_round_trip() {
+        let config = Config {
+            music_toggle: false,
+            music_volume: 12,
+            mode: "Tiny Sprint".to_string(),
+            show_stats: true,
+            show_ghost: false,
+        };
+        assert!(parse(&to_text(&config)) == config);
+    }
+
+    #[test]
+    fn bad_values_keep_defaults() {
+        let config = parse("music = maybe\nmusic_volume = 1000\nunknown = 1\n");
+        assert_eq!(config.music_toggle, DEFAULT_MUSIC_TOGGLE);
+        assert_eq!(config.music_volume, MAX_MUSIC_VOLUME);
+    }
+}
@@ -0,0 +1,233 @@
+/*
+
+Two players share one Metal board, each moving their own block. The
+blocks can't move into each other, and a block resting on the other
+player's block waits for it rather than locking. Score and lines are
+shared, and the game is over as soon as either player tops out, which
+includes a block spawning onto the other player's.
+
+There is one Game for the board. It holds the first player's block,
+the second player's waits in a Seat and is swapped in while it's
+moved, so both blocks fall by the rules of game.rs. Each player's
+blocks spawn in their own half of the board and are shown in their own
+preview first.
+
+The keys of each player are the versus keys, see main.rs.
+
+*/
+
+use std::time;
+
+use crate::block::{Block, Delta};
+use crate::game;
+use crate::mode;
+use crate::random;
+use crate::sound;
+use crate::stats;
+
+pub const PLAYERS: usize = 2;
+
+pub enum ResultItem {
+    PlayAgain,
+    Menu,
+}
+
+// The block of the player that isn't in the game, see swap_seat.
+pub struct Seat {
+    pub block: Block,
+    pub block_orientation: u8,
+    pub next: Option<Block>,
+    pub fall_rate_millis: u128,
+    pub block_drop_clock: time::Instant,
+    pub spawn_shift: i32,
+}
+
+pub struct Coop {
+    pub game: game::Game, // the shared board with the first player's block
+    pub seat: Seat,       // the second player's block
+    // Countdown or Play, then GameOver while the players soak in their
+    // defeat.
+    pub state: game::State,
+    pub result_items: Vec<ResultItem>,
+    pub result_selected: usize,
+}
+
+// A new game, started with start.
+pub fn initialise(modes: Vec<mode::Mode>, mode_selected: usize) -> Coop {
+    let mode = mode::coop_mode();
+    let mut game = game::initialise_seeded_game(modes, mode_selected, mode, random::random_seed());
+    // Each player's blocks spawn in the middle of their half of the
+    // board, rather than where the mode spawns them.
+    let width = game.board[0].len() as i32;
+    let xs = game.block.positions.iter().map(|p| p.x);
+    let spawn_mid = (xs.clone().min().unwrap_or(0) + xs.max().unwrap_or(0)) / 2;
+    let shifts = [width / 4 - spawn_mid, 3 * width / 4 - spawn_mid];
+
+    game.spawn_shift = shifts[1];
+    let block = game::deal_block(&mut game);
+    let next = game::deal_block(&mut game);
+    stats::record_spawn(&mut game.stats, &block.label, game.time_played);
+    let seat = Seat {
+        block: block,
+        block_orientation: 0,
+        next: Some(next),
+        fall_rate_millis: game.fall_rate_millis,
+        block_drop_clock: time::Instant::now(),
+        spawn_shift: shifts[1],
+    };
+
+    // The first block was dealt before the shift.
+    game.spawn_shift = shifts[0];
+    let delta = Delta { x: shifts[0], y: 0 };
+    game.block.positions = game.block.positions.iter().map(|&p| p + delta).collect();
+    let next = game::deal_block(&mut game);
+    game.next = Some(next);
+
+    Coop {
+        state: game::start_state(&game),
+        game: game,
+        seat: seat,
+        result_items: vec![ResultItem::PlayAgain, ResultItem::Menu],
+        result_selected: 0,
+    }
+}
+
+pub fn result_label(item: &ResultItem) -> &'static str {
+    match item {
+        ResultItem::PlayAgain => "Play again",
+        ResultItem::Menu => "Menu",
+    }
+}
+
+pub fn start(world: &mut game::World) {
+    world.coop = initialise(world.game.modes.clone(), world.game.mode_selected);
+    world.music_file = sound::music_path(&world.coop.game.mode);
+    world.state = game::State::Coop;
+}
+
+// Puts the seated player's block in the game and the game's in the seat.
+fn swap_seat(game: &mut game::Game, seat: &mut Seat) {
+    std::mem::swap(&mut game.block, &mut seat.block);
+    std::mem::swap(&mut game.block_orientation, &mut seat.block_orientation);
+    std::mem::swap(&mut game.next, &mut seat.next);
+    std::mem::swap(&mut game.fall_rate_millis, &mut seat.fall_rate_millis);
+    std::mem::swap(&mut game.block_drop_clock, &mut seat.block_drop_clock);
+    std::mem::swap(&mut game.spawn_shift, &mut seat.spawn_shift);
+}
+
+pub fn update(
+    event: &Option<game::Input>,
+    player_inputs: &[(usize, game::Input)],
+    world: &mut game::World,
+) {
+    if let Some(game::Input::EscKeyDown) = event {
+        world.state = game::State::Menu;
+        return;
+    }
+
+    let coop = &mut world.coop;
+    match coop.state {
+        game::State::Countdown => {
+            coop.state = game::update_countdown(&mut coop.game);
+            coop.seat.block_drop_clock = coop.game.block_drop_clock;
+        }
+        game::State::Play => {
+            for player in 0..PLAYERS {
+                // Like the single player game only one input is taken a
+                // frame.
+                let event = player_inputs
+                    .iter()
+                    .rev()
+                    .find(|(p, _)| *p == player)
+                    .map(|(_, input)| input.clone());
+                if update_player(coop, player, &event) == game::State::GameOver {
+                    // The pause is timed by the first player's block.
+                    game::top_out(&mut coop.game);
+                    coop.state = game::State::GameOver;
+                    break;
+                }
+            }
+        }
+        game::State::GameOver => {
+            let state = game::update(&None, &mut coop.game, &coop.state);
+            if state == game::State::Menu {
+                coop.result_selected = 0;
+                world.state = game::State::CoopResult;
+            }
+        }
+        _ => {}
+    }
+}
+
+// Moves the player's block, returns GameOver if they topped out.
+fn update_player(coop: &mut Coop, player: usize, event: &Option<game::Input>) -> game::State {
+    if player == 1 {
+        swap_seat(&mut coop.game, &mut coop.seat);
+    }
+    coop.game.other_blocks = coop.seat.block.positions.clone();
+    let state = game::update(event, &mut coop.game, &game::State::Play);
+    coop.game.other_blocks = vec![];
+    if player == 1 {
+        swap_seat(&mut coop.game, &mut coop.seat);
+    }
+    state
+}
+
+pub fn update_result(event: &Option<game::Input>, world: &mut game::World) {
+    let coop = &mut world.coop;
+    let n_items = coop.result_items.len();
+    if let Some(event) = event {
+        match event {
+            game::Input::UpKeyDown | game::Input::LeftKeyDown => {
+                coop.result_selected = (coop.result_selected + n_items - 1) % n_items;
+            }
+            game::Input::DownKeyDown | game::Input::RightKeyDown => {
+                coop.result_selected = (coop.result_selected + 1) % n_items;
+            }
+            game::Input::ReturnKeyDown | game::Input::SpaceKeyDown => {
+                match coop.result_items[coop.result_selected] {
+                    ResultItem::PlayAgain => start(world),
+                    ResultItem::Menu => world.state = game::State::Menu,
+                }
+            }
+            game::Input::EscKeyDown => world.state = game::State::Menu,
+            _ => {}
+        }
+    }
+}
+
+// The shared score, drawn on the result screen.
+pub fn result_line(coop: &Coop) -> String {
+    format!(
+        "Score {}   Lines {}   Level {}",
+        coop.game.score, coop.game.lines, coop.game.level
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::block::Position;
+
+    #[test]
+    fn spawning_onto_the_other_block_tops_out() {
+        let modes = mode::initialise_modes();
+        let mut coop = initialise(modes, 0);
+        // The second player's block waits where the first player's next
+        // block spawns, and the first player's block is about to lock.
+        coop.seat.block.positions = coop.game.next.as_ref().unwrap().positions.clone();
+        let bottom = coop.game.board.len() as i32 - 1;
+        coop.game.block.positions = (0..4).map(|x| Position { x: x, y: bottom }).collect();
+        let locked_at = coop.game.block_drop_clock + time::Duration::from_secs(10);
+        coop.game.clock = Some(locked_at);
+
+        assert!(update_player(&mut coop, 0, &None) == game::State::GameOver);
+        let overlapping = coop
+            .game
+            .block
+            .positions
+            .iter()
+            .any(|p| coop.seat.block.positions.contains(p));
+        assert!(!overlapping);
+    }
+}
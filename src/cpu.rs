@@ -0,0 +1,421 @@
+/*
+
+A computer player for versus. When a block spawns the CPU tries every
+orientation and column it could drop it in and scores the board each
+would leave, using the weights below. Harder CPUs also look ahead to
+the block after, trying each block that could come next, and take the
+placement that leaves the best average.
+
+The CPU then plays the placement with the same inputs as a player,
+one input each time it has finished thinking, so slower CPUs also
+place blocks slower. Easier CPUs sometimes pick a placement at random.
+
+*/
+
+use std::time;
+
+use rand::Rng;
+
+use crate::block;
+use crate::block::{Block, Position};
+use crate::game;
+use crate::random;
+use crate::stats;
+
+// Weights of the board scoring, from a well known hand tuned player.
+const LINES_WEIGHT: f32 = 0.76;
+const HEIGHT_WEIGHT: f32 = -0.51;
+const HOLES_WEIGHT: f32 = -0.36;
+const BUMPINESS_WEIGHT: f32 = -0.18;
+
+// Placements of the first block looked further into when looking ahead.
+const LOOK_AHEAD_CANDIDATES: usize = 6;
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Difficulty {
+    Easy,
+    Normal,
+    Hard,
+}
+
+pub const DIFFICULTIES: [Difficulty; 3] = [Difficulty::Easy, Difficulty::Normal, Difficulty::Hard];
+
+struct Settings {
+    depth: usize,       // blocks placed in each search, 1 is only the block in play
+    think_millis: u128, // between inputs
+    mistake_rate: f64,  // chance of a random placement
+}
+
+fn settings(difficulty: Difficulty) -> Settings {
+    match difficulty {
+        Difficulty::Easy => Settings {
+            depth: 1,
+            think_millis: 250,
+            mistake_rate: 0.2,
+        },
+        Difficulty::Normal => Settings {
+            depth: 1,
+            think_millis: 100,
+            mistake_rate: 0.05,
+        },
+        Difficulty::Hard => Settings {
+            depth: 2,
+            think_millis: 40,
+            mistake_rate: 0.,
+        },
+    }
+}
+
+pub fn difficulty_label(difficulty: Difficulty) -> &'static str {
+    match difficulty {
+        Difficulty::Easy => "Easy",
+        Difficulty::Normal => "Normal",
+        Difficulty::Hard => "Hard",
+    }
+}
+
+// Where the block in play is being taken.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub struct Target {
+    pub orientation: u8,
+    pub x: i32, // leftmost column
+}
+
+pub struct Cpu {
+    pub difficulty: Difficulty,
+    target: Option<Target>,
+    pieces: u32,    // dealt when the target was picked, a new block needs a new one
+    dropping: bool, // soft drop is held
+    think_clock: time::Instant,
+    rng: random::Rng,
+}
+
+pub fn initialise(difficulty: Difficulty) -> Cpu {
+    Cpu {
+        difficulty: difficulty,
+        target: None,
+        pieces: 0,
+        dropping: false,
+        think_clock: time::Instant::now(),
+        rng: random::from_seed(random::random_seed()),
+    }
+}
+
+// The CPU's input for this frame, if it has finished thinking.
+pub fn input(cpu: &mut Cpu, game: &game::Game) -> Option<game::Input> {
+    let settings = settings(cpu.difficulty);
+    if cpu.think_clock.elapsed().as_millis() < settings.think_millis {
+        return None;
+    }
+    cpu.think_clock = time::Instant::now();
+
+    let pieces = stats::pieces(&game.stats);
+    if cpu.pieces != pieces || cpu.target.is_none() {
+        cpu.target = choose_target(cpu, game, &settings);
+        cpu.pieces = pieces;
+        // Let go of soft drop so the new block isn't rushed.
+        if cpu.dropping {
+            cpu.dropping = false;
+            return Some(game::Input::DownKeyUp);
+        }
+    }
+    steer(game, cpu.target?, &mut cpu.dropping)
+}
+
+// The input that takes the block in play towards the target, then soft
+// drops it. Bots placing blocks are steered the same way, see bot.rs.
+pub fn steer(game: &game::Game, target: Target, dropping: &mut bool) -> Option<game::Input> {
+    let x = leftmost(&game.block.positions);
+    if game.block_orientation != target.orientation {
+        // Blocks at the very top may need to fall a row before they can turn.
+        let rotated = block::rotate_block(&game.block, game.block_orientation);
+        match fits(&game.board, &rotated) {
+            true => Some(game::Input::UpKeyDown),
+            false => None,
+        }
+    } else if x > target.x {
+        Some(game::Input::LeftKeyDown)
+    } else if x < target.x {
+        Some(game::Input::RightKeyDown)
+    } else if !*dropping {
+        *dropping = true;
+        Some(game::Input::DownKeyDown)
+    } else {
+        None
+    }
+}
+
+// Where the block in play can be dropped.
+pub fn targets(game: &game::Game) -> Vec<Target> {
+    let rotate = game.mode.rotate();
+    placements(&game.board, &game.block, game.block_orientation, rotate)
+        .iter()
+        .map(|p| p.target)
+        .collect()
+}
+
+fn choose_target(cpu: &mut Cpu, game: &game::Game, settings: &Settings) -> Option<Target> {
+    let rotate = game.mode.rotate();
+    let placements = placements(&game.board, &game.block, game.block_orientation, rotate);
+    if placements.is_empty() {
+        return None;
+    }
+    if cpu.rng.gen_bool(settings.mistake_rate) {
+        let i = cpu.rng.gen_range(0, placements.len());
+        return Some(placements[i].target);
+    }
+
+    // Scores with the index of their placement.
+    let mut scored: Vec<(f32, usize)> = placements
+        .iter()
+        .enumerate()
+        .map(|(i, p)| (p.lines as f32 * LINES_WEIGHT + evaluate(&p.board), i))
+        .collect();
+    if settings.depth > 1 {
+        scored.sort_by(|a, b| b.0.partial_cmp(&a.0).unwrap());
+        scored.truncate(LOOK_AHEAD_CANDIDATES);
+        scored.iter_mut().for_each(|(value, i)| {
+            let p = &placements[*i];
+            *value = p.lines as f32 * LINES_WEIGHT
+                + look_ahead(&p.board, &game.blocks, settings.depth - 1, rotate);
+        });
+    }
+    // The first of equal placements needs the fewest turns.
+    scored
+        .iter()
+        .fold(None, |best: Option<&(f32, usize)>, s| match best {
+            Some(b) if b.0 >= s.0 => Some(b),
+            _ => Some(s),
+        })
+        .map(|&(_, i)| placements[i].target)
+}
+
+// Average of the best placement of each block that could come next.
+fn look_ahead(board: &game::Board, blocks: &[Block], depth: usize, rotate: bool) -> f32 {
+    let total: f32 = blocks
+        .iter()
+        .map(|block| {
+            placements(board, block, 0, rotate)
+                .iter()
+                .map(|p| {
+                    let value = match depth {
+                        1 => evaluate(&p.board),
+                        _ => look_ahead(&p.board, blocks, depth - 1, rotate),
+                    };
+                    p.lines as f32 * LINES_WEIGHT + value
+                })
+                .fold(f32::MIN, f32::max)
+        })
+        .sum();
+    total / blocks.len() as f32
+}
+
+struct Placement {
+    target: Target,
+    board: game::Board, // after the block has locked and lines cleared
+    lines: usize,
+}
+
+// Every way the block can be dropped, turned near the top of the board
+// then moved sideways.
+fn placements(board: &game::Board, block: &Block, orientation: u8, rotate: bool) -> Vec<Placement> {
+    let width = board[0].len() as i32;
+    let mut placements = vec![];
+    let mut block = block.clone();
+    let mut orientation = orientation;
+    let turns = match rotate {
+        true => block::orientation_count(&block),
+        false => 1,
+    };
+    for _ in 0..turns {
+        let x = leftmost(&block.positions);
+        for target_x in 0..width {
+            let shifted: Vec<Position> = block
+                .positions
+                .iter()
+                .map(|p| Position {
+                    x: p.x + target_x - x,
+                    y: p.y,
+                })
+                .collect();
+            if !fits(board, &shifted) {
+                continue;
+            }
+            let (board, lines) = drop_block(board, &shifted, block.color);
+            placements.push(Placement {
+                target: Target {
+                    orientation: orientation,
+                    x: target_x,
+                },
+                board: board,
+                lines: lines,
+            });
+        }
+        // Turning at the very top can go off the board, the CPU waits
+        // for the block to fall far enough, see input.
+        let rotated = block::rotate_block(&block, orientation);
+        let below_top = -rotated.iter().map(|p| p.y).min().unwrap_or(0).min(0);
+        block.positions = rotated
+            .iter()
+            .map(|p| Position {
+                x: p.x,
+                y: p.y + below_top,
+            })
+            .collect();
+        orientation = block::next_orientation(&block, orientation);
+    }
+    placements
+}
+
+fn fits(board: &game::Board, positions: &[Position]) -> bool {
+    positions.iter().all(|p| {
+        p.x >= 0
+            && p.y >= 0
+            && (p.x as usize) < board[0].len()
+            && (p.y as usize) < board.len()
+            && board[p.y as usize][p.x as usize].is_none()
+    })
+}
+
+// Drops the block as far as it goes, locks it and clears full lines.
+fn drop_block(
+    board: &game::Board,
+    positions: &[Position],
+    color: block::Color,
+) -> (game::Board, usize) {
+    let mut positions = positions.to_vec();
+    loop {
+        let below: Vec<Position> = positions
+            .iter()
+            .map(|p| Position { x: p.x, y: p.y + 1 })
+            .collect();
+        if !fits(board, &below) {
+            break;
+        }
+        positions = below;
+    }
+    let mut board = board.clone();
+    positions
+        .iter()
+        .for_each(|p| board[p.y as usize][p.x as usize] = Some(color));
+    let width = board[0].len();
+    let height = board.len();
+    board.retain(|row| row.iter().any(|cell| cell.is_none()));
+    let lines = height - board.len();
+    (0..lines).for_each(|_| board.insert(0, vec![None; width]));
+    (board, lines)
+}
+
+fn evaluate(board: &game::Board) -> f32 {
+    let heights: Vec<i32> = (0..board[0].len())
+        .map(|x| {
+            let top = board.iter().position(|row| row[x].is_some());
+            top.map_or(0, |y| (board.len() - y) as i32)
+        })
+        .collect();
+    let height: i32 = heights.iter().sum();
+    let bumpiness: i32 = heights.windows(2).map(|w| (w[0] - w[1]).abs()).sum();
+    let holes = stats::count_holes(board) as f32;
+    HEIGHT_WEIGHT * height as f32 + HOLES_WEIGHT * holes + BUMPINESS_WEIGHT * bumpiness as f32
+}
+
+fn leftmost(positions: &[Position]) -> i32 {
+    positions.iter().map(|p| p.x).min().unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A classic game with the named block in play.
+    fn classic_game(piece: &str) -> game::Game {
+        let mut game = game::played_game(1, 0, |_| None);
+        game.block = game
+            .blocks
+            .iter()
+            .find(|b| b.label.name() == piece)
+            .unwrap()
+            .clone();
+        game.block_orientation = 0;
+        game
+    }
+
+    // The bottom row filled but for the columns given.
+    fn gapped_board(game: &game::Game, gap: std::ops::Range<usize>) -> game::Board {
+        let color = block::Color { r: 1, g: 1, b: 1 };
+        let mut board = game.board.clone();
+        let bottom = board.last_mut().unwrap();
+        (0..bottom.len())
+            .filter(|x| !gap.contains(x))
+            .for_each(|x| bottom[x] = Some(color));
+        board
+    }
+
+    #[test]
+    fn places_a_block_in_every_column_it_fits() {
+        let game = classic_game("O");
+        let placements = placements(&game.board, &game.block, 0, true);
+        let width = game.board[0].len();
+        assert_eq!(placements.len(), width - 1);
+        let height = game.board.len();
+        for (x, placement) in placements.iter().enumerate() {
+            assert!(
+                placement.target
+                    == Target {
+                        orientation: 0,
+                        x: x as i32
+                    }
+            );
+            assert_eq!(placement.lines, 0);
+            let filled: Vec<Position> = (0..height)
+                .flat_map(|y| {
+                    (0..width).map(move |x| Position {
+                        x: x as i32,
+                        y: y as i32,
+                    })
+                })
+                .filter(|p| placement.board[p.y as usize][p.x as usize].is_some())
+                .collect();
+            assert_eq!(filled.len(), 4);
+            assert!(filled.iter().all(|p| p.y as usize >= height - 2));
+            assert!(filled
+                .iter()
+                .all(|p| p.x == x as i32 || p.x == x as i32 + 1));
+        }
+    }
+
+    #[test]
+    fn dropping_clears_full_lines() {
+        let game = classic_game("I");
+        let board = gapped_board(&game, 3..7);
+        let positions: Vec<Position> = (3..7).map(|x| Position { x: x, y: 0 }).collect();
+        let (cleared, lines) = drop_block(&board, &positions, game.block.color);
+        assert_eq!(lines, 1);
+        assert_eq!(cleared.len(), board.len());
+        assert!(cleared.iter().flatten().all(|cell| cell.is_none()));
+
+        let (kept, lines) = drop_block(&game.board, &positions, game.block.color);
+        assert_eq!(lines, 0);
+        assert!(kept.last().unwrap()[3..7].iter().all(|cell| cell.is_some()));
+    }
+
+    #[test]
+    fn takes_the_clearing_placement() {
+        let mut game = classic_game("I");
+        game.board = gapped_board(&game, 3..7);
+        let mut cpu = initialise(Difficulty::Normal);
+        let settings = Settings {
+            depth: 1,
+            think_millis: 0,
+            mistake_rate: 0.,
+        };
+        let target = choose_target(&mut cpu, &game, &settings).unwrap();
+        assert!(
+            target
+                == Target {
+                    orientation: 0,
+                    x: 3
+                }
+        );
+    }
+}
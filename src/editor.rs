@@ -0,0 +1,277 @@
+/*
+
+The board editor paints a starting board, picks the pieces dealt and a
+goal, and saves the result as a puzzle or practice board in the
+editor's pack, see puzzle.rs. It's also handy for rebuilding a board
+from a bug report.
+
+Typed text is enabled while editing so letters can be used as
+commands, see update.
+
+*/
+
+use std::rc::Rc;
+
+use crate::block;
+use crate::block::Position;
+use crate::game;
+use crate::menu;
+use crate::mode;
+use crate::puzzle;
+use crate::puzzle::Goal;
+
+// Goals cycled through with the goal key.
+const GOALS: [Goal; 6] = [
+    Goal::Lines(1),
+    Goal::Lines(2),
+    Goal::Lines(3),
+    Goal::Lines(4),
+    Goal::PerfectClear,
+    Goal::Practice,
+];
+
+pub struct Editor {
+    pub board: game::Board,
+    pub cursor: Position,
+    // The mode whose board size and pieces are used.
+    pub mode_selected: usize,
+    pub blocks: Vec<block::Block>,
+    // Garbage followed by the colour of every block of every mode.
+    pub palette: Vec<block::Color>,
+    pub color_selected: usize,
+    pub piece_selected: usize,
+    pub sequence: Vec<String>,
+    pub goal: Goal,
+    // Feedback from the last command, e.g. where the puzzle was saved.
+    pub message: String,
+}
+
+pub fn initialise(modes: &[mode::Mode], mode_selected: usize) -> Editor {
+    let mode = &modes[mode_selected];
+    let size = mode.board_size();
+    let blocks = block::load_blocks(mode.pieces());
+    Editor {
+        board: vec![vec![None; size.x as usize]; size.y as usize],
+        cursor: Position {
+            x: size.x / 2,
+            y: size.y - 1,
+        },
+        mode_selected: mode_selected,
+        blocks: blocks,
+        palette: palette(modes),
+        color_selected: 0,
+        piece_selected: 0,
+        sequence: vec![],
+        goal: Goal::Lines(1),
+        message: "".to_string(),
+    }
+}
+
+// Lines describing the keys, drawn next to the board.
+pub fn help_lines() -> Vec<&'static str> {
+    vec![
+        "Arrows  move",
+        "Space   paint",
+        "Bksp    erase",
+        "c       colour",
+        "m       mode",
+        "p       piece",
+        "a       add piece",
+        "r       remove piece",
+        "g       goal",
+        "t       try it",
+        "Return  save",
+        "Esc     menu",
+    ]
+}
+
+pub fn update(event: &Option<game::Input>, world: &mut game::World) {
+    let editor = &mut world.editor;
+    let width = editor.board[0].len() as i32;
+    let height = editor.board.len() as i32;
+    if let Some(event) = event {
+        match event {
+            game::Input::LeftKeyDown => editor.cursor.x = (editor.cursor.x - 1).max(0),
+            game::Input::RightKeyDown => editor.cursor.x = (editor.cursor.x + 1).min(width - 1),
+            // The hidden row and the one under it are kept clear for
+            // blocks to spawn in.
+            game::Input::UpKeyDown => editor.cursor.y = (editor.cursor.y - 1).max(2),
+            game::Input::DownKeyDown => editor.cursor.y = (editor.cursor.y + 1).min(height - 1),
+            game::Input::Character(' ') | game::Input::SpaceKeyDown => {
+                let color = editor.palette[editor.color_selected];
+                set_cell(editor, Some(color));
+            }
+            game::Input::BackspaceKeyDown => set_cell(editor, None),
+            game::Input::Character('c') => {
+                editor.color_selected = (editor.color_selected + 1) % editor.palette.len();
+            }
+            game::Input::Character('m') => {
+                let mode_selected = (editor.mode_selected + 1) % world.game.modes.len();
+                change_mode(editor, &world.game.modes, mode_selected);
+            }
+            game::Input::Character('p') => {
+                editor.piece_selected = (editor.piece_selected + 1) % editor.blocks.len();
+            }
+            game::Input::Character('a') => {
+                let label = &editor.blocks[editor.piece_selected].label;
+                editor.sequence.push(label.name().to_string());
+            }
+            game::Input::Character('r') => {
+                editor.sequence.pop();
+            }
+            game::Input::Character('g') => {
+                let index = GOALS.iter().position(|&g| g == editor.goal).unwrap_or(0);
+                editor.goal = GOALS[(index + 1) % GOALS.len()];
+            }
+            game::Input::Character('t') => match to_puzzle(editor, &world.game.modes) {
+                Ok(puzzle) => {
+                    let mode = Rc::new(mode::puzzle::PuzzleMode::new(puzzle));
+                    menu::start_game_in_mode(world, mode);
+                }
+                Err(error) => editor.message = error,
+            },
+            game::Input::ReturnKeyDown => {
+                editor.message = match to_puzzle(editor, &world.game.modes) {
+                    Ok(puzzle) => format!("Saved as {}", puzzle::save_to_editor_pack(&puzzle)),
+                    Err(error) => error,
+                };
+            }
+            game::Input::EscKeyDown => world.state = game::State::Menu,
+            _ => {}
+        }
+    }
+}
+
+// Garbage then the block colours in the order the modes list them.
+fn palette(modes: &[mode::Mode]) -> Vec<block::Color> {
+    let mut palette = vec![block::GARBAGE_COLOR];
+    modes
+        .iter()
+        .filter_map(|mode| block::find_blocks(mode.pieces()).ok())
+        .flatten()
+        .for_each(|b| {
+            if !palette.contains(&b.color) {
+                palette.push(b.color);
+            }
+        });
+    palette
+}
+
+// Switches to another mode's board size and pieces. The board is kept
+// from the bottom left and the sequence where the new mode has the
+// pieces, the message says what didn't fit.
+fn change_mode(editor: &mut Editor, modes: &[mode::Mode], mode_selected: usize) {
+    let mut changed = initialise(modes, mode_selected);
+    changed.color_selected = editor.color_selected;
+    changed.goal = editor.goal;
+
+    let old_height = editor.board.len();
+    let new_height = changed.board.len();
+    let mut cells_dropped = 0;
+    editor.board.iter().enumerate().for_each(|(y, row)| {
+        row.iter().enumerate().for_each(|(x, cell)| {
+            // Rows are counted from the bottom, the top two are kept
+            // clear as the cursor can't reach them.
+            let new_y = (y + new_height).checked_sub(old_height).filter(|&y| y >= 2);
+            match (cell, new_y) {
+                (None, _) => {}
+                (Some(_), Some(new_y)) if x < changed.board[new_y].len() => {
+                    changed.board[new_y][x] = *cell
+                }
+                (Some(_), _) => cells_dropped += 1,
+            }
+        })
+    });
+
+    let labels = block::labels(&changed.blocks);
+    let (kept, dropped): (Vec<String>, Vec<String>) = editor
+        .sequence
+        .iter()
+        .cloned()
+        .partition(|name| labels.iter().any(|label| label.name() == name.as_str()));
+    changed.sequence = kept;
+
+    let counts: Vec<String> = [(cells_dropped, "cell"), (dropped.len(), "piece")]
+        .iter()
+        .filter(|&&(n, _)| n > 0)
+        .map(|&(n, thing)| match n {
+            1 => format!("1 {}", thing),
+            n => format!("{} {}s", n, thing),
+        })
+        .collect();
+    if !counts.is_empty() {
+        changed.message = format!("Dropped {} that didn't fit", counts.join(" and "));
+    }
+    *editor = changed;
+}
+
+fn set_cell(editor: &mut Editor, cell: Option<block::Color>) {
+    let Position { x, y } = editor.cursor;
+    editor.board[y as usize][x as usize] = cell;
+}
+
+// The board from its first filled row down, with the sequence and goal.
+pub fn to_puzzle(editor: &Editor, modes: &[mode::Mode]) -> Result<puzzle::Puzzle, String> {
+    let mode = &modes[editor.mode_selected];
+    let top = editor
+        .board
+        .iter()
+        .position(|row| row.iter().any(|cell| cell.is_some()))
+        .unwrap_or(editor.board.len());
+    let puzzle = puzzle::Puzzle {
+        name: "Editor".to_string(),
+        board_size: mode.board_size(),
+        rows: editor.board[top..].to_vec(),
+        pieces: mode.pieces().to_string(),
+        sequence: editor.sequence.clone(),
+        goal: editor.goal,
+    };
+    puzzle::check_puzzle(&puzzle).map(|_| puzzle)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn changing_mode_keeps_what_fits() {
+        let modes = mode::initialise_modes();
+        let bane = modes.iter().position(|m| m.label() == "Bane").unwrap();
+        let classic = modes.iter().position(|m| m.label() == "Classic").unwrap();
+        let mut editor = initialise(&modes, bane);
+        let bottom = editor.board.len() - 1;
+        editor.board[bottom][0] = Some(block::GARBAGE_COLOR);
+        editor.board[bottom][15] = Some(block::GARBAGE_COLOR);
+        editor.sequence = vec!["Bane I".to_string(), "Bane I".to_string()];
+        editor.goal = Goal::PerfectClear;
+
+        change_mode(&mut editor, &modes, classic);
+        let bottom = editor.board.len() - 1;
+        assert_eq!(editor.board[0].len(), 10);
+        assert!(editor.board[bottom][0] == Some(block::GARBAGE_COLOR));
+        assert!(editor.sequence.is_empty());
+        assert!(editor.goal == Goal::PerfectClear);
+        assert_eq!(
+            editor.message,
+            "Dropped 1 cell and 2 pieces that didn't fit"
+        );
+
+        editor.sequence = vec!["T".to_string()];
+        change_mode(&mut editor, &modes, bane);
+        let bottom = editor.board.len() - 1;
+        assert!(editor.board[bottom][0] == Some(block::GARBAGE_COLOR));
+        assert_eq!(editor.message, "Dropped 1 piece that didn't fit");
+    }
+
+    #[test]
+    fn palette_has_every_block_colour() {
+        let modes = mode::initialise_modes();
+        let editor = initialise(&modes, 0);
+        assert!(editor.palette[0] == block::GARBAGE_COLOR);
+        for pieces in &["bane", "classic", "metal"] {
+            for b in block::load_blocks(pieces) {
+                assert!(editor.palette.contains(&b.color), "{}", b.label.name());
+            }
+        }
+    }
+}
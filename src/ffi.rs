@@ -0,0 +1,438 @@
+/*
+
+The game for programs written in other languages, through a C
+interface. The library is also built as a C dynamic library, e.g.
+target/release/libtetris_bane.so, declared in include/tetris_bane.h.
+The header is generated from this file with cbindgen:
+
+    cbindgen --config cbindgen.toml --output include/tetris_bane.h src/ffi.rs
+
+A game is a handle made by tb_game_create, in one of the modes counted
+by tb_mode_count, and freed by tb_game_destroy. Only the built in modes
+are offered, not those the player has defined, so a mode's number is
+the same wherever the library is used. Games are played like
+gym.rs, with no window, a frame for each step, so a game played again
+from the same seed with the same inputs plays out the same:
+
+    TbGame *game = tb_game_create(0, 1234);    // Bane
+    while (tb_game_step(game, TB_INPUT_LEFT) == TB_PLAYING) {
+        int32_t color = tb_game_cell(game, x, y);
+        ...
+    }
+    tb_game_destroy(game);
+
+The blocks are read from the assets directory in the working directory
+unless the program says where it is with tb_set_assets_dir first. A
+game that can't be made, e.g. because its pieces can't be read, is
+null, and a panic inside the library is caught rather than unwinding
+into the caller: the function returns its failure value, TB_ERROR for
+the status of a step.
+
+Colours are 0xRRGGBB, and -1 where a cell is empty. Positions count
+from the top left of the board, and include the hidden top row.
+
+A game has to be used from the thread that created it. Handles and
+other pointers given to the library have to be null or valid, as
+described under Safety for each function.
+
+*/
+
+use std::ffi::CStr;
+use std::os::raw::c_char;
+use std::panic;
+use std::path::PathBuf;
+
+use crate::block;
+use crate::config;
+use crate::cpu;
+use crate::game;
+use crate::gym;
+use crate::mode;
+
+pub const TB_INPUT_NONE: u32 = 0;
+pub const TB_INPUT_LEFT: u32 = 1;
+pub const TB_INPUT_RIGHT: u32 = 2;
+pub const TB_INPUT_ROTATE: u32 = 3;
+pub const TB_INPUT_DROP: u32 = 4; // hold soft drop
+pub const TB_INPUT_RELEASE: u32 = 5; // let go of soft drop
+
+pub const TB_PLAYING: u32 = 0;
+pub const TB_WON: u32 = 1;
+pub const TB_LOST: u32 = 2;
+pub const TB_ERROR: u32 = 3; // the library failed, the game can't go on
+
+pub const TB_EMPTY: i32 = -1;
+
+pub struct TbGame {
+    env: gym::Env,
+}
+
+// Runs the body of a function, returning the failure value if it
+// panics, as unwinding out of an extern "C" function aborts.
+fn guard<T>(failure: T, body: impl FnOnce() -> T) -> T {
+    panic::catch_unwind(panic::AssertUnwindSafe(body)).unwrap_or(failure)
+}
+
+// Handles are only ever made by tb_game_create, a null handle is
+// treated as a game that isn't being played.
+unsafe fn take_game(game: *mut TbGame) -> Option<Box<TbGame>> {
+    match game.is_null() {
+        true => None,
+        false => Some(Box::from_raw(game)),
+    }
+}
+
+// See tb_mode_name.
+unsafe fn copy_text(text: &str, buffer: *mut c_char, size: usize) {
+    if buffer.is_null() || size == 0 {
+        return;
+    }
+    let length = text.len().min(size - 1);
+    std::ptr::copy_nonoverlapping(text.as_ptr() as *const c_char, buffer, length);
+    *buffer.add(length) = 0;
+}
+
+// Reads from the game, the failure value if the handle is null or
+// reading panics.
+unsafe fn read<T>(game: *const TbGame, failure: T, read: impl FnOnce(&TbGame) -> T) -> T {
+    match game.as_ref() {
+        Some(game) => guard(failure, || read(game)),
+        None => failure,
+    }
+}
+
+// Returns false if there's nowhere to write.
+unsafe fn write(value: i32, to: *mut i32) -> bool {
+    match to.as_mut() {
+        Some(to) => {
+            *to = value;
+            true
+        }
+        None => false,
+    }
+}
+
+/// Reads the blocks from the assets directory at the path, e.g.
+/// "/opt/tetris-bane/assets", rather than from the working directory.
+/// Returns false, changing nothing, if it has no pieces directory.
+///
+/// # Safety
+///
+/// `path` has to be null or a 0 terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn tb_set_assets_dir(path: *const c_char) -> bool {
+    if path.is_null() {
+        return false;
+    }
+    let path = CStr::from_ptr(path);
+    guard(false, || {
+        let dir = match path.to_str() {
+            Ok(dir) => PathBuf::from(dir),
+            Err(_) => return false,
+        };
+        if !dir.join("pieces").is_dir() {
+            return false;
+        }
+        config::set_assets_dir(dir);
+        true
+    })
+}
+
+/// The number of built in modes, numbered from 0: Bane, Classic, Chill,
+/// Metal, Marathon, Sprint, Ultra, Dig and Survival.
+#[no_mangle]
+pub extern "C" fn tb_mode_count() -> u32 {
+    guard(0, || mode::built_in_modes().len() as u32)
+}
+
+/// Copies the mode's name into the buffer, ending with a 0 and cut
+/// short if the buffer is too small. Returns the length of the whole
+/// name, 0 if there's no such mode.
+///
+/// # Safety
+///
+/// `buffer` has to be null or have room for `size` bytes.
+#[no_mangle]
+pub unsafe extern "C" fn tb_mode_name(mode: u32, buffer: *mut c_char, size: usize) -> usize {
+    let label = guard(None, || {
+        mode::built_in_modes()
+            .get(mode as usize)
+            .map(|mode| mode.label().to_string())
+    });
+    match label {
+        Some(label) => {
+            copy_text(&label, buffer, size);
+            label.len()
+        }
+        None => 0,
+    }
+}
+
+/// Returns null if there's no such mode or its pieces can't be read.
+#[no_mangle]
+pub extern "C" fn tb_game_create(mode: u32, seed: u64) -> *mut TbGame {
+    guard(std::ptr::null_mut(), || {
+        let mode = match mode::built_in_modes().get(mode as usize) {
+            Some(mode) => mode.clone(),
+            None => return std::ptr::null_mut(),
+        };
+        if block::find_blocks(mode.pieces()).is_err() {
+            return std::ptr::null_mut();
+        }
+        let mut env = gym::initialise(mode, gym::Rewards::default());
+        gym::reset(&mut env, seed);
+        Box::into_raw(Box::new(TbGame { env: env }))
+    })
+}
+
+/// # Safety
+///
+/// `game` has to be null or a handle from tb_game_create, which isn't
+/// used again.
+#[no_mangle]
+pub unsafe extern "C" fn tb_game_destroy(game: *mut TbGame) {
+    let game = take_game(game);
+    guard((), || drop(game));
+}
+
+/// Starts the game again from the seed, in the same mode. Returns
+/// false if the game couldn't be started.
+///
+/// # Safety
+///
+/// `game` has to be null or a live handle from tb_game_create.
+#[no_mangle]
+pub unsafe extern "C" fn tb_game_reset(game: *mut TbGame, seed: u64) -> bool {
+    match game.as_mut() {
+        Some(game) => guard(false, || {
+            gym::reset(&mut game.env, seed);
+            true
+        }),
+        None => false,
+    }
+}
+
+/// Plays a frame with the input, one of TB_INPUT_*. Returns the status
+/// after the frame, one of TB_PLAYING, TB_WON, TB_LOST or TB_ERROR.
+///
+/// # Safety
+///
+/// `game` has to be null or a live handle from tb_game_create.
+#[no_mangle]
+pub unsafe extern "C" fn tb_game_step(game: *mut TbGame, input: u32) -> u32 {
+    let game = match game.as_mut() {
+        Some(game) => game,
+        None => return TB_LOST,
+    };
+    let action = match input {
+        TB_INPUT_LEFT => gym::Action::Input(game::Input::LeftKeyDown),
+        TB_INPUT_RIGHT => gym::Action::Input(game::Input::RightKeyDown),
+        TB_INPUT_ROTATE => gym::Action::Input(game::Input::UpKeyDown),
+        TB_INPUT_DROP => gym::Action::Input(game::Input::DownKeyDown),
+        TB_INPUT_RELEASE => gym::Action::Input(game::Input::DownKeyUp),
+        _ => gym::Action::Wait,
+    };
+    guard(TB_ERROR, || {
+        gym::step(&mut game.env, &action);
+        status(&game.env)
+    })
+}
+
+/// Turns the block in play to the orientation, moves it to the leftmost
+/// column given and drops it, playing frames until it has locked.
+/// Returns false, having played nothing, if it can't be placed there.
+///
+/// # Safety
+///
+/// `game` has to be null or a live handle from tb_game_create.
+#[no_mangle]
+pub unsafe extern "C" fn tb_game_place(game: *mut TbGame, orientation: u32, column: i32) -> bool {
+    let game = match game.as_mut() {
+        Some(game) => game,
+        None => return false,
+    };
+    let target = cpu::Target {
+        orientation: orientation as u8,
+        x: column,
+    };
+    guard(false, || {
+        let (_, _, _, info) = gym::step(&mut game.env, &gym::Action::Place(target));
+        !info.invalid
+    })
+}
+
+/// # Safety
+///
+/// `game` has to be null or a live handle from tb_game_create.
+#[no_mangle]
+pub unsafe extern "C" fn tb_game_status(game: *const TbGame) -> u32 {
+    match game.is_null() {
+        true => TB_LOST,
+        false => read(game, TB_ERROR, |game| status(&game.env)),
+    }
+}
+
+fn status(env: &gym::Env) -> u32 {
+    match (&env.state, env.game.outcome) {
+        (game::State::Play, _) => TB_PLAYING,
+        (_, mode::Outcome::Won) => TB_WON,
+        _ => TB_LOST,
+    }
+}
+
+/// # Safety
+///
+/// `game` has to be null or a live handle from tb_game_create.
+#[no_mangle]
+pub unsafe extern "C" fn tb_game_width(game: *const TbGame) -> i32 {
+    read(game, 0, |game| game.env.game.board[0].len() as i32)
+}
+
+/// # Safety
+///
+/// `game` has to be null or a live handle from tb_game_create.
+#[no_mangle]
+pub unsafe extern "C" fn tb_game_height(game: *const TbGame) -> i32 {
+    read(game, 0, |game| game.env.game.board.len() as i32)
+}
+
+/// The colour of the cell of the board, without the block in play.
+/// TB_EMPTY if it's empty or off the board.
+///
+/// # Safety
+///
+/// `game` has to be null or a live handle from tb_game_create.
+#[no_mangle]
+pub unsafe extern "C" fn tb_game_cell(game: *const TbGame, x: i32, y: i32) -> i32 {
+    if x < 0 || y < 0 {
+        return TB_EMPTY;
+    }
+    read(game, TB_EMPTY, |game| {
+        let board = &game.env.game.board;
+        match board.get(y as usize).and_then(|row| row.get(x as usize)) {
+            Some(Some(color)) => color_value(*color),
+            _ => TB_EMPTY,
+        }
+    })
+}
+
+/// The number of cells of the block in play.
+///
+/// # Safety
+///
+/// `game` has to be null or a live handle from tb_game_create.
+#[no_mangle]
+pub unsafe extern "C" fn tb_game_block_size(game: *const TbGame) -> i32 {
+    read(game, 0, |game| game.env.game.block.positions.len() as i32)
+}
+
+/// Sets x and y to the position of a cell of the block in play. Returns
+/// false if there's no such cell.
+///
+/// # Safety
+///
+/// `game` has to be null or a live handle from tb_game_create, and `x`
+/// and `y` null or pointers to an int32_t each.
+#[no_mangle]
+pub unsafe extern "C" fn tb_game_block_cell(
+    game: *const TbGame,
+    index: i32,
+    x: *mut i32,
+    y: *mut i32,
+) -> bool {
+    if index < 0 {
+        return false;
+    }
+    let position = read(game, None, |game| {
+        game.env.game.block.positions.get(index as usize).copied()
+    });
+    match position {
+        Some(position) => write(position.x, x) && write(position.y, y),
+        None => false,
+    }
+}
+
+/// # Safety
+///
+/// `game` has to be null or a live handle from tb_game_create.
+#[no_mangle]
+pub unsafe extern "C" fn tb_game_block_color(game: *const TbGame) -> i32 {
+    read(game, TB_EMPTY, |game| {
+        color_value(game.env.game.block.color)
+    })
+}
+
+/// # Safety
+///
+/// `game` has to be null or a live handle from tb_game_create.
+#[no_mangle]
+pub unsafe extern "C" fn tb_game_score(game: *const TbGame) -> i32 {
+    read(game, 0, |game| game.env.game.score)
+}
+
+/// # Safety
+///
+/// `game` has to be null or a live handle from tb_game_create.
+#[no_mangle]
+pub unsafe extern "C" fn tb_game_lines(game: *const TbGame) -> i32 {
+    read(game, 0, |game| game.env.game.lines)
+}
+
+/// # Safety
+///
+/// `game` has to be null or a live handle from tb_game_create.
+#[no_mangle]
+pub unsafe extern "C" fn tb_game_level(game: *const TbGame) -> i32 {
+    read(game, 0, |game| game.env.game.level)
+}
+
+fn color_value(color: block::Color) -> i32 {
+    (color.r as i32) << 16 | (color.g as i32) << 8 | color.b as i32
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn plays_through_handles() {
+        unsafe {
+            let game = tb_game_create(0, 1234);
+            assert!(!game.is_null());
+            assert_eq!(tb_game_status(game), TB_PLAYING);
+            assert_eq!(tb_game_step(game, TB_INPUT_LEFT), TB_PLAYING);
+            let (mut x, mut y) = (-1, -1);
+            assert!(tb_game_block_cell(game, 0, &mut x, &mut y));
+            assert!(x >= 0 && x < tb_game_width(game));
+            assert!(y >= 0 && y < tb_game_height(game));
+            assert!(tb_game_reset(game, 99));
+            tb_game_destroy(game);
+
+            let null = std::ptr::null_mut();
+            assert_eq!(tb_game_step(null, TB_INPUT_LEFT), TB_LOST);
+            assert!(!tb_game_reset(null, 1));
+            assert_eq!(tb_game_cell(null, 0, 0), TB_EMPTY);
+            assert!(tb_game_create(tb_mode_count(), 1).is_null());
+        }
+    }
+
+    #[test]
+    fn mode_names_are_cut_to_the_buffer() {
+        let mut buffer = [1 as c_char; 4];
+        unsafe {
+            let length = tb_mode_name(0, buffer.as_mut_ptr(), buffer.len());
+            assert!(length > 3);
+            assert_eq!(buffer[3], 0);
+            assert_eq!(tb_mode_name(tb_mode_count(), buffer.as_mut_ptr(), 4), 0);
+        }
+    }
+
+    #[test]
+    fn assets_dir_needs_pieces() {
+        let dir = std::ffi::CString::new("no/such/assets").unwrap();
+        unsafe {
+            assert!(!tb_set_assets_dir(dir.as_ptr()));
+            assert!(!tb_set_assets_dir(std::ptr::null()));
+        }
+    }
+}
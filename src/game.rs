@@ -31,10 +31,24 @@ board.
 use std::ops::Add;
 use std::time;
 
+use crate::attack;
 use crate::block;
 use crate::block::{Block, Delta, Position};
 
+use crate::config;
+use crate::coop;
+use crate::editor;
+use crate::garbage;
+use crate::highscore;
 use crate::menu;
+use crate::mode;
+use crate::puzzle;
+use crate::random;
+use crate::replay;
+use crate::stats;
+use crate::summary;
+use crate::versus;
+use crate::victory;
 
 type Dimension = Position;
 
@@ -44,25 +58,35 @@ pub const METAL_BOARD_SIZE: Dimension = Dimension { x: 24, y: 24 + 1 };
 pub const FAST_FALL_RATE: u128 = 25; // milliseconds
 pub const DEFAULT_FALL_RATE: u128 = 500; // milliseconds
 pub const GAME_OVER_PAUSE: u128 = 1000; // milliseconds
+pub const LINES_PER_LEVEL: i32 = 10;
+// A frame of a game played by a program, see step.
+pub const FRAME: time::Duration = time::Duration::from_micros(16_667);
 
-type Board = Vec<Vec<Option<block::Color>>>;
-
-pub enum Mode {
-    Classic { label: String },
-    Chill { label: String },
-    Bane { label: String },
-    Metal { label: String },
-}
+pub type Board = Vec<Vec<Option<block::Color>>>;
 
 #[derive(PartialEq, Eq)]
 pub enum State {
     Play,
+    Countdown,
     Menu,
     Quit,
     GameOver,
+    Victory,
     Paused,
+    NameEntry,
+    HighScores,
+    Summary,
+    PuzzleSelect,
+    Editor,
+    VersusSetup,
+    VersusConnecting,
+    Versus,
+    VersusResult,
+    Coop,
+    CoopResult,
 }
 
+#[derive(Clone)]
 pub enum Input {
     LeftKeyDown,
     RightKeyDown,
@@ -76,6 +100,9 @@ pub enum Input {
     SKeyUp,
     EscKeyDown,
     PKeyDown,
+    BackspaceKeyDown,
+    // Text typed while text input is enabled, e.g. during name entry.
+    Character(char),
 }
 
 impl Add for Position {
@@ -94,6 +121,17 @@ pub struct World {
     pub menu: menu::Menu,
     pub state: State,
     pub music_file: String,
+    pub config: config::Config,
+    pub high_scores: highscore::HighScores,
+    pub high_score_view: highscore::View,
+    pub player_name: String,
+    pub summary: summary::Summary,
+    pub victory: victory::Victory,
+    pub puzzle_select: puzzle::Select,
+    pub editor: editor::Editor,
+    pub versus: versus::Versus,
+    pub coop: coop::Coop,
+    pub ghost: Option<replay::Ghost>, // the mode's best run, raced while playing
 }
 
 pub struct Game {
@@ -103,89 +141,277 @@ pub struct Game {
     pub fall_rate_millis: u128, // elapsed ms before blocks drop to next row
     pub block_drop_clock: time::Instant,
     pub score: i32,
-    pub modes: Vec<Mode>,
+    pub lines: i32,
+    pub level: i32,
+    pub time_played: time::Duration,
+    pub frame_clock: time::Instant, // measures time_played while playing
+    pub stats: stats::Stats,
+    pub modes: Vec<mode::Mode>,
     pub mode_selected: usize,
+    pub rng: random::Rng,          // decides which block spawns next
+    pub blocks: Vec<block::Block>, // the blocks that can spawn in the mode
+    pub bag: Vec<usize>,           // blocks left to deal when the mode uses a bag
+    pub countdown: time::Duration, // left before the game starts
+    pub outcome: mode::Outcome,
+    pub endless: bool, // playing on after winning, until the player tops out
+    pub garbage_inserted: u32, // rows pushed in from the bottom by the mode
+    pub attack: attack::Attack, // garbage sent and received in versus
+    pub mode: mode::Mode, // the mode being played
+    pub next: Option<block::Block>, // shown as a preview, only in co-op
+    pub spawn_shift: i32, // columns right of the mode's spawn blocks are dealt at
+    // The other player's block in co-op, which this one can't move into.
+    pub other_blocks: Vec<Position>,
+    pub replay: Option<replay::Replay>, // None if not recorded from the start
+    // The time of a game stepped a frame at a time rather than following
+    // the wall clock, see step. None when played live.
+    pub clock: Option<time::Instant>,
 }
 
 pub fn initialise_world() -> World {
-    let game = initialise_game(0);
-    let menu = menu::initialise(&game);
+    let config = config::load();
+    // The saved mode may no longer exist.
+    let mode_selected = mode::initialise_modes()
+        .iter()
+        .position(|m| m.label() == config.mode)
+        .unwrap_or(0);
+    let game = initialise_game(mode_selected);
+    let menu = menu::initialise(&game, &config);
+    let high_scores = highscore::load(&game.modes);
+    let editor = editor::initialise(&game.modes, mode_selected);
+    let coop = coop::initialise(game.modes.clone(), mode_selected);
     World {
         game: game,
         menu: menu,
         state: State::Menu,
         music_file: "".to_string(),
+        high_score_view: highscore::initialise_view(mode_selected),
+        config: config,
+        high_scores: high_scores,
+        player_name: "".to_string(),
+        summary: summary::initialise(None),
+        victory: victory::initialise(false),
+        puzzle_select: puzzle::initialise_select(),
+        editor: editor,
+        versus: versus::initialise(),
+        coop: coop,
+        ghost: None,
     }
 }
 
 pub fn initialise_game(mode_selected: usize) -> Game {
-    let modes = vec![
-        Mode::Bane {
-            label: "Bane".to_string(),
-        },
-        Mode::Classic {
-            label: "Classic".to_string(),
-        },
-        Mode::Chill {
-            label: "Chill".to_string(),
-        },
-        Mode::Metal {
-            label: "Metal".to_string(),
-        },
-    ];
-
-    let mode = &modes[mode_selected];
-
-    let board_size = match mode {
-        Mode::Bane { .. } => BANE_BOARD_SIZE,
-        Mode::Metal { .. } => METAL_BOARD_SIZE,
-        Mode::Classic { .. } | Mode::Chill { .. } => CLASSIC_BOARD_SIZE,
-    };
+    let modes = mode::initialise_modes();
+    let mode = modes[mode_selected].clone();
+    initialise_game_in_mode(modes, mode_selected, mode)
+}
+
+// Starts a game in a mode that might not be one of the menu's, such as a
+// puzzle. mode_selected keeps the menu's choice for the next game.
+pub fn initialise_game_in_mode(
+    modes: Vec<mode::Mode>,
+    mode_selected: usize,
+    mode: mode::Mode,
+) -> Game {
+    initialise_seeded_game(modes, mode_selected, mode, random::random_seed())
+}
+
+// Games started from the same seed are dealt the same blocks.
+pub fn initialise_seeded_game(
+    modes: Vec<mode::Mode>,
+    mode_selected: usize,
+    mode: mode::Mode,
+    seed: u64,
+) -> Game {
+    let board_size = mode.board_size();
+
+    let blocks = block::load_blocks(mode.pieces());
+    let now = time::Instant::now();
 
-    Game {
+    let mut game = Game {
         board: vec![vec![None; board_size.x as usize]; board_size.y as usize],
-        block: block::spawn(&mode),
-        block_drop_clock: time::Instant::now(),
-        fall_rate_millis: DEFAULT_FALL_RATE,
+        // Replaced below, the mode picks the first block.
+        block: blocks[0].clone(),
+        block_drop_clock: now,
+        fall_rate_millis: mode.gravity(1),
         score: 0,
+        lines: 0,
+        level: 1,
+        time_played: time::Duration::from_millis(0),
+        frame_clock: now,
+        stats: stats::initialise(),
         block_orientation: 0,
         modes: modes,
         mode_selected: mode_selected,
+        rng: random::from_seed(seed),
+        bag: vec![],
+        blocks: blocks,
+        countdown: mode.countdown(),
+        outcome: mode::Outcome::Playing,
+        endless: false,
+        garbage_inserted: 0,
+        attack: attack::initialise(),
+        mode: mode.clone(),
+        next: None,
+        spawn_shift: 0,
+        other_blocks: vec![],
+        replay: None,
+        clock: None,
+    };
+    mode.on_start(&mut game);
+    game.replay = Some(replay::initialise(&game.board));
+    game.block = mode.next_block(&mut game);
+    stats::record_spawn(&mut game.stats, &game.block.label, game.time_played);
+    game
+}
+
+pub fn current_mode(game: &Game) -> mode::Mode {
+    game.mode.clone()
+}
+
+// The time by the game's clock, see step.
+pub fn now(game: &Game) -> time::Instant {
+    game.clock.unwrap_or_else(time::Instant::now)
+}
+
+pub fn elapsed(game: &Game, since: time::Instant) -> time::Duration {
+    now(game).saturating_duration_since(since)
+}
+
+// Moves the game's clock on by a frame of the given length, however
+// long it really took. Once a game's clock has been moved it no longer
+// follows the wall clock, time only passes when the clock is moved, so
+// games dealt from the same seed and played with the same inputs play
+// out the same.
+pub fn advance_clock(game: &mut Game, frame: time::Duration) {
+    let clock = game.clock.unwrap_or(game.frame_clock);
+    game.clock = Some(clock + frame);
+}
+
+// Puts a game whose clock was being moved back on the wall clock, from
+// the time its clock had got to.
+pub fn release_clock(game: &mut Game) {
+    let clock = match game.clock.take() {
+        Some(clock) => clock,
+        None => return,
+    };
+    let wall = time::Instant::now();
+    let moved = |instant: time::Instant| {
+        wall.checked_sub(clock.saturating_duration_since(instant))
+            .unwrap_or(wall)
+    };
+    game.frame_clock = moved(game.frame_clock);
+    game.block_drop_clock = moved(game.block_drop_clock);
+}
+
+// Plays a frame of the given length, see advance_clock.
+pub fn step(event: &Option<Input>, game: &mut Game, state: &State, frame: time::Duration) -> State {
+    advance_clock(game, frame);
+    match state {
+        State::Countdown => update_countdown(game),
+        _ => update(event, game, state),
+    }
+}
+
+// A Classic game dealt from the seed and played a frame at a time with
+// the input for each frame, until it ends or the frames run out.
+#[cfg(test)]
+pub fn played_game(seed: u64, frames: u32, input: impl Fn(u32) -> Option<Input>) -> Game {
+    let modes = mode::initialise_modes();
+    let classic = modes.iter().position(|m| m.label() == "Classic").unwrap();
+    let mode = modes[classic].clone();
+    let mut game = initialise_seeded_game(modes, classic, mode, seed);
+    let mut state = State::Play;
+    for frame in 0..frames {
+        state = step(&input(frame), &mut game, &state, FRAME);
+        if state != State::Play {
+            break;
+        }
+    }
+    game
+}
+
+// The state a new game starts in.
+pub fn start_state(game: &Game) -> State {
+    if game.countdown > time::Duration::from_millis(0) {
+        return State::Countdown;
     }
+    State::Play
+}
+
+// Nothing moves until the countdown is over.
+pub fn update_countdown(game: &mut Game) -> State {
+    let elapsed = elapsed(game, game.frame_clock);
+    game.frame_clock = now(game);
+    game.countdown = game.countdown.checked_sub(elapsed).unwrap_or_default();
+    if game.countdown > time::Duration::from_millis(0) {
+        return State::Countdown;
+    }
+    game.block_drop_clock = now(game);
+    State::Play
 }
 
 pub fn update(event: &Option<Input>, game: &mut Game, state: &State) -> State {
     let mut game_state = State::Play;
+    if *state == State::Play {
+        game.time_played += elapsed(game, game.frame_clock);
+    }
+    game.frame_clock = now(game);
+
+    let mode = current_mode(game);
     if let Some(event) = event {
         match event {
             // NOTE: DownKeyUp needs to be first in the match call otherwise
             // the DownKeyUp event will be missed if the user is holding down
             // another key.
             Input::SKeyUp | Input::SpaceKeyUp | Input::DownKeyUp => {
-                game.fall_rate_millis = DEFAULT_FALL_RATE;
+                game.fall_rate_millis = mode.gravity(game.level);
             }
             Input::LeftKeyDown => {
-                game.block.positions = move_block(&game.block, &game.board, Delta { y: 0, x: -1 });
+                shift_block(game, Delta { y: 0, x: -1 });
             }
             Input::RightKeyDown => {
-                game.block.positions = move_block(&game.block, &game.board, Delta { y: 0, x: 1 });
+                shift_block(game, Delta { y: 0, x: 1 });
             }
-            Input::UpKeyDown => {
+            Input::UpKeyDown if mode.rotate() => {
                 let (positions, orientation) =
                     rotate_block(&game.block, &game.board, game.block_orientation);
-                game.block.positions = positions;
-                game.block_orientation = orientation;
+                if !hits_other_blocks(game, &positions) {
+                    game.attack.rotated |= positions != game.block.positions;
+                    game.block.positions = positions;
+                    game.block_orientation = orientation;
+                }
             }
-            Input::DownKeyDown | Input::SpaceKeyDown | Input::SKeyDown => {
-                game.fall_rate_millis = FAST_FALL_RATE;
+            Input::DownKeyDown | Input::SpaceKeyDown | Input::SKeyDown if mode.soft_drop() => {
+                // Soft drop never slows the block down at high levels.
+                game.fall_rate_millis = FAST_FALL_RATE.min(mode.gravity(game.level));
             }
             Input::EscKeyDown | Input::PKeyDown => game_state = State::Paused,
             _ => {}
         }
+        if *state == State::Play && is_block_input(event) {
+            stats::record_input(&mut game.stats);
+        }
+    }
+
+    if *state == State::Play {
+        let outcome = match mode.on_frame(game) {
+            mode::Outcome::Playing if !game.endless => mode.outcome(game),
+            outcome => outcome,
+        };
+        match outcome {
+            mode::Outcome::Playing => {}
+            mode::Outcome::Won => {
+                game.outcome = outcome;
+                return State::Victory;
+            }
+            mode::Outcome::Lost => {
+                top_out(game);
+                return State::GameOver;
+            }
+        }
     }
 
-    if game.block_drop_clock.elapsed().as_millis() > game.fall_rate_millis {
-        game.block_drop_clock = time::Instant::now();
+    if elapsed(game, game.block_drop_clock).as_millis() > game.fall_rate_millis {
+        game.block_drop_clock = now(game);
 
         // Having the game over state allows the player to
         // soak briefly in thier defeat. Rather then a sudden loss.
@@ -198,28 +424,104 @@ pub fn update(event: &Option<Input>, game: &mut Game, state: &State) -> State {
         // to quickly move the block at the last split second and "wedge" it into
         // gaps.
         if has_block_finished_falling(&game.board, &game.block) {
-            game.board = paint_positions(&game.board, &game.block.positions, game.block.color);
+            let spin = attack::is_spin(game);
+            let locked = game.block.clone();
+            let board = paint_positions(&game.board, &game.block.positions, game.block.color);
+            stats::record_lock(&mut game.stats, &game.board, &board, game.time_played);
+            game.board = board;
+            mode.on_lock(game);
 
-            let spawned_block = block::spawn(&game.modes[game.mode_selected]);
-            if !positions_empty_on_board(&spawned_block.positions, &game.board) {
+            let spawned_block = deal_block(game);
+            // The other player's block in co-op is in the way too.
+            let blocked = hits_other_blocks(game, &spawned_block.positions);
+            if blocked || !positions_empty_on_board(&spawned_block.positions, &game.board) {
                 game_state = State::GameOver;
-                game.fall_rate_millis = GAME_OVER_PAUSE;
+                top_out(game);
             } else {
+                stats::record_spawn(&mut game.stats, &spawned_block.label, game.time_played);
                 game.block = spawned_block;
                 game.block_orientation = 0;
-                game.fall_rate_millis = DEFAULT_FALL_RATE;
+                game.attack.rotated = false;
             }
-            let (board, score) = delete_full_lines(&game.board);
+            let (board, lines) = delete_full_lines(&game.board);
             game.board = board;
-            game.score += score;
+            mode.on_line_clear(game, lines);
+            replay::record_lock(game, &locked);
+            // Garbage from the other player in versus can top out too.
+            if game_state != State::GameOver && !attack::on_lock(game, lines, spin) {
+                game_state = State::GameOver;
+                top_out(game);
+            }
+            game.fall_rate_millis = match game_state {
+                State::GameOver => GAME_OVER_PAUSE,
+                _ => mode.gravity(game.level),
+            };
             return game_state;
         }
         // Move block one square down.
-        game.block.positions = move_block(&game.block, &game.board, Delta { y: 1, x: 0 });
+        shift_block(game, Delta { y: 1, x: 0 });
     }
     return game_state;
 }
 
+// The next block from the mode, moved by spawn_shift. When the game has
+// a preview the block dealt waits there and the previewed one is played.
+pub fn deal_block(game: &mut Game) -> Block {
+    let mut block = current_mode(game).next_block(game);
+    let delta = Delta {
+        x: game.spawn_shift,
+        y: 0,
+    };
+    block.positions = block.positions.iter().map(|&p| p + delta).collect();
+    match &mut game.next {
+        Some(next) => std::mem::replace(next, block),
+        None => block,
+    }
+}
+
+// Ends the game as lost, pausing briefly before leaving the board.
+pub fn top_out(game: &mut Game) {
+    // Topping out after winning still counts as a win.
+    if !game.endless {
+        game.outcome = mode::Outcome::Lost;
+    }
+    game.block_drop_clock = now(game);
+    game.fall_rate_millis = GAME_OVER_PAUSE;
+}
+
+// Pushes garbage rows in from the bottom of the board. The block is
+// pushed up with the stack if it's in the way. Returns false if the
+// stack or the block is pushed through the top of the board.
+pub fn insert_garbage(game: &mut Game, rows: Vec<Vec<Option<block::Color>>>) -> bool {
+    let delta = Delta {
+        x: 0,
+        y: -(rows.len() as i32),
+    };
+    replay::record_garbage(game, &rows);
+    let crossed_top = garbage::insert_rows(&mut game.board, rows);
+    if !positions_empty_on_board(&game.block.positions, &game.board) {
+        let pushed: Vec<Position> = game.block.positions.iter().map(|&p| p + delta).collect();
+        if !positions_empty_on_board(&pushed, &game.board) {
+            return false;
+        }
+        game.block.positions = pushed;
+    }
+    !crossed_top
+}
+
+// Inputs that move or rotate the block.
+fn is_block_input(event: &Input) -> bool {
+    matches!(
+        event,
+        Input::LeftKeyDown
+            | Input::RightKeyDown
+            | Input::UpKeyDown
+            | Input::DownKeyDown
+            | Input::SpaceKeyDown
+            | Input::SKeyDown
+    )
+}
+
 fn paint_positions(board: &Board, positions: &Vec<Position>, color: block::Color) -> Board {
     let mut board = board.clone();
     positions
@@ -228,6 +530,23 @@ fn paint_positions(board: &Board, positions: &Vec<Position>, color: block::Color
     board
 }
 
+// Moves the block if there's room. A block that moves is no longer
+// spinning.
+fn shift_block(game: &mut Game, delta: Delta) {
+    let positions = move_block(&game.block, &game.board, delta);
+    if hits_other_blocks(game, &positions) {
+        return;
+    }
+    game.attack.rotated &= positions == game.block.positions;
+    game.block.positions = positions;
+}
+
+// Blocks don't lock on the other player's block in co-op, they wait
+// until it moves out of the way.
+fn hits_other_blocks(game: &Game, positions: &[Position]) -> bool {
+    positions.iter().any(|p| game.other_blocks.contains(p))
+}
+
 fn move_block(block: &Block, board: &Board, delta: Delta) -> Vec<Position> {
     let new_positions: Vec<Position> = block.positions.iter().map(|&p| p + delta).collect();
     if positions_empty_on_board(&new_positions, &board) {
@@ -260,24 +579,29 @@ fn can_move_here(board: &Board, p: Position) -> bool {
     return !is_occupied(board, p);
 }
 
+// Off the board nothing is in the way.
 fn is_occupied(board: &Board, position: Position) -> bool {
-    match board[position.y as usize][position.x as usize] {
-        Some(_) => true,
-        None => false,
+    if position.x < 0 || position.y < 0 {
+        return false;
     }
+    let row = board.get(position.y as usize);
+    matches!(
+        row.and_then(|row| row.get(position.x as usize)),
+        Some(Some(_))
+    )
 }
 
 fn rotate_block(block: &Block, board: &Board, orientation: u8) -> (Vec<Position>, u8) {
     let new_positions = block::rotate_block(&block, orientation);
     if new_positions.iter().all(|&p| can_move_here(&board, p)) {
-        return (new_positions, (orientation + 1) % 4);
+        return (new_positions, block::next_orientation(block, orientation));
     }
     (block.positions.clone(), orientation)
 }
 
 // Deletes full lines on board and returns te number of lines
 // deleted.
-fn delete_full_lines(board: &Board) -> (Board, i32) {
+pub fn delete_full_lines(board: &Board) -> (Board, i32) {
     let mut board = board.clone();
     let mut count = 0;
 
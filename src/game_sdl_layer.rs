@@ -3,16 +3,34 @@ use std::path::Path;
 
 use sdl2::pixels::Color;
 use sdl2::rect::{Point, Rect};
-use sdl2::render::WindowCanvas;
+use sdl2::render::{BlendMode, WindowCanvas};
 use sdl2::surface::Surface;
 use sdl2::ttf::{Font, Sdl2TtfContext};
 
+use crate::coop;
+use crate::editor;
 use crate::game;
+use crate::highscore;
 use crate::menu;
+use crate::mode;
+use crate::puzzle;
+use crate::replay;
+use crate::stats;
+use crate::summary;
+use crate::versus;
+use crate::victory;
 
+use crate::attack;
 use crate::block;
+use crate::block::Position;
 
-pub type SurfaceCache<'a> = HashMap<(String, Color), Surface<'a>>;
+// Keyed by the text, its colour and the height of the font, which tells
+// the fonts apart as each is a different size.
+pub type SurfaceCache<'a> = HashMap<(String, Color, i32), Surface<'a>>;
+
+// Text that changes every frame, e.g. a name being typed, would
+// otherwise fill the cache without end, so it's emptied at this size.
+const MAX_CACHED_SURFACES: usize = 256;
 
 const GAME_FONT_PATH: &str = "assets/fonts/muli/Muli.ttf";
 const SETTINGS_FONT_PATH: &str = "assets/fonts/JetBrainsMono-2.001/ttf/JetBrainsMono-Regular.ttf";
@@ -27,6 +45,13 @@ const DEFAULT_TEXT_COLOR: Color = Color {
     a: 255,
 };
 
+const SELECTED_TEXT_COLOR: Color = Color {
+    r: 200,
+    g: 200,
+    b: 200,
+    a: 255,
+};
+
 const BOARD_COLOR: Color = Color {
     r: 40,
     g: 40,
@@ -34,10 +59,21 @@ const BOARD_COLOR: Color = Color {
     a: 255,
 };
 
+// Alpha of the ghost's board, drawn faintly beside the game's.
+const GHOST_ALPHA: u8 = 90;
+
+const INCOMING_COLOR: Color = Color {
+    r: 208,
+    g: 49,
+    b: 3,
+    a: 255,
+};
+
 pub struct GameFonts<'ttf> {
     score: Font<'ttf, 'static>,
     title: Font<'ttf, 'static>,
     settings: Font<'ttf, 'static>,
+    table: Font<'ttf, 'static>,
 }
 
 pub struct Render<'a> {
@@ -52,10 +88,12 @@ pub fn initialise_fonts(ttf_context: &Sdl2TtfContext) -> GameFonts {
 
     let settings_font_path: &Path = Path::new(SETTINGS_FONT_PATH);
     let settings_font = ttf_context.load_font(settings_font_path, 30).unwrap();
+    let table_font = ttf_context.load_font(settings_font_path, 18).unwrap();
     GameFonts {
         score: score_font,
         title: title_font,
         settings: settings_font,
+        table: table_font,
     }
 }
 
@@ -63,6 +101,7 @@ pub fn update_and_render(
     mut render: &mut Render<'static>,
     fonts: &GameFonts,
     event: &Option<game::Input>,
+    player_inputs: &[(usize, game::Input)],
     mut world: &mut game::World,
 ) {
     match world.state {
@@ -74,13 +113,17 @@ pub fn update_and_render(
             match game::update(event, &mut world.game, &world.state) {
                 game::State::Paused => {
                     world.state = game::State::Paused;
-                    world.menu.items =
-                        menu::paused_menu_items(world.menu.music_toggle, world.menu.music_volume);
+                    world.menu.items = menu::paused_menu_items(
+                        &world.game,
+                        world.menu.music_toggle,
+                        world.menu.music_volume,
+                    );
                     world.menu.item_selected = 0;
                     world.menu.title = "Paused".to_string();
                 }
                 game::State::Menu => {
-                    world.state = game::State::Menu;
+                    stats::append_history(&world.game);
+                    world.state = highscore::game_finished(world);
                 }
                 game::State::GameOver => {
                     world.menu.items = menu::menu_items(
@@ -91,16 +134,76 @@ pub fn update_and_render(
                     world.menu.item_selected = 0;
                     world.state = game::State::GameOver;
                 }
+                game::State::Victory => {
+                    let endless = game::current_mode(&world.game).endless();
+                    world.victory = victory::initialise(endless);
+                    world.state = game::State::Victory;
+                }
                 _ => {}
             }
-            render_game(&mut render.canvas, fonts, &world.game);
+            if let Some(ghost) = &mut world.ghost {
+                replay::advance(ghost, world.game.time_played);
+            }
+            render_game(&mut render.canvas, fonts, world);
+        }
+        game::State::Countdown => {
+            world.state = game::update_countdown(&mut world.game);
+            render_game(&mut render.canvas, fonts, world);
+        }
+        game::State::Victory => {
+            victory::update(event, world);
+            render_victory(render, fonts, world);
+        }
+        game::State::NameEntry => {
+            highscore::update_name_entry(event, world);
+            render_name_entry(render, fonts, world);
+        }
+        game::State::HighScores => {
+            highscore::update(event, world);
+            render_high_scores(render, fonts, world);
+        }
+        game::State::Summary => {
+            summary::update(event, world);
+            render_summary(render, fonts, world);
+        }
+        game::State::PuzzleSelect => {
+            puzzle::update(event, world);
+            render_puzzle_select(render, fonts, world);
+        }
+        game::State::Editor => {
+            editor::update(event, world);
+            render_editor(render, fonts, world);
+        }
+        game::State::VersusSetup => {
+            versus::update_setup(event, world);
+            render_versus_setup(render, fonts, world);
+        }
+        game::State::VersusConnecting => {
+            versus::update_connecting(event, world);
+            render_versus_connecting(render, fonts, &world.versus);
+        }
+        game::State::Versus => {
+            versus::update(event, player_inputs, world);
+            render_versus(render, fonts, &world.versus);
+        }
+        game::State::VersusResult => {
+            versus::update_result(event, world);
+            render_versus_result(render, fonts, &world.versus);
+        }
+        game::State::Coop => {
+            coop::update(event, player_inputs, world);
+            render_coop(render, fonts, &world.coop);
+        }
+        game::State::CoopResult => {
+            coop::update_result(event, world);
+            render_coop_result(render, fonts, &world.coop);
         }
 
         game::State::Quit => {}
     }
 }
 
-fn render_game(canvas: &mut WindowCanvas, fonts: &GameFonts, game: &game::Game) {
+fn render_game(canvas: &mut WindowCanvas, fonts: &GameFonts, world: &game::World) {
     // render
     canvas.set_draw_color(Color::RGB(0, 0, 0));
     canvas.clear();
@@ -110,13 +213,87 @@ fn render_game(canvas: &mut WindowCanvas, fonts: &GameFonts, game: &game::Game)
         (canvas_width as f32 / 2.) as i32,
         (canvas_height as f32 / 2.) as i32,
     );
+    draw_game(
+        canvas,
+        fonts,
+        &world.game,
+        canvas_mid,
+        world.config.show_stats,
+    );
+    if let Some(ghost) = &world.ghost {
+        if world.config.show_ghost {
+            draw_ghost(canvas, fonts, ghost, &world.game, canvas_mid);
+        }
+    }
+}
 
+// Draws the ghost's board faintly, smaller, to the right of the game's
+// with how the game compares under it.
+fn draw_ghost(
+    canvas: &mut WindowCanvas,
+    fonts: &GameFonts,
+    ghost: &replay::Ghost,
+    game: &game::Game,
+    board_mid: Point,
+) {
+    let (canvas_width, _) = canvas.output_size().unwrap();
+    let board_width = (BLOCK_SIZE + GAP) * game.board[0].len() as i32;
+    let board_origin = board_origin(game, board_mid);
+    let left = board_origin.x + board_width + BLOCK_SIZE;
+    // Half size, or smaller if that doesn't fit in the window.
+    let room = canvas_width as i32 - left - BLOCK_SIZE;
+    let cell = (BLOCK_SIZE / 2).min(room / ghost.board[0].len() as i32 - GAP);
+    if cell < 2 {
+        return;
+    }
+
+    canvas.set_blend_mode(BlendMode::Blend);
+    // Don't draw the top row
+    (1..ghost.board.len()).for_each(|y| {
+        (0..ghost.board[0].len()).for_each(|x| {
+            let color = match ghost.board[y][x] {
+                Some(color) => game_color_to_sdl_color(color),
+                None => BOARD_COLOR,
+            };
+            canvas.set_draw_color(Color::RGBA(color.r, color.g, color.b, GHOST_ALPHA));
+            canvas
+                .fill_rect(Rect::new(
+                    left + (cell + GAP) * x as i32,
+                    board_origin.y + (cell + GAP) * y as i32,
+                    cell as u32,
+                    cell as u32,
+                ))
+                .unwrap();
+        })
+    });
+    canvas.set_blend_mode(BlendMode::None);
+
+    let texture_creator = canvas.texture_creator();
+    let font_surface = fonts
+        .table
+        .render(&replay::race_line(ghost, game))
+        .blended(DEFAULT_TEXT_COLOR)
+        .unwrap();
+    let texture = font_surface.as_texture(&texture_creator).unwrap();
+    let mut rect = font_surface.rect();
+    rect.reposition(Point::new(
+        left,
+        board_origin.y + (cell + GAP) * ghost.board.len() as i32 + GAP,
+    ));
+    canvas.copy(&texture, None, rect).unwrap();
+}
+
+// Draws the game with its board centred on board_mid.
+fn draw_game(
+    canvas: &mut WindowCanvas,
+    fonts: &GameFonts,
+    game: &game::Game,
+    board_mid: Point,
+    show_stats: bool,
+) {
     let board_width = (BLOCK_SIZE + GAP) * game.board[0].len() as i32;
     let board_height = (BLOCK_SIZE + GAP) * game.board.len() as i32;
-    let board_origin = Point::new(
-        canvas_mid.x - (board_width as f32 / 2.) as i32,
-        canvas_mid.y - (board_height as f32 / 2.) as i32,
-    );
+    let board_origin = board_origin(game, board_mid);
     // Draw board
     {
         // Don't draw the top row
@@ -138,23 +315,21 @@ fn render_game(canvas: &mut WindowCanvas, fonts: &GameFonts, game: &game::Game)
         });
     }
     // Draw active block on the board
-    {
-        canvas.set_draw_color(game_color_to_sdl_color(game.block.color));
+    draw_block(canvas, &game.block, board_origin);
 
-        game.block
-            .positions
-            .iter()
-            .filter(|p| p.y != 0) // Don't draw if on the top row
-            .for_each(|&p| {
-                canvas
-                    .fill_rect(Rect::new(
-                        board_origin.x + (BLOCK_SIZE + GAP) * p.x,
-                        board_origin.y + (BLOCK_SIZE + GAP) * p.y,
-                        BLOCK_SIZE as u32,
-                        BLOCK_SIZE as u32,
-                    ))
-                    .unwrap();
-            });
+    // Draw the garbage waiting to be pushed in up the left of the board
+    let incoming = attack::incoming_rows(&game.attack) as i32;
+    if incoming > 0 {
+        let height = ((BLOCK_SIZE + GAP) * incoming).min(board_height - BLOCK_SIZE);
+        canvas.set_draw_color(INCOMING_COLOR);
+        canvas
+            .fill_rect(Rect::new(
+                board_origin.x - BLOCK_SIZE / 3 - GAP,
+                board_origin.y + board_height - height,
+                (BLOCK_SIZE / 3) as u32,
+                height as u32,
+            ))
+            .unwrap();
     }
 
     // Draw score board
@@ -174,16 +349,117 @@ fn render_game(canvas: &mut WindowCanvas, fonts: &GameFonts, game: &game::Game)
         score_rect.reposition(score_board_origin);
         canvas.copy(&texture, None, score_rect).unwrap();
     }
+
+    // Draw the mode's clock under the left side of the board
+    if let Some(timer) = game::current_mode(game).timer(game) {
+        let texture_creator = canvas.texture_creator();
+        let font_surface = fonts
+            .score
+            .render(&highscore::format_precise_duration(timer.as_millis()))
+            .blended(DEFAULT_TEXT_COLOR)
+            .unwrap();
+        let texture = font_surface.as_texture(&texture_creator).unwrap();
+        let mut timer_rect = font_surface.rect();
+        timer_rect.reposition(Point::new(board_origin.x, board_origin.y + board_height));
+        canvas.copy(&texture, None, timer_rect).unwrap();
+    }
+
+    // Draw how far the player is from the mode's goal above the board
+    if let Some(progress) = game::current_mode(game).progress(game) {
+        let texture_creator = canvas.texture_creator();
+        let font_surface = fonts
+            .score
+            .render(&progress)
+            .blended(DEFAULT_TEXT_COLOR)
+            .unwrap();
+        let texture = font_surface.as_texture(&texture_creator).unwrap();
+        let mut progress_rect = font_surface.rect();
+        // The top row of the board isn't drawn, leaving room for the text.
+        progress_rect.reposition(Point::new(
+            board_mid.x - progress_rect.width() as i32 / 2,
+            board_origin.y + BLOCK_SIZE - progress_rect.height() as i32,
+        ));
+        canvas.copy(&texture, None, progress_rect).unwrap();
+    }
+
+    // Draw the countdown over the board before the game starts
+    if game.countdown.as_millis() > 0 {
+        let texture_creator = canvas.texture_creator();
+        let seconds_left = game.countdown.as_millis().div_ceil(1000);
+        let font_surface = fonts
+            .title
+            .render(&format!("{}", seconds_left))
+            .blended(SELECTED_TEXT_COLOR)
+            .unwrap();
+        let texture = font_surface.as_texture(&texture_creator).unwrap();
+        let mut rect = font_surface.rect();
+        rect.center_on(board_mid);
+        canvas.copy(&texture, None, rect).unwrap();
+    }
+
+    // Draw stats panel to the left of the board
+    if show_stats {
+        let texture_creator = canvas.texture_creator();
+        let mut y = board_origin.y + BLOCK_SIZE + GAP;
+        stats::panel_lines(game).iter().for_each(|line| {
+            let font_surface = fonts
+                .table
+                .render(line)
+                .blended(DEFAULT_TEXT_COLOR)
+                .unwrap();
+            let texture = font_surface.as_texture(&texture_creator).unwrap();
+            let mut rect = font_surface.rect();
+            rect.reposition(Point::new(
+                board_origin.x - rect.width() as i32 - BLOCK_SIZE,
+                y,
+            ));
+            canvas.copy(&texture, None, rect).unwrap();
+            y += rect.height() as i32 + 4;
+        });
+    }
 }
 
-fn render_menu(mut render: &mut Render<'static>, fonts: &GameFonts, menu: &menu::Menu) {
-    let selected_text_color: Color = Color {
-        r: 200,
-        g: 200,
-        b: 200,
-        a: 255,
-    };
+// Top left corner of the board when it's centred on board_mid.
+fn board_origin(game: &game::Game, board_mid: Point) -> Point {
+    let board_width = (BLOCK_SIZE + GAP) * game.board[0].len() as i32;
+    let board_height = (BLOCK_SIZE + GAP) * game.board.len() as i32;
+    Point::new(
+        board_mid.x - (board_width as f32 / 2.) as i32,
+        board_mid.y - (board_height as f32 / 2.) as i32,
+    )
+}
 
+fn draw_block(canvas: &mut WindowCanvas, block: &block::Block, board_origin: Point) {
+    // Don't draw if on the top row
+    let positions: Vec<Position> = block
+        .positions
+        .iter()
+        .filter(|p| p.y != 0)
+        .copied()
+        .collect();
+    draw_cells(canvas, &positions, block.color, board_origin);
+}
+
+fn draw_cells(
+    canvas: &mut WindowCanvas,
+    positions: &[Position],
+    color: block::Color,
+    origin: Point,
+) {
+    canvas.set_draw_color(game_color_to_sdl_color(color));
+    positions.iter().for_each(|&p| {
+        canvas
+            .fill_rect(Rect::new(
+                origin.x + (BLOCK_SIZE + GAP) * p.x,
+                origin.y + (BLOCK_SIZE + GAP) * p.y,
+                BLOCK_SIZE as u32,
+                BLOCK_SIZE as u32,
+            ))
+            .unwrap();
+    });
+}
+
+fn render_menu(mut render: &mut Render<'static>, fonts: &GameFonts, menu: &menu::Menu) {
     render.canvas.set_draw_color(Color::RGB(0, 0, 0));
     render.canvas.clear();
     let texture_creator = render.canvas.texture_creator();
@@ -208,7 +484,7 @@ fn render_menu(mut render: &mut Render<'static>, fonts: &GameFonts, menu: &menu:
     let mut text_offset = 50;
     menu.items.iter().enumerate().for_each(|(index, item)| {
         let color = if index == menu.item_selected as usize {
-            selected_text_color
+            SELECTED_TEXT_COLOR
         } else {
             DEFAULT_TEXT_COLOR
         };
@@ -220,7 +496,14 @@ fn render_menu(mut render: &mut Render<'static>, fonts: &GameFonts, menu: &menu:
             | menu::Item::Resume { label }
             | menu::Item::EndGame { label }
             | menu::Item::Music { label }
-            | menu::Item::MusicVolume { label } => label,
+            | menu::Item::MusicVolume { label }
+            | menu::Item::HighScores { label }
+            | menu::Item::Puzzles { label }
+            | menu::Item::Editor { label }
+            | menu::Item::Versus { label }
+            | menu::Item::Coop { label }
+            | menu::Item::Continue { label }
+            | menu::Item::SaveAndQuit { label } => label,
         };
 
         // Rendering font is expensive so use a simple surface cache
@@ -238,22 +521,747 @@ fn render_menu(mut render: &mut Render<'static>, fonts: &GameFonts, menu: &menu:
     });
 }
 
+fn render_name_entry(render: &mut Render<'static>, fonts: &GameFonts, world: &game::World) {
+    render.canvas.set_draw_color(Color::RGB(0, 0, 0));
+    render.canvas.clear();
+
+    let (canvas_width, canvas_height) = render.canvas.output_size().unwrap();
+    let mid_x = (canvas_width as f32 / 2.) as i32;
+    let mid_y = (canvas_height as f32 / 2.) as i32;
+
+    let title = "New High Score".to_string();
+    draw_text_centered(
+        render,
+        &fonts.title,
+        &title,
+        DEFAULT_TEXT_COLOR,
+        mid_x,
+        mid_y - 150,
+    );
+
+    let score = format!("Score: {}", world.game.score);
+    draw_text_centered(
+        render,
+        &fonts.score,
+        &score,
+        DEFAULT_TEXT_COLOR,
+        mid_x,
+        mid_y - 50,
+    );
+
+    // Pad the name so the text doesn't shift around while typing.
+    let name = format!(
+        "{:<width$}",
+        format!("{}_", world.high_score_view.name),
+        width = highscore::MAX_NAME_LENGTH + 1
+    );
+    draw_text_centered(
+        render,
+        &fonts.settings,
+        &name,
+        SELECTED_TEXT_COLOR,
+        mid_x,
+        mid_y + 25,
+    );
+}
+
+fn render_high_scores(render: &mut Render<'static>, fonts: &GameFonts, world: &game::World) {
+    render.canvas.set_draw_color(Color::RGB(0, 0, 0));
+    render.canvas.clear();
+
+    let (canvas_width, canvas_height) = render.canvas.output_size().unwrap();
+    let mid_x = (canvas_width as f32 / 2.) as i32;
+    let mid_y = (canvas_height as f32 / 2.) as i32;
+    let view = &world.high_score_view;
+    let mode = world.game.modes[view.mode_selected].label();
+    let ranking = world.game.modes[view.mode_selected].ranking();
+
+    let title = "High Scores".to_string();
+    draw_text_centered(
+        render,
+        &fonts.title,
+        &title,
+        DEFAULT_TEXT_COLOR,
+        mid_x,
+        mid_y - 250,
+    );
+
+    let mode_label = format!("< {} >", mode);
+    draw_text_centered(
+        render,
+        &fonts.settings,
+        &mode_label,
+        SELECTED_TEXT_COLOR,
+        mid_x,
+        mid_y - 170,
+    );
+
+    let mut y = mid_y - 110;
+    let header = highscore::table_header();
+    draw_text_centered(render, &fonts.table, &header, DEFAULT_TEXT_COLOR, mid_x, y);
+
+    let table = highscore::table(&world.high_scores, mode);
+    if table.is_empty() {
+        let empty = "No scores yet".to_string();
+        draw_text_centered(
+            render,
+            &fonts.table,
+            &empty,
+            DEFAULT_TEXT_COLOR,
+            mid_x,
+            y + 60,
+        );
+    }
+    table.iter().enumerate().for_each(|(rank, entry)| {
+        y += 30;
+        let color = if view.highlight == Some(rank) {
+            SELECTED_TEXT_COLOR
+        } else {
+            DEFAULT_TEXT_COLOR
+        };
+        let row = highscore::format_entry(rank, entry, ranking);
+        draw_text_centered(render, &fonts.table, &row, color, mid_x, y);
+    });
+}
+
+fn render_summary(render: &mut Render<'static>, fonts: &GameFonts, world: &game::World) {
+    render.canvas.set_draw_color(Color::RGB(0, 0, 0));
+    render.canvas.clear();
+
+    let (canvas_width, canvas_height) = render.canvas.output_size().unwrap();
+    let mid_x = (canvas_width as f32 / 2.) as i32;
+    let mid_y = (canvas_height as f32 / 2.) as i32;
+    let game = &world.game;
+
+    let title = match game.outcome {
+        mode::Outcome::Won => "Victory",
+        _ => "Game Over",
+    }
+    .to_string();
+    draw_text_centered(
+        render,
+        &fonts.title,
+        &title,
+        DEFAULT_TEXT_COLOR,
+        mid_x,
+        mid_y - 300,
+    );
+
+    let mode = game.mode.label().to_string();
+    draw_text_centered(
+        render,
+        &fonts.score,
+        &mode,
+        DEFAULT_TEXT_COLOR,
+        mid_x,
+        mid_y - 230,
+    );
+
+    // Stats on the left, block histogram on the right.
+    let column_top = mid_y - 170;
+    summary::stats_lines(game)
+        .iter()
+        .enumerate()
+        .for_each(|(i, line)| {
+            let y = column_top + 30 * i as i32;
+            draw_text_centered(
+                render,
+                &fonts.table,
+                line,
+                DEFAULT_TEXT_COLOR,
+                mid_x - 180,
+                y,
+            );
+        });
+
+    let histogram = summary::histogram(game);
+    let max_count = histogram.iter().map(|&(_, n)| n).max().unwrap_or(0).max(1);
+    let bar_max_width = 160;
+    histogram
+        .iter()
+        .enumerate()
+        .for_each(|(i, (label, count))| {
+            let y = column_top + 25 * i as i32;
+            let name = format!("{:>7}", label.name());
+            draw_text_centered(
+                render,
+                &fonts.table,
+                &name,
+                DEFAULT_TEXT_COLOR,
+                mid_x + 80,
+                y,
+            );
+
+            let bar_width = (bar_max_width * count / max_count) as i32;
+            render.canvas.set_draw_color(BOARD_COLOR);
+            render
+                .canvas
+                .fill_rect(Rect::new(mid_x + 130, y + 4, bar_max_width, 16))
+                .unwrap();
+            if bar_width > 0 {
+                render.canvas.set_draw_color(DEFAULT_TEXT_COLOR);
+                render
+                    .canvas
+                    .fill_rect(Rect::new(mid_x + 130, y + 4, bar_width as u32, 16))
+                    .unwrap();
+            }
+            let count = format!("{}", count);
+            draw_text_centered(
+                render,
+                &fonts.table,
+                &count,
+                DEFAULT_TEXT_COLOR,
+                mid_x + 320,
+                y,
+            );
+        });
+
+    let best = summary::personal_best_line(&world.summary, game);
+    draw_text_centered(
+        render,
+        &fonts.score,
+        &best,
+        SELECTED_TEXT_COLOR,
+        mid_x,
+        mid_y + 90,
+    );
+
+    let mut y = mid_y + 160;
+    world
+        .summary
+        .items
+        .iter()
+        .enumerate()
+        .for_each(|(index, item)| {
+            let color = if index == world.summary.item_selected {
+                SELECTED_TEXT_COLOR
+            } else {
+                DEFAULT_TEXT_COLOR
+            };
+            let label = summary::item_label(item).to_string();
+            draw_text_centered(render, &fonts.settings, &label, color, mid_x, y);
+            y += 50;
+        });
+}
+
+fn render_puzzle_select(render: &mut Render<'static>, fonts: &GameFonts, world: &game::World) {
+    render.canvas.set_draw_color(Color::RGB(0, 0, 0));
+    render.canvas.clear();
+
+    let (canvas_width, canvas_height) = render.canvas.output_size().unwrap();
+    let mid_x = (canvas_width as f32 / 2.) as i32;
+    let mid_y = (canvas_height as f32 / 2.) as i32;
+    let select = &world.puzzle_select;
+
+    let title = "Puzzles".to_string();
+    draw_text_centered(
+        render,
+        &fonts.title,
+        &title,
+        DEFAULT_TEXT_COLOR,
+        mid_x,
+        mid_y - 250,
+    );
+
+    let pack = match select.packs.get(select.pack_selected) {
+        Some(pack) => pack,
+        None => {
+            let empty = "No puzzles found".to_string();
+            draw_text_centered(
+                render,
+                &fonts.table,
+                &empty,
+                DEFAULT_TEXT_COLOR,
+                mid_x,
+                mid_y - 110,
+            );
+            return;
+        }
+    };
+
+    let pack_label = format!("< {} >", pack.name);
+    draw_text_centered(
+        render,
+        &fonts.settings,
+        &pack_label,
+        SELECTED_TEXT_COLOR,
+        mid_x,
+        mid_y - 170,
+    );
+
+    let mut y = mid_y - 110;
+    pack.puzzles.iter().enumerate().for_each(|(index, p)| {
+        let color = if index == select.puzzle_selected {
+            SELECTED_TEXT_COLOR
+        } else {
+            DEFAULT_TEXT_COLOR
+        };
+        let row = format!(
+            "{:>2}. {:<20} {:<16} {} pieces",
+            index + 1,
+            p.name,
+            puzzle::goal_label(p.goal),
+            p.sequence.len()
+        );
+        draw_text_centered(render, &fonts.table, &row, color, mid_x, y);
+        y += 30;
+    });
+}
+
+fn render_editor(render: &mut Render<'static>, fonts: &GameFonts, world: &game::World) {
+    render.canvas.set_draw_color(Color::RGB(0, 0, 0));
+    render.canvas.clear();
+
+    let (canvas_width, canvas_height) = render.canvas.output_size().unwrap();
+    let canvas_mid = Point::new(
+        (canvas_width as f32 / 2.) as i32,
+        (canvas_height as f32 / 2.) as i32,
+    );
+    let editor = &world.editor;
+
+    let board_width = (BLOCK_SIZE + GAP) * editor.board[0].len() as i32;
+    let board_height = (BLOCK_SIZE + GAP) * editor.board.len() as i32;
+    let board_origin = Point::new(
+        canvas_mid.x - (board_width as f32 / 2.) as i32,
+        canvas_mid.y - (board_height as f32 / 2.) as i32,
+    );
+    let cell_rect = |x: i32, y: i32| {
+        Rect::new(
+            board_origin.x + (BLOCK_SIZE + GAP) * x,
+            board_origin.y + (BLOCK_SIZE + GAP) * y,
+            BLOCK_SIZE as u32,
+            BLOCK_SIZE as u32,
+        )
+    };
+
+    // Draw board, without the top row as in the game
+    (1..editor.board.len()).for_each(|y| {
+        (0..editor.board[0].len()).for_each(|x| {
+            match editor.board[y][x] {
+                Some(color) => render.canvas.set_draw_color(game_color_to_sdl_color(color)),
+                None => render.canvas.set_draw_color(BOARD_COLOR),
+            }
+            render
+                .canvas
+                .fill_rect(cell_rect(x as i32, y as i32))
+                .unwrap();
+        })
+    });
+
+    // Outline the cursor in the colour that would be painted
+    let color = editor.palette[editor.color_selected];
+    render.canvas.set_draw_color(SELECTED_TEXT_COLOR);
+    render
+        .canvas
+        .draw_rect(cell_rect(editor.cursor.x, editor.cursor.y))
+        .unwrap();
+    let mut swatch = cell_rect(editor.cursor.x, editor.cursor.y);
+    swatch.resize(BLOCK_SIZE as u32 / 3, BLOCK_SIZE as u32 / 3);
+    swatch.center_on(cell_rect(editor.cursor.x, editor.cursor.y).center());
+    render.canvas.set_draw_color(game_color_to_sdl_color(color));
+    render.canvas.fill_rect(swatch).unwrap();
+
+    // Draw what will be saved to the right of the board
+    let mode = &world.game.modes[editor.mode_selected];
+    let piece = editor.blocks[editor.piece_selected].label.name();
+    let sequence = match editor.sequence.is_empty() {
+        true => "-".to_string(),
+        false => editor.sequence.join(" "),
+    };
+    let mut panel_lines = vec![
+        format!("Mode      {}", mode.label()),
+        format!("Goal      {}", puzzle::goal_label(editor.goal)),
+        format!("Piece     {}", piece),
+        format!("Sequence  {}", sequence),
+        "".to_string(),
+    ];
+    panel_lines.extend(editor::help_lines().iter().map(|line| line.to_string()));
+    let texture_creator = render.canvas.texture_creator();
+    let mut y = board_origin.y + BLOCK_SIZE + GAP;
+    panel_lines.iter().for_each(|line| {
+        // Fonts can't render empty text
+        if !line.is_empty() {
+            let font_surface = surface_from_cache(render, &fonts.table, line, DEFAULT_TEXT_COLOR);
+            let texture = font_surface.as_texture(&texture_creator).unwrap();
+            let mut rect = font_surface.rect();
+            rect.reposition(Point::new(board_origin.x + board_width + BLOCK_SIZE, y));
+            render.canvas.copy(&texture, None, rect).unwrap();
+        }
+        y += 24;
+    });
+
+    // The result of the last save or try
+    if !editor.message.is_empty() {
+        draw_text_centered(
+            render,
+            &fonts.table,
+            &editor.message,
+            SELECTED_TEXT_COLOR,
+            canvas_mid.x,
+            board_origin.y + board_height,
+        );
+    }
+}
+
+fn render_versus_setup(render: &mut Render<'static>, fonts: &GameFonts, world: &game::World) {
+    render.canvas.set_draw_color(Color::RGB(0, 0, 0));
+    render.canvas.clear();
+
+    let (canvas_width, canvas_height) = render.canvas.output_size().unwrap();
+    let mid_x = (canvas_width as f32 / 2.) as i32;
+    let mid_y = (canvas_height as f32 / 2.) as i32;
+    let versus = &world.versus;
+
+    let title = "Versus".to_string();
+    draw_text_centered(
+        render,
+        &fonts.title,
+        &title,
+        DEFAULT_TEXT_COLOR,
+        mid_x,
+        mid_y - 250,
+    );
+
+    let mode = world.game.modes[world.game.mode_selected]
+        .label()
+        .to_string();
+    draw_text_centered(
+        render,
+        &fonts.score,
+        &mode,
+        DEFAULT_TEXT_COLOR,
+        mid_x,
+        mid_y - 170,
+    );
+
+    let keys = "Player 1: A D W S    Player 2: Arrows".to_string();
+    draw_text_centered(
+        render,
+        &fonts.table,
+        &keys,
+        DEFAULT_TEXT_COLOR,
+        mid_x,
+        mid_y - 120,
+    );
+
+    let mut y = mid_y - 40;
+    versus
+        .setup_items
+        .iter()
+        .enumerate()
+        .for_each(|(index, item)| {
+            let color = if index == versus.setup_selected {
+                SELECTED_TEXT_COLOR
+            } else {
+                DEFAULT_TEXT_COLOR
+            };
+            let label = versus::setup_label(item, versus);
+            draw_text_centered(render, &fonts.settings, &label, color, mid_x, y);
+            y += 50;
+        });
+
+    if !versus.message.is_empty() {
+        draw_text_centered(
+            render,
+            &fonts.table,
+            &versus.message,
+            SELECTED_TEXT_COLOR,
+            mid_x,
+            y + 20,
+        );
+    }
+}
+
+fn render_versus_connecting(
+    render: &mut Render<'static>,
+    fonts: &GameFonts,
+    versus: &versus::Versus,
+) {
+    render.canvas.set_draw_color(Color::RGB(0, 0, 0));
+    render.canvas.clear();
+
+    let (canvas_width, canvas_height) = render.canvas.output_size().unwrap();
+    let mid_x = (canvas_width as f32 / 2.) as i32;
+    let mid_y = (canvas_height as f32 / 2.) as i32;
+
+    let title = "Versus".to_string();
+    draw_text_centered(
+        render,
+        &fonts.title,
+        &title,
+        DEFAULT_TEXT_COLOR,
+        mid_x,
+        mid_y - 250,
+    );
+    draw_text_centered(
+        render,
+        &fonts.table,
+        &versus.message,
+        SELECTED_TEXT_COLOR,
+        mid_x,
+        mid_y - 40,
+    );
+    let cancel = "Esc to cancel".to_string();
+    draw_text_centered(
+        render,
+        &fonts.table,
+        &cancel,
+        DEFAULT_TEXT_COLOR,
+        mid_x,
+        mid_y + 10,
+    );
+}
+
+fn render_versus(render: &mut Render<'static>, fonts: &GameFonts, versus: &versus::Versus) {
+    render.canvas.set_draw_color(Color::RGB(0, 0, 0));
+    render.canvas.clear();
+
+    let (canvas_width, canvas_height) = render.canvas.output_size().unwrap();
+    let n_players = versus.players.len() as i32;
+    versus.players.iter().enumerate().for_each(|(i, player)| {
+        // Each board is centred in its share of the window.
+        let board_mid = Point::new(
+            canvas_width as i32 * (2 * i as i32 + 1) / (2 * n_players),
+            (canvas_height as f32 / 2.) as i32,
+        );
+        draw_game(&mut render.canvas, fonts, &player.game, board_mid, false);
+
+        let board_height = (BLOCK_SIZE + GAP) * player.game.board.len() as i32;
+        let label = versus::player_label(versus, i);
+        draw_text_centered(
+            render,
+            &fonts.score,
+            &label,
+            DEFAULT_TEXT_COLOR,
+            board_mid.x,
+            board_mid.y - board_height / 2 - 30,
+        );
+    });
+}
+
+fn render_versus_result(render: &mut Render<'static>, fonts: &GameFonts, versus: &versus::Versus) {
+    render.canvas.set_draw_color(Color::RGB(0, 0, 0));
+    render.canvas.clear();
+
+    let (canvas_width, canvas_height) = render.canvas.output_size().unwrap();
+    let mid_x = (canvas_width as f32 / 2.) as i32;
+    let mid_y = (canvas_height as f32 / 2.) as i32;
+
+    let title = versus::result_title(versus);
+    draw_text_centered(
+        render,
+        &fonts.title,
+        &title,
+        SELECTED_TEXT_COLOR,
+        mid_x,
+        mid_y - 250,
+    );
+
+    let mut y = mid_y - 150;
+    versus::player_lines(versus).iter().for_each(|line| {
+        draw_text_centered(render, &fonts.table, line, DEFAULT_TEXT_COLOR, mid_x, y);
+        y += 30;
+    });
+
+    let mut y = mid_y;
+    versus
+        .result_items
+        .iter()
+        .enumerate()
+        .for_each(|(index, item)| {
+            let color = if index == versus.result_selected {
+                SELECTED_TEXT_COLOR
+            } else {
+                DEFAULT_TEXT_COLOR
+            };
+            let label = versus::result_label(item).to_string();
+            draw_text_centered(render, &fonts.settings, &label, color, mid_x, y);
+            y += 50;
+        });
+}
+
+fn render_coop(render: &mut Render<'static>, fonts: &GameFonts, coop: &coop::Coop) {
+    render.canvas.set_draw_color(Color::RGB(0, 0, 0));
+    render.canvas.clear();
+
+    let (canvas_width, canvas_height) = render.canvas.output_size().unwrap();
+    let canvas_mid = Point::new(
+        (canvas_width as f32 / 2.) as i32,
+        (canvas_height as f32 / 2.) as i32,
+    );
+    draw_game(&mut render.canvas, fonts, &coop.game, canvas_mid, false);
+    let board_origin = board_origin(&coop.game, canvas_mid);
+    draw_block(&mut render.canvas, &coop.seat.block, board_origin);
+
+    // Each player's preview on their side of the board
+    let board_width = (BLOCK_SIZE + GAP) * coop.game.board[0].len() as i32;
+    let previews = [(&coop.game.next, -1), (&coop.seat.next, 1)];
+    previews
+        .iter()
+        .enumerate()
+        .for_each(|(player, (next, side))| {
+            let next = match next {
+                Some(next) => next,
+                None => return,
+            };
+            let min_x = next.positions.iter().map(|p| p.x).min().unwrap_or(0);
+            let max_x = next.positions.iter().map(|p| p.x).max().unwrap_or(0);
+            let min_y = next.positions.iter().map(|p| p.y).min().unwrap_or(0);
+            let preview_width = (BLOCK_SIZE + GAP) * (max_x - min_x + 1);
+            let x = match side {
+                -1 => board_origin.x - BLOCK_SIZE - preview_width,
+                _ => board_origin.x + board_width + BLOCK_SIZE,
+            };
+            let y = board_origin.y + 3 * (BLOCK_SIZE + GAP);
+            let label = format!("Player {}", player + 1);
+            draw_text_centered(
+                render,
+                &fonts.score,
+                &label,
+                DEFAULT_TEXT_COLOR,
+                x + preview_width / 2,
+                board_origin.y + BLOCK_SIZE,
+            );
+            let cells: Vec<Position> = next
+                .positions
+                .iter()
+                .map(|p| Position {
+                    x: p.x - min_x,
+                    y: p.y - min_y,
+                })
+                .collect();
+            draw_cells(&mut render.canvas, &cells, next.color, Point::new(x, y));
+        });
+}
+
+fn render_coop_result(render: &mut Render<'static>, fonts: &GameFonts, coop: &coop::Coop) {
+    render.canvas.set_draw_color(Color::RGB(0, 0, 0));
+    render.canvas.clear();
+
+    let (canvas_width, canvas_height) = render.canvas.output_size().unwrap();
+    let mid_x = (canvas_width as f32 / 2.) as i32;
+    let mid_y = (canvas_height as f32 / 2.) as i32;
+
+    let title = "Game over".to_string();
+    draw_text_centered(
+        render,
+        &fonts.title,
+        &title,
+        SELECTED_TEXT_COLOR,
+        mid_x,
+        mid_y - 250,
+    );
+    let line = coop::result_line(coop);
+    draw_text_centered(
+        render,
+        &fonts.table,
+        &line,
+        DEFAULT_TEXT_COLOR,
+        mid_x,
+        mid_y - 150,
+    );
+
+    let mut y = mid_y;
+    coop.result_items
+        .iter()
+        .enumerate()
+        .for_each(|(index, item)| {
+            let color = if index == coop.result_selected {
+                SELECTED_TEXT_COLOR
+            } else {
+                DEFAULT_TEXT_COLOR
+            };
+            let label = coop::result_label(item).to_string();
+            draw_text_centered(render, &fonts.settings, &label, color, mid_x, y);
+            y += 50;
+        });
+}
+
+fn render_victory(render: &mut Render<'static>, fonts: &GameFonts, world: &game::World) {
+    render.canvas.set_draw_color(Color::RGB(0, 0, 0));
+    render.canvas.clear();
+
+    let (canvas_width, canvas_height) = render.canvas.output_size().unwrap();
+    let mid_x = (canvas_width as f32 / 2.) as i32;
+    let mid_y = (canvas_height as f32 / 2.) as i32;
+    let game = &world.game;
+
+    let title = "Victory!".to_string();
+    draw_text_centered(
+        render,
+        &fonts.title,
+        &title,
+        SELECTED_TEXT_COLOR,
+        mid_x,
+        mid_y - 300,
+    );
+
+    let mode = game.mode.label().to_string();
+    draw_text_centered(
+        render,
+        &fonts.score,
+        &mode,
+        DEFAULT_TEXT_COLOR,
+        mid_x,
+        mid_y - 230,
+    );
+
+    let mut y = mid_y - 170;
+    summary::stats_lines(game).iter().for_each(|line| {
+        draw_text_centered(render, &fonts.table, line, DEFAULT_TEXT_COLOR, mid_x, y);
+        y += 30;
+    });
+
+    let mut y = mid_y + 100;
+    world
+        .victory
+        .items
+        .iter()
+        .enumerate()
+        .for_each(|(index, item)| {
+            let color = if index == world.victory.item_selected {
+                SELECTED_TEXT_COLOR
+            } else {
+                DEFAULT_TEXT_COLOR
+            };
+            let label = victory::item_label(item).to_string();
+            draw_text_centered(render, &fonts.settings, &label, color, mid_x, y);
+            y += 50;
+        });
+}
+
+// Draws text with its top edge at y, horizontally centred on x.
+fn draw_text_centered(
+    render: &mut Render<'static>,
+    font: &Font<'_, 'static>,
+    text: &String,
+    color: Color,
+    x: i32,
+    y: i32,
+) {
+    let texture_creator = render.canvas.texture_creator();
+    let font_surface = surface_from_cache(render, font, text, color);
+    let texture = font_surface.as_texture(&texture_creator).unwrap();
+    let mut rect = font_surface.rect();
+    rect.reposition(Point::new(x - (rect.width() as f32 / 2.) as i32, y));
+    render.canvas.copy(&texture, None, rect).unwrap();
+}
+
 fn surface_from_cache<'a, 'ttf>(
     render: &'a mut Render,
     font: &Font<'ttf, 'static>,
     label: &String,
     color: Color,
 ) -> &'a Surface<'a> {
-    if !render
-        .surface_cache
-        .contains_key(&(label.to_string(), color))
-    {
-        let font_surface = font.render(&label).blended(color).unwrap();
-        render
-            .surface_cache
-            .insert((label.to_string(), color), font_surface);
+    let key = (label.to_string(), color, font.height());
+    if !render.surface_cache.contains_key(&key) {
+        if render.surface_cache.len() >= MAX_CACHED_SURFACES {
+            render.surface_cache.clear();
+        }
+        let font_surface = font.render(label).blended(color).unwrap();
+        render.surface_cache.insert(key.clone(), font_surface);
     }
-    &render.surface_cache[&(label.to_string(), color)]
+    &render.surface_cache[&key]
 }
 
 fn game_color_to_sdl_color(color: block::Color) -> Color {
@@ -0,0 +1,119 @@
+/*
+
+Garbage rows are full rows of GARBAGE_COLOR cells with a single hole,
+added to the board by modes such as Dig. A row counts as garbage while
+any of its garbage cells remain, which is until it is cleared, so the
+board itself records which rows are garbage.
+
+*/
+
+use rand::Rng;
+
+use crate::block;
+use crate::game::Board;
+use crate::random;
+
+pub fn row(width: usize, hole: usize) -> Vec<Option<block::Color>> {
+    (0..width)
+        .map(|x| {
+            if x == hole {
+                None
+            } else {
+                Some(block::GARBAGE_COLOR)
+            }
+        })
+        .collect()
+}
+
+// Replaces the bottom rows of the board with garbage, each with its
+// hole in a random column.
+pub fn fill(board: &mut Board, rows: usize, rng: &mut random::Rng) {
+    let width = board[0].len();
+    let height = board.len();
+    board
+        .iter_mut()
+        .skip(height - rows.min(height - 1))
+        .for_each(|r| *r = row(width, rng.gen_range(0, width)));
+}
+
+// Pushes the rows in from the bottom, moving the rest of the board up.
+// Returns true if the stack crossed into the hidden top row or was
+// pushed off the board.
+pub fn insert_rows(board: &mut Board, rows: Vec<Vec<Option<block::Color>>>) -> bool {
+    let is_filled = |row: &Vec<Option<block::Color>>| row.iter().any(|cell| cell.is_some());
+    let pushed_off = board
+        .drain(..rows.len().min(board.len()))
+        .any(|r| is_filled(&r));
+    board.extend(rows);
+    pushed_off || is_filled(&board[0])
+}
+
+pub fn is_garbage_row(row: &[Option<block::Color>]) -> bool {
+    row.contains(&Some(block::GARBAGE_COLOR))
+}
+
+pub fn count_rows(board: &Board) -> usize {
+    board.iter().filter(|r| is_garbage_row(r)).count()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn empty_board(width: usize, height: usize) -> Board {
+        vec![vec![None; width]; height]
+    }
+
+    #[test]
+    fn rows_have_one_hole() {
+        let garbage = row(5, 2);
+        assert_eq!(garbage.len(), 5);
+        assert!(garbage[2].is_none());
+        assert_eq!(garbage.iter().filter(|c| c.is_some()).count(), 4);
+        assert!(is_garbage_row(&garbage));
+        assert!(!is_garbage_row(&[
+            None,
+            Some(block::Color { r: 1, g: 2, b: 3 })
+        ]));
+    }
+
+    #[test]
+    fn fills_the_bottom_rows() {
+        let mut rng = random::from_seed(5);
+        let mut board = empty_board(6, 10);
+        fill(&mut board, 4, &mut rng);
+        assert_eq!(count_rows(&board), 4);
+        assert!(board[..6].iter().flatten().all(|c| c.is_none()));
+        assert!(board[6..]
+            .iter()
+            .all(|r| r.iter().filter(|c| c.is_none()).count() == 1));
+
+        // The hidden top row is always left empty.
+        let mut board = empty_board(6, 10);
+        fill(&mut board, 20, &mut rng);
+        assert_eq!(count_rows(&board), 9);
+        assert!(board[0].iter().all(|c| c.is_none()));
+    }
+
+    #[test]
+    fn inserted_rows_push_the_board_up() {
+        let mut board = empty_board(4, 6);
+        board[5][0] = Some(block::GARBAGE_COLOR);
+        assert!(!insert_rows(&mut board, vec![row(4, 1), row(4, 2)]));
+        assert_eq!(board.len(), 6);
+        assert!(board[3][0] == Some(block::GARBAGE_COLOR));
+        assert!(board[4][1].is_none() && board[5][2].is_none());
+        assert_eq!(count_rows(&board), 3);
+
+        // Reaching the hidden top row.
+        assert!(insert_rows(
+            &mut board,
+            vec![row(4, 0), row(4, 0), row(4, 0)]
+        ));
+        // Pushing the stack off the board.
+        let mut board = empty_board(4, 3);
+        board[1][0] = Some(block::GARBAGE_COLOR);
+        assert!(insert_rows(&mut board, vec![row(4, 0), row(4, 0)]));
+        assert_eq!(board.len(), 3);
+    }
+}
@@ -0,0 +1,275 @@
+/*
+
+An environment for training bots by reinforcement learning, in the
+style of OpenAI Gym, with no window so it can run as fast as the CPU
+allows. The bot resets the environment to a new game from a seed, then
+steps it with actions, each returning what the bot sees, its reward,
+whether the game is done and what happened:
+
+    let mut env = gym::initialise(mode, gym::Rewards::default());
+    let mut observation = gym::reset(&mut env, seed);
+    loop {
+        let action = gym::Action::Place(observation.placements[0]);
+        let (next, reward, done, info) = gym::step(&mut env, &action);
+        ...
+    }
+
+Actions are either the inputs of a player, a frame each, or where to
+place the block in play, taking the frames needed to steer it there
+and drop it. Placements are checked against those in the observation,
+a placement that can't be made plays nothing and is marked invalid.
+
+Any mode can be played, including Bane. Games are dealt the same
+blocks from the same seed, with the next block previewed, and time
+passes a frame at a time, see game::step, so a game played again with
+the same actions plays out the same. The game is done once it's won or
+the bot tops out.
+
+The reward of a step adds up each of the Rewards for what happened:
+lines cleared, holes made under the stack, surviving the step and
+topping out. By default it's the lines cleared.
+
+*/
+
+use std::time;
+
+use crate::block;
+use crate::cpu;
+use crate::game;
+use crate::mode;
+use crate::stats;
+
+// A placement steered for longer than this has gone wrong, e.g. the
+// mode never lets the block lock.
+const MAX_PLACE_FRAMES: u32 = 60 * 60;
+
+pub struct Rewards {
+    pub line: f32,     // for each line cleared
+    pub hole: f32,     // for each hole made, taken away for each filled
+    pub survival: f32, // for each step the game goes on
+    pub top_out: f32,  // once, for topping out
+}
+
+impl Default for Rewards {
+    fn default() -> Self {
+        Rewards {
+            line: 1.,
+            hole: 0.,
+            survival: 0.,
+            top_out: 0.,
+        }
+    }
+}
+
+pub enum Action {
+    Input(game::Input),
+    Wait,
+    Place(cpu::Target),
+}
+
+pub struct Observation {
+    pub board: game::Board,
+    pub block: block::Block, // in play
+    pub orientation: u8,
+    pub next: Option<block::Block>,
+    pub placements: Vec<cpu::Target>, // where the block can be placed
+    pub score: i32,
+    pub lines: i32,
+    pub level: i32,
+    pub time_played: time::Duration,
+}
+
+pub struct Info {
+    pub lines: i32,  // cleared in the step
+    pub holes: i32,  // made in the step, negative if more were filled
+    pub frames: u32, // played in the step
+    pub outcome: mode::Outcome,
+    pub invalid: bool, // the placement couldn't be made
+}
+
+pub struct Env {
+    pub mode: mode::Mode,
+    pub rewards: Rewards,
+    pub game: game::Game,
+    pub state: game::State, // Play until the game is done
+}
+
+// The environment needs a reset before it's stepped.
+pub fn initialise(mode: mode::Mode, rewards: Rewards) -> Env {
+    Env {
+        game: new_game(vec![mode.clone()], 0, mode.clone(), 0),
+        mode: mode,
+        rewards: rewards,
+        state: game::State::GameOver,
+    }
+}
+
+// A game for a program to play, started without a countdown and showing
+// the next block. Also used by bots, see bot.rs.
+pub fn new_game(
+    modes: Vec<mode::Mode>,
+    mode_selected: usize,
+    mode: mode::Mode,
+    seed: u64,
+) -> game::Game {
+    let mut game = game::initialise_seeded_game(modes, mode_selected, mode, seed);
+    game.countdown = time::Duration::from_millis(0);
+    let next = game::deal_block(&mut game);
+    game.next = Some(next);
+    game
+}
+
+pub fn reset(env: &mut Env, seed: u64) -> Observation {
+    env.game = new_game(vec![env.mode.clone()], 0, env.mode.clone(), seed);
+    // Stops the game following the wall clock.
+    game::advance_clock(&mut env.game, time::Duration::from_millis(0));
+    env.state = game::State::Play;
+    observe(&env.game)
+}
+
+pub fn step(env: &mut Env, action: &Action) -> (Observation, f32, bool, Info) {
+    let lines = env.game.lines;
+    let holes = stats::count_holes(&env.game.board) as i32;
+    let mut info = Info {
+        lines: 0,
+        holes: 0,
+        frames: 0,
+        outcome: env.game.outcome,
+        invalid: false,
+    };
+    if env.state == game::State::Play {
+        match action {
+            Action::Input(input) => play_frame(env, &Some(input.clone()), &mut info),
+            Action::Wait => play_frame(env, &None, &mut info),
+            Action::Place(target) => match cpu::targets(&env.game).contains(target) {
+                true => place(env, *target, &mut info),
+                false => info.invalid = true,
+            },
+        }
+    }
+
+    info.lines = env.game.lines - lines;
+    info.holes = stats::count_holes(&env.game.board) as i32 - holes;
+    info.outcome = env.game.outcome;
+    let done = env.state != game::State::Play;
+    // Steps after the game is done play nothing, so topping out is only
+    // counted once.
+    let topped_out = done && info.frames > 0 && info.outcome == mode::Outcome::Lost;
+    let rewards = &env.rewards;
+    let mut reward = info.lines as f32 * rewards.line + info.holes as f32 * rewards.hole;
+    if topped_out {
+        reward += rewards.top_out;
+    } else if !done {
+        reward += rewards.survival;
+    }
+    (observe(&env.game), reward, done, info)
+}
+
+fn play_frame(env: &mut Env, event: &Option<game::Input>, info: &mut Info) {
+    env.state = match game::step(event, &mut env.game, &env.state, game::FRAME) {
+        // The bot can't pause.
+        game::State::Paused => game::State::Play,
+        state => state,
+    };
+    info.frames += 1;
+}
+
+// Steers the block to the target and drops it, until it has locked.
+fn place(env: &mut Env, target: cpu::Target, info: &mut Info) {
+    let pieces = stats::pieces(&env.game.stats);
+    let mut dropping = false;
+    while env.state == game::State::Play
+        && stats::pieces(&env.game.stats) == pieces
+        && info.frames < MAX_PLACE_FRAMES
+    {
+        let input = cpu::steer(&env.game, target, &mut dropping);
+        play_frame(env, &input, info);
+    }
+    // Let go of soft drop without playing a frame, so the next block
+    // isn't rushed.
+    if dropping && env.state == game::State::Play {
+        let release = Some(game::Input::DownKeyUp);
+        game::step(
+            &release,
+            &mut env.game,
+            &env.state,
+            time::Duration::from_millis(0),
+        );
+    }
+}
+
+pub fn observe(game: &game::Game) -> Observation {
+    Observation {
+        board: game.board.clone(),
+        block: game.block.clone(),
+        orientation: game.block_orientation,
+        next: game.next.clone(),
+        placements: cpu::targets(game),
+        score: game.score,
+        lines: game.lines,
+        level: game.level,
+        time_played: game.time_played,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Plays a game from the seed, alternating placements with frames of
+    // input, and returns what was seen after each step.
+    fn play(seed: u64) -> Vec<(Observation, f32, bool)> {
+        let classic = mode::initialise_modes()
+            .into_iter()
+            .find(|m| m.label() == "Classic")
+            .unwrap();
+        let rewards = Rewards {
+            hole: -0.5,
+            survival: 0.1,
+            top_out: -10.,
+            ..Rewards::default()
+        };
+        let mut env = initialise(classic, rewards);
+        let mut observation = reset(&mut env, seed);
+        let mut seen = vec![];
+        for i in 0..60 {
+            let action = match i % 3 {
+                0 => Action::Place(observation.placements[i % observation.placements.len()]),
+                1 => Action::Input(game::Input::UpKeyDown),
+                _ => Action::Wait,
+            };
+            let (next, reward, done, _) = step(&mut env, &action);
+            observation = observe(&env.game);
+            seen.push((next, reward, done));
+            if done {
+                break;
+            }
+        }
+        seen
+    }
+
+    #[test]
+    fn same_seed_plays_the_same() {
+        let first = play(3);
+        let second = play(3);
+        assert!(first.len() > 10);
+        assert_eq!(first.len(), second.len());
+        for ((a, a_reward, a_done), (b, b_reward, b_done)) in first.iter().zip(&second) {
+            assert!(a.board == b.board);
+            assert!(a.block.positions == b.block.positions);
+            assert_eq!(a.orientation, b.orientation);
+            assert!(a.placements == b.placements);
+            assert_eq!(a.score, b.score);
+            assert_eq!(a.lines, b.lines);
+            assert_eq!(a.time_played, b.time_played);
+            assert_eq!(a_reward, b_reward);
+            assert_eq!(a_done, b_done);
+        }
+        let boards = |seen: &[(Observation, f32, bool)]| {
+            seen.iter()
+                .map(|(o, _, _)| o.board.clone())
+                .collect::<Vec<_>>()
+        };
+        assert!(boards(&first) != boards(&play(4)));
+    }
+}
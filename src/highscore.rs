@@ -0,0 +1,408 @@
+/*
+
+High scores are kept per mode, the top TABLE_SIZE results for each.
+
+They are stored in the config directory, one entry per line:
+
+  mode|name|score|lines|level|duration_ms|timestamp
+
+The timestamp is seconds since the unix epoch.
+
+*/
+
+use std::collections::HashMap;
+use std::time;
+
+use crate::config;
+use crate::game;
+use crate::mode;
+use crate::mode::Ranking;
+use crate::replay;
+use crate::summary;
+
+const HIGH_SCORE_FILE: &str = "highscores.txt";
+const SEPARATOR: char = '|';
+
+pub const TABLE_SIZE: usize = 10;
+pub const MAX_NAME_LENGTH: usize = 10;
+
+#[derive(Clone)]
+pub struct Entry {
+    pub name: String,
+    pub score: i32,
+    pub lines: i32,
+    pub level: i32,
+    pub duration_millis: u128,
+    pub timestamp: u64,
+}
+
+// Tables are keyed by the mode label.
+pub struct HighScores {
+    pub tables: HashMap<String, Vec<Entry>>,
+}
+
+// State of the name entry and high score screens.
+pub struct View {
+    pub mode_selected: usize,
+    // Index of the entry just added, drawn highlighted.
+    pub highlight: Option<usize>,
+    pub name: String,
+}
+
+pub fn initialise_view(mode_selected: usize) -> View {
+    View {
+        mode_selected: mode_selected,
+        highlight: None,
+        name: "".to_string(),
+    }
+}
+
+pub fn load(modes: &[mode::Mode]) -> HighScores {
+    let mut high_scores = HighScores {
+        tables: HashMap::new(),
+    };
+    let contents = match config::read_file(HIGH_SCORE_FILE) {
+        Some(contents) => contents,
+        None => return high_scores,
+    };
+
+    contents
+        .lines()
+        .filter_map(parse_line)
+        .for_each(|(mode, entry)| high_scores.tables.entry(mode).or_default().push(entry));
+
+    high_scores.tables.iter_mut().for_each(|(label, table)| {
+        let ranking = ranking(modes, label);
+        table.sort_by(|a, b| compare_entries(ranking, a, b));
+        table.truncate(TABLE_SIZE);
+    });
+    high_scores
+}
+
+pub fn save(high_scores: &HighScores) {
+    let mut modes: Vec<&String> = high_scores.tables.keys().collect();
+    modes.sort();
+
+    let contents: String = modes
+        .iter()
+        .flat_map(|&mode| {
+            high_scores.tables[mode]
+                .iter()
+                .map(move |e| format_line(mode, e))
+        })
+        .collect();
+    config::write_file(HIGH_SCORE_FILE, &contents);
+}
+
+fn format_line(mode: &str, e: &Entry) -> String {
+    format!(
+        "{mode}{s}{}{s}{}{s}{}{s}{}{s}{}{s}{}\n",
+        e.name,
+        e.score,
+        e.lines,
+        e.level,
+        e.duration_millis,
+        e.timestamp,
+        mode = mode,
+        s = SEPARATOR,
+    )
+}
+
+fn parse_line(line: &str) -> Option<(String, Entry)> {
+    let fields: Vec<&str> = line.trim_end().split(SEPARATOR).collect();
+    if fields.len() != 7 {
+        return None;
+    }
+    let entry = Entry {
+        name: fields[1].to_string(),
+        score: fields[2].parse().ok()?,
+        lines: fields[3].parse().ok()?,
+        level: fields[4].parse().ok()?,
+        duration_millis: fields[5].parse().ok()?,
+        timestamp: fields[6].parse().ok()?,
+    };
+    Some((fields[0].to_string(), entry))
+}
+
+// Tables of modes that no longer exist are kept, ordered by score.
+fn ranking(modes: &[mode::Mode], label: &str) -> Ranking {
+    modes
+        .iter()
+        .find(|m| m.label() == label)
+        .map_or(Ranking::Score, |m| m.ranking())
+}
+
+// Best first. Ties go to whoever got there first.
+pub fn compare_entries(ranking: Ranking, a: &Entry, b: &Entry) -> std::cmp::Ordering {
+    let order = match ranking {
+        Ranking::Score | Ranking::Unranked => b.score.cmp(&a.score).then(b.lines.cmp(&a.lines)),
+        Ranking::Time => a.duration_millis.cmp(&b.duration_millis),
+    };
+    order.then(a.timestamp.cmp(&b.timestamp))
+}
+
+pub fn table<'a>(high_scores: &'a HighScores, mode: &str) -> &'a [Entry] {
+    match high_scores.tables.get(mode) {
+        Some(table) => table,
+        None => &[],
+    }
+}
+
+// The player's best entry in the mode's table. Tables are best first.
+pub fn personal_best(high_scores: &HighScores, mode: &str, name: &str) -> Option<Entry> {
+    if name.is_empty() {
+        return None;
+    }
+    table(high_scores, mode)
+        .iter()
+        .find(|e| e.name == name)
+        .cloned()
+}
+
+pub fn qualifies(high_scores: &HighScores, mode: &mode::Mode, game: &game::Game) -> bool {
+    let ranking = mode.ranking();
+    let eligible = match ranking {
+        Ranking::Score => game.score > 0,
+        Ranking::Time => game.outcome == mode::Outcome::Won,
+        Ranking::Unranked => false,
+    };
+    if !eligible {
+        return false;
+    }
+    let entry = entry_from_game(game, "");
+    let table = table(high_scores, mode.label());
+    table.len() < TABLE_SIZE
+        || table
+            .iter()
+            .any(|e| compare_entries(ranking, &entry, e) == std::cmp::Ordering::Less)
+}
+
+// Adds the entry to the mode's table and returns its rank.
+pub fn insert(high_scores: &mut HighScores, mode: &mode::Mode, entry: Entry) -> Option<usize> {
+    let ranking = mode.ranking();
+    let table = high_scores
+        .tables
+        .entry(mode.label().to_string())
+        .or_default();
+    let rank = table
+        .iter()
+        .position(|e| compare_entries(ranking, &entry, e) == std::cmp::Ordering::Less)
+        .unwrap_or(table.len());
+    table.insert(rank, entry);
+    table.truncate(TABLE_SIZE);
+    if rank < TABLE_SIZE {
+        Some(rank)
+    } else {
+        None
+    }
+}
+
+pub fn entry_from_game(game: &game::Game, name: &str) -> Entry {
+    Entry {
+        name: name.to_string(),
+        score: game.score,
+        lines: game.lines,
+        level: game.level,
+        duration_millis: game.time_played.as_millis(),
+        timestamp: now_timestamp(),
+    }
+}
+
+pub fn now_timestamp() -> u64 {
+    time::SystemTime::now()
+        .duration_since(time::UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+// Decides where to go once a game has ended. Players with a top
+// score enter their name before seeing the summary.
+pub fn game_finished(world: &mut game::World) -> game::State {
+    let mode = game::current_mode(&world.game);
+    replay::save_if_best(&world.game);
+    let best = personal_best(&world.high_scores, mode.label(), &world.player_name);
+    world.summary = summary::initialise(best);
+    world.high_score_view = initialise_view(world.game.mode_selected);
+    world.high_score_view.name = world.player_name.to_string();
+    if qualifies(&world.high_scores, &mode, &world.game) {
+        return game::State::NameEntry;
+    }
+    game::State::Summary
+}
+
+pub fn update_name_entry(event: &Option<game::Input>, world: &mut game::World) {
+    let view = &mut world.high_score_view;
+    if let Some(event) = event {
+        match event {
+            game::Input::Character(c)
+                if is_valid_name_character(*c) && view.name.chars().count() < MAX_NAME_LENGTH =>
+            {
+                view.name.push(*c);
+            }
+            game::Input::BackspaceKeyDown => {
+                view.name.pop();
+            }
+            game::Input::ReturnKeyDown => {
+                let name = match view.name.trim() {
+                    "" => "Anonymous",
+                    name => name,
+                };
+                let mode = game::current_mode(&world.game);
+                let entry = entry_from_game(&world.game, name);
+                world.summary.personal_best = personal_best(&world.high_scores, mode.label(), name);
+                view.highlight = insert(&mut world.high_scores, &mode, entry);
+                summary::set_rank(&mut world.summary, view.highlight);
+                save(&world.high_scores);
+                world.player_name = name.to_string();
+                world.state = game::State::Summary;
+            }
+            game::Input::EscKeyDown => world.state = game::State::Summary,
+            _ => {}
+        }
+    }
+}
+
+pub fn update(event: &Option<game::Input>, world: &mut game::World) {
+    let view = &mut world.high_score_view;
+    let n_modes = world.game.modes.len();
+    if let Some(event) = event {
+        match event {
+            game::Input::LeftKeyDown => {
+                view.mode_selected = (view.mode_selected + n_modes - 1) % n_modes;
+                view.highlight = None;
+            }
+            game::Input::RightKeyDown => {
+                view.mode_selected = (view.mode_selected + 1) % n_modes;
+                view.highlight = None;
+            }
+            game::Input::ReturnKeyDown | game::Input::SpaceKeyDown | game::Input::EscKeyDown => {
+                world.state = game::State::Menu;
+            }
+            _ => {}
+        }
+    }
+}
+
+fn is_valid_name_character(c: char) -> bool {
+    c.is_alphanumeric() || c == ' ' || c == '-' || c == '_' || c == '.'
+}
+
+pub fn format_duration(millis: u128) -> String {
+    let seconds = millis / 1000;
+    format!("{}:{:02}", seconds / 60, seconds % 60)
+}
+
+// As format_duration with milliseconds, for timed modes.
+pub fn format_precise_duration(millis: u128) -> String {
+    format!("{}.{:03}", format_duration(millis), millis % 1000)
+}
+
+// Formats a unix timestamp as YYYY-MM-DD (UTC).
+pub fn format_date(timestamp: u64) -> String {
+    // Converts days since the epoch to a civil date.
+    // See http://howardhinnant.github.io/date_algorithms.html#civil_from_days
+    let z = (timestamp / 86_400) as i64 + 719_468;
+    let era = z.div_euclid(146_097);
+    let day_of_era = z.rem_euclid(146_097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+    format!("{:04}-{:02}-{:02}", year, month, day)
+}
+
+pub fn format_entry(rank: usize, entry: &Entry, ranking: Ranking) -> String {
+    let duration = match ranking {
+        Ranking::Score | Ranking::Unranked => format_duration(entry.duration_millis),
+        Ranking::Time => format_precise_duration(entry.duration_millis),
+    };
+    format!(
+        "{:>2}. {:<10} {:>6} {:>5} {:>3} {:>9} {}",
+        rank + 1,
+        entry.name,
+        entry.score,
+        entry.lines,
+        entry.level,
+        duration,
+        format_date(entry.timestamp)
+    )
+}
+
+pub fn table_header() -> String {
+    format!(
+        "{:>2}  {:<10} {:>6} {:>5} {:>3} {:>9} {:<10}",
+        "#", "Name", "Score", "Lines", "Lvl", "Time", "Date"
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(name: &str, score: i32, timestamp: u64) -> Entry {
+        Entry {
+            name: name.to_string(),
+            score: score,
+            lines: 12,
+            level: 2,
+            duration_millis: 61_500,
+            timestamp: timestamp,
+        }
+    }
+
+    #[test]
+    fn line_round_trip() {
+        let (mode, parsed) = parse_line(&format_line("Classic", &entry("Ann", 900, 7))).unwrap();
+        assert_eq!(mode, "Classic");
+        assert_eq!(parsed.name, "Ann");
+        assert_eq!(parsed.score, 900);
+        assert_eq!(parsed.lines, 12);
+        assert_eq!(parsed.level, 2);
+        assert_eq!(parsed.duration_millis, 61_500);
+        assert_eq!(parsed.timestamp, 7);
+    }
+
+    #[test]
+    fn broken_lines_are_skipped() {
+        assert!(parse_line("Classic|Ann|900|12|2|61500").is_none());
+        assert!(parse_line("Classic|Ann|lots|12|2|61500|7").is_none());
+        assert!(parse_line("Classic|A|nn|900|12|2|61500|7").is_none());
+    }
+
+    #[test]
+    fn ties_go_to_the_earlier_entry() {
+        let earlier = entry("Ann", 900, 1);
+        let later = entry("Bob", 900, 2);
+        let order = compare_entries(Ranking::Score, &earlier, &later);
+        assert_eq!(order, std::cmp::Ordering::Less);
+        let better = entry("Cat", 1000, 3);
+        let order = compare_entries(Ranking::Score, &better, &earlier);
+        assert_eq!(order, std::cmp::Ordering::Less);
+    }
+
+    #[test]
+    fn personal_best_is_the_players_own() {
+        let mut high_scores = HighScores {
+            tables: HashMap::new(),
+        };
+        let table = vec![
+            entry("Ann", 900, 1),
+            entry("Bob", 500, 2),
+            entry("Bob", 400, 3),
+        ];
+        high_scores.tables.insert("Classic".to_string(), table);
+        let best = personal_best(&high_scores, "Classic", "Bob").unwrap();
+        assert_eq!(best.score, 500);
+        assert!(personal_best(&high_scores, "Classic", "Cat").is_none());
+        assert!(personal_best(&high_scores, "Classic", "").is_none());
+    }
+
+    #[test]
+    fn format_date_from_timestamp() {
+        assert_eq!(format_date(0), "1970-01-01");
+        assert_eq!(format_date(951_782_400), "2000-02-29");
+    }
+}
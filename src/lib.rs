@@ -0,0 +1,44 @@
+/*
+
+The game without its window and sound, so it can be played by other
+programs, e.g. training a bot with gym.rs, and built without SDL:
+
+    cargo build --release --lib --no-default-features
+
+It's also built as a C library, see ffi.rs.
+
+The game itself is main.rs, drawn by game_sdl_layer.rs. The blocks are
+loaded from the assets directory, so like the game it's run from the
+directory holding it, unless another is given with
+config::set_assets_dir.
+
+*/
+
+// Fields are initialised as `field: field` throughout.
+#![allow(clippy::redundant_field_names)]
+
+pub mod attack;
+pub mod block;
+pub mod bot;
+pub mod config;
+pub mod coop;
+pub mod cpu;
+pub mod editor;
+pub mod ffi;
+pub mod game;
+pub mod garbage;
+pub mod gym;
+pub mod highscore;
+pub mod menu;
+pub mod mode;
+pub mod network;
+pub mod puzzle;
+pub mod random;
+pub mod replay;
+pub mod save;
+pub mod sound;
+pub mod spectator;
+pub mod stats;
+pub mod summary;
+pub mod versus;
+pub mod victory;
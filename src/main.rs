@@ -2,6 +2,9 @@
 // wasm
 // Menu works with mouse
 
+// Fields are initialised as `field: field` throughout, as in lib.rs.
+#![allow(clippy::redundant_field_names)]
+
 use std::collections::HashMap;
 use std::path::Path;
 use std::time::Instant;
@@ -13,19 +16,27 @@ use sdl2::mixer;
 use sdl2::mixer::Music;
 use sdl2::pixels::Color;
 
-mod block;
-mod game;
 mod game_sdl_layer;
-mod menu;
-mod sound;
 mod sound_sdl;
 
+use tetris_bane::{
+    attack, block, bot, config, coop, editor, game, highscore, menu, mode, puzzle, replay,
+    spectator, stats, summary, versus, victory,
+};
+
 use menu::GAME_TITLE;
 
 const SYSTEM_FONT_PATH: &str = "assets/fonts/Bitstream-Vera-Sans-Mono/VeraMono.ttf";
 
 const OVERLAY_FONT_SIZE: u16 = 12;
 
+// Keys of each player in versus and co-op: left, right, rotate and soft
+// drop.
+const VERSUS_KEYS: [[Keycode; 4]; versus::PLAYERS] = [
+    [Keycode::A, Keycode::D, Keycode::W, Keycode::S],
+    [Keycode::Left, Keycode::Right, Keycode::Up, Keycode::Down],
+];
+
 fn fps_color(fps: u32) -> Color {
     match fps {
         0..=23 => Color::RGB(255, 0, 0),
@@ -34,7 +45,62 @@ fn fps_color(fps: u32) -> Color {
     }
 }
 
+// Whether each player's keys are played with, rather than either key.
+fn wants_player_keys(state: &game::State) -> bool {
+    [game::State::Versus, game::State::Coop].contains(state)
+}
+
+fn is_versus_key(keycode: Keycode) -> bool {
+    VERSUS_KEYS.iter().flatten().any(|&key| key == keycode)
+}
+
+// The player a versus key belongs to and the input it makes.
+fn versus_input(keycode: Keycode, key_down: bool) -> Option<(usize, game::Input)> {
+    let player = VERSUS_KEYS
+        .iter()
+        .position(|keys| keys.contains(&keycode))?;
+    let key = VERSUS_KEYS[player].iter().position(|&k| k == keycode)?;
+    let input = match (key, key_down) {
+        (0, true) => game::Input::LeftKeyDown,
+        (1, true) => game::Input::RightKeyDown,
+        (2, true) => game::Input::UpKeyDown,
+        (3, true) => game::Input::DownKeyDown,
+        (3, false) => game::Input::DownKeyUp,
+        _ => return None,
+    };
+    Some((player, input))
+}
+
+fn has_flag(flag: &str) -> bool {
+    std::env::args().any(|arg| arg == flag)
+}
+
+// The value given after a command line flag, or the default if it has
+// none. None if the flag wasn't given.
+fn flag_value(flag: &str, default: String) -> Option<String> {
+    let args: Vec<String> = std::env::args().collect();
+    let i = args.iter().position(|arg| arg == flag)?;
+    match args.get(i + 1) {
+        Some(value) if !value.starts_with("--") => Some(value.to_string()),
+        _ => Some(default),
+    }
+}
+
 pub fn main() {
+    // Failing to listen isn't worth stopping the game for. See
+    // spectator.rs and bot.rs for the flags.
+    let mut spectator =
+        flag_value("--spectate", spectator::DEFAULT_PORT.to_string()).and_then(|address| {
+            spectator::listen(&address)
+                .map_err(|error| eprintln!("{}", error))
+                .ok()
+        });
+    let mut bot = flag_value("--bot", bot::DEFAULT_PORT.to_string()).and_then(|address| {
+        bot::listen(&address, has_flag("--lock-step"))
+            .map_err(|error| eprintln!("{}", error))
+            .ok()
+    });
+
     let sdl_context = sdl2::init().unwrap();
     let ttf_context = sdl2::ttf::init().unwrap();
 
@@ -79,6 +145,9 @@ pub fn main() {
     let mut show_fps = false;
 
     let mut input_event: Option<game::Input> = None;
+    // Inputs of each player in versus and co-op, which can press keys at
+    // once.
+    let mut player_inputs: Vec<(usize, game::Input)> = vec![];
     let mut world = game::initialise_world();
 
     let mut music: Option<Music> = None;
@@ -88,6 +157,22 @@ pub fn main() {
     while world.state != game::State::Quit {
         let start = timer_subsystem.performance_counter();
 
+        // Typed text is only wanted when entering a name or an address,
+        // or editing a board. Otherwise the text events would replace
+        // the key events used to play.
+        let text_input = video_subsystem.text_input();
+        let wants_text = [
+            game::State::NameEntry,
+            game::State::Editor,
+            game::State::VersusSetup,
+        ]
+        .contains(&world.state);
+        match (wants_text, text_input.is_active()) {
+            (true, false) => text_input.start(),
+            (false, true) => text_input.stop(),
+            _ => {}
+        }
+
         for event in event_pump.poll_iter() {
             match event {
                 Event::Quit { .. } => world.state = game::State::Quit,
@@ -95,10 +180,30 @@ pub fn main() {
                     win_event: WindowEvent::Resized(_width, _height),
                     ..
                 } => {}
+                Event::KeyDown {
+                    keycode: Some(keycode),
+                    ..
+                } if wants_player_keys(&world.state) && is_versus_key(keycode) => {
+                    player_inputs.extend(versus_input(keycode, true));
+                }
+                Event::KeyUp {
+                    keycode: Some(keycode),
+                    ..
+                } if wants_player_keys(&world.state) && is_versus_key(keycode) => {
+                    player_inputs.extend(versus_input(keycode, false));
+                }
                 Event::KeyDown { keycode, .. } => match keycode {
                     Some(Keycode::F1) => {
                         show_fps = !show_fps;
                     }
+                    Some(Keycode::F2) => {
+                        world.config.show_stats = !world.config.show_stats;
+                        config::save(&world.config);
+                    }
+                    Some(Keycode::F3) => {
+                        world.config.show_ghost = !world.config.show_ghost;
+                        config::save(&world.config);
+                    }
 
                     Some(Keycode::Escape) => input_event = Some(game::Input::EscKeyDown),
 
@@ -126,8 +231,16 @@ pub fn main() {
                     Some(Keycode::Space) => {
                         input_event = Some(game::Input::SpaceKeyDown);
                     }
+                    Some(Keycode::Backspace) => {
+                        input_event = Some(game::Input::BackspaceKeyDown);
+                    }
                     _ => {}
                 },
+                Event::TextInput { text, .. } => {
+                    if let Some(c) = text.chars().next() {
+                        input_event = Some(game::Input::Character(c));
+                    }
+                }
                 Event::KeyUp { keycode, .. } => match keycode {
                     Some(Keycode::Down) => {
                         input_event = Some(game::Input::DownKeyUp);
@@ -145,24 +258,49 @@ pub fn main() {
             }
         }
 
-        game_sdl_layer::update_and_render(&mut render, &game_fonts, &input_event, &mut world);
+        if let Some(bot) = &mut bot {
+            input_event = bot::update(bot, &input_event, &mut world);
+        }
+
+        game_sdl_layer::update_and_render(
+            &mut render,
+            &game_fonts,
+            &input_event,
+            &player_inputs,
+            &mut world,
+        );
+
+        if let Some(bot) = &mut bot {
+            bot::observe(bot, &world);
+        }
+        if let Some(spectator) = &mut spectator {
+            spectator::publish(spectator, &world);
+        }
 
         input_event = None;
+        player_inputs.clear();
 
         if !world.music_file.is_empty() {
             if world.music_file != music_playing {
-                music = None;
                 music_playing = world.music_file.to_string();
+                // Music that can't be played is left out, the game goes
+                // on without it.
+                music = match Music::from_file(&world.music_file) {
+                    Ok(music) => Some(music),
+                    Err(error) => {
+                        eprintln!("Unable to play {}: {}", world.music_file, error);
+                        None
+                    }
+                };
             }
 
-            match &music {
-                Some(music) => sound_sdl::handle_music(
+            if let Some(music) = &music {
+                sound_sdl::handle_music(
                     music,
                     &world.state,
                     world.menu.music_toggle,
                     world.menu.music_volume,
-                ),
-                None => music = Some(Music::from_file(&world.music_file).unwrap()),
+                );
             }
         }
 
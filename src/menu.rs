@@ -1,4 +1,12 @@
+use crate::config;
+use crate::coop;
+use crate::editor;
 use crate::game;
+use crate::highscore;
+use crate::mode;
+use crate::puzzle;
+use crate::replay;
+use crate::save;
 use crate::sound;
 
 pub const GAME_TITLE: &str = "Tetris Bane";
@@ -13,6 +21,13 @@ pub enum Item {
     EndGame { label: String },
     Music { label: String },
     MusicVolume { label: String },
+    HighScores { label: String },
+    Puzzles { label: String },
+    Editor { label: String },
+    Versus { label: String },
+    Coop { label: String },
+    Continue { label: String },
+    SaveAndQuit { label: String },
 }
 
 pub struct Menu {
@@ -23,9 +38,9 @@ pub struct Menu {
     pub music_volume: i32,
 }
 
-pub fn initialise(game: &game::Game) -> Menu {
-    let music_toggle = true;
-    let music_volume = 70;
+pub fn initialise(game: &game::Game, config: &config::Config) -> Menu {
+    let music_toggle = config.music_toggle;
+    let music_volume = config.music_volume;
 
     Menu {
         items: menu_items(&game, music_toggle, music_volume),
@@ -37,31 +52,60 @@ pub fn initialise(game: &game::Game) -> Menu {
 }
 
 pub fn menu_items(game: &game::Game, music_toggle: bool, music_volume: i32) -> Vec<Item> {
-    vec![
+    let mut items = vec![];
+    if save::exists() {
+        items.push(Item::Continue {
+            label: "Continue              ".to_string(),
+        });
+    }
+    items.extend(vec![
         Item::Play {
             label: "Play                  ".to_string(),
         },
         build_mode_item(&game.modes, game.mode_selected),
+        Item::Puzzles {
+            label: "Puzzles               ".to_string(),
+        },
+        Item::Editor {
+            label: "Editor                ".to_string(),
+        },
+        Item::Versus {
+            label: "Versus                ".to_string(),
+        },
+        Item::Coop {
+            label: "Co-op                 ".to_string(),
+        },
         Item::Music {
             label: music_label(music_toggle),
         },
         Item::MusicVolume {
             label: volume_label(music_volume),
         },
+        Item::HighScores {
+            label: "High Scores           ".to_string(),
+        },
         Item::Quit {
             label: "Quit                  ".to_string(),
         },
-    ]
+    ]);
+    items
 }
 
-pub fn paused_menu_items(music_toggle: bool, music_volume: i32) -> Vec<Item> {
-    vec![
+pub fn paused_menu_items(game: &game::Game, music_toggle: bool, music_volume: i32) -> Vec<Item> {
+    let mut items = vec![
         Item::Resume {
             label: "Resume                ".to_string(),
         },
         Item::EndGame {
             label: "End Game              ".to_string(),
         },
+    ];
+    if game.mode.can_save() {
+        items.push(Item::SaveAndQuit {
+            label: "Save & Quit           ".to_string(),
+        });
+    }
+    items.extend(vec![
         Item::Music {
             label: music_label(music_toggle),
         },
@@ -71,7 +115,8 @@ pub fn paused_menu_items(music_toggle: bool, music_volume: i32) -> Vec<Item> {
         Item::Quit {
             label: "Quit                  ".to_string(),
         },
-    ]
+    ]);
+    items
 }
 
 pub fn update(event: &Option<game::Input>, mut world: &mut game::World) {
@@ -95,9 +140,8 @@ pub fn update(event: &Option<game::Input>, mut world: &mut game::World) {
             game::Input::ReturnKeyDown | game::Input::SpaceKeyDown => {
                 match menu.items[menu.item_selected] {
                     Item::Play { .. } => {
-                        world.state = game::State::Play;
-                        world.music_file = sound::music_path(&game.modes[game.mode_selected]);
-                        *game = game::initialise_game(game.mode_selected);
+                        start_game(world);
+                        return;
                     }
                     Item::EndGame { .. } => {
                         world.state = game::State::Menu;
@@ -108,8 +152,39 @@ pub fn update(event: &Option<game::Input>, mut world: &mut game::World) {
                     Item::Resume { .. } => {
                         world.state = game::State::Play;
                         menu.title = GAME_TITLE.to_string();
+                        // Time spent paused doesn't count as time played.
+                        game.frame_clock = game::now(game);
+                    }
+                    Item::HighScores { .. } => {
+                        world.state = game::State::HighScores;
+                        world.high_score_view = highscore::initialise_view(game.mode_selected);
+                    }
+                    Item::Puzzles { .. } => {
+                        world.state = game::State::PuzzleSelect;
+                        // Reloaded so new packs show up without a restart.
+                        world.puzzle_select = puzzle::initialise_select();
+                    }
+                    Item::Editor { .. } => {
+                        world.state = game::State::Editor;
+                        world.editor = editor::initialise(&game.modes, game.mode_selected);
+                    }
+                    Item::Versus { .. } => {
+                        world.state = game::State::VersusSetup;
+                        world.versus.setup_selected = 0;
+                    }
+                    Item::Coop { .. } => {
+                        coop::start(world);
+                        return;
                     }
                     Item::Quit { .. } => world.state = game::State::Quit,
+                    Item::SaveAndQuit { .. } => {
+                        save::save(game);
+                        world.state = game::State::Quit;
+                    }
+                    Item::Continue { .. } => {
+                        continue_game(world);
+                        return;
+                    }
                     Item::Mode { .. } => {
                         game.mode_selected = shift_left_or_right(&mut menu, &game, -1)
                     }
@@ -132,6 +207,64 @@ pub fn update(event: &Option<game::Input>, mut world: &mut game::World) {
             _ => {}
         }
     }
+
+    let mut config = world.config.clone();
+    config.music_toggle = menu.music_toggle;
+    config.music_volume = menu.music_volume;
+    config.mode = game.modes[game.mode_selected].label().to_string();
+    if config != world.config {
+        config::save(&config);
+        world.config = config;
+    }
+}
+
+// Starts a new game in the selected mode.
+pub fn start_game(world: &mut game::World) {
+    let mode = world.game.modes[world.game.mode_selected].clone();
+    start_game_in_mode(world, mode);
+}
+
+// Starts a new game in the same mode as the last one.
+pub fn retry_game(world: &mut game::World) {
+    let mode = game::current_mode(&world.game);
+    start_game_in_mode(world, mode);
+}
+
+pub fn start_game_in_mode(world: &mut game::World, mode: mode::Mode) {
+    world.music_file = sound::music_path(&mode);
+    let modes = mode::initialise_modes();
+    world.ghost = replay::load_ghost(&mode);
+    world.game = game::initialise_game_in_mode(modes, world.game.mode_selected, mode);
+    world.state = game::start_state(&world.game);
+}
+
+// Resumes the saved game, paused so the player has a moment to get
+// ready. A save that can't be read is thrown away.
+fn continue_game(world: &mut game::World) {
+    let saved_game = save::load();
+    save::delete();
+    match saved_game {
+        Some(saved_game) => {
+            world.game = saved_game;
+            world.ghost = replay::load_ghost(&world.game.mode);
+            world.music_file = sound::music_path(&world.game.mode);
+            world.state = game::State::Paused;
+            world.menu.items = paused_menu_items(
+                &world.game,
+                world.menu.music_toggle,
+                world.menu.music_volume,
+            );
+            world.menu.title = "Paused".to_string();
+        }
+        None => {
+            world.menu.items = menu_items(
+                &world.game,
+                world.menu.music_toggle,
+                world.menu.music_volume,
+            );
+        }
+    }
+    world.menu.item_selected = 0;
 }
 
 fn shift_left_or_right(mut menu: &mut Menu, game: &game::Game, delta: i32) -> usize {
@@ -151,7 +284,7 @@ fn shift_left_or_right(mut menu: &mut Menu, game: &game::Game, delta: i32) -> us
             let volume = menu.music_volume + delta * 10;
             menu.music_volume = match volume {
                 volume if volume < 0 => 0,
-                volume if volume > 128 => 128,
+                volume if volume > config::MAX_MUSIC_VOLUME => config::MAX_MUSIC_VOLUME,
                 _ => volume,
             };
             menu.items[menu.item_selected] = Item::MusicVolume {
@@ -173,25 +306,21 @@ fn change_index_wrapped(index: usize, delta: i32, length: usize) -> usize {
     }
 }
 
-fn build_mode_item(modes: &Vec<game::Mode>, selected: usize) -> Item {
+fn build_mode_item(modes: &Vec<mode::Mode>, selected: usize) -> Item {
     Item::Mode {
         label: mode_label(&modes[selected]),
     }
 }
 
-fn mode_label(mode: &game::Mode) -> String {
+fn mode_label(mode: &mode::Mode) -> String {
     let prefix = "Mode:";
-    let mode = format!(
-        "{}",
-        match mode {
-            game::Mode::Classic { label }
-            | game::Mode::Chill { label }
-            | game::Mode::Bane { label }
-            | game::Mode::Metal { label } => label,
-        }
-    );
+    let mode = mode.label();
 
-    let padding = " ".repeat(LABEL_LENGTH - prefix.len() - mode.len());
+    let padding = " ".repeat(
+        LABEL_LENGTH
+            .saturating_sub(prefix.len() + mode.len())
+            .max(1),
+    );
     format!("{}{}{}", prefix, padding, mode)
 }
 
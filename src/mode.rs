@@ -0,0 +1,192 @@
+/*
+
+A mode is a set of rules implementing GameMode. The hooks decide the
+board size, which block comes next, what happens when a block locks or
+lines are cleared, how fast blocks fall, when the game is won or lost
+and the music that plays. Only the label, board size, pieces and music
+have to be given, everything else defaults to the Classic rules.
+
+Each built in mode lives in its own module under src/mode. Players can
+add their own with a mode file, see custom.rs.
+
+*/
+
+use std::rc::Rc;
+use std::time;
+
+use crate::block;
+use crate::block::{Block, Position};
+use crate::game;
+use crate::stats;
+
+mod bane;
+mod chill;
+mod classic;
+pub mod custom;
+mod dig;
+mod marathon;
+mod metal;
+pub mod puzzle;
+mod sprint;
+mod survival;
+mod ultra;
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Randomizer {
+    // Every block is equally likely each time.
+    Random,
+    // Each block of the set is dealt once, in a random order, before the
+    // set is shuffled again.
+    Bag,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Outcome {
+    Playing,
+    Won,
+    Lost,
+}
+
+// How the mode's high score table is ordered.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Ranking {
+    // Highest score first.
+    Score,
+    // Quickest win first. Games that weren't won don't make the table.
+    Time,
+    // No table, e.g. puzzles which are either solved or not.
+    Unranked,
+}
+
+pub trait GameMode {
+    fn label(&self) -> &str;
+
+    // Includes the hidden row at the top of the board.
+    fn board_size(&self) -> Position;
+
+    // Name of the piece file, without the .txt.
+    fn pieces(&self) -> &str;
+
+    fn music(&self) -> &str;
+
+    fn randomizer(&self) -> Randomizer {
+        Randomizer::Random
+    }
+
+    // Called once the board is made, before the first block spawns.
+    fn on_start(&self, _game: &mut game::Game) {}
+
+    fn next_block(&self, game: &mut game::Game) -> Block {
+        block::spawn(
+            &game.blocks,
+            self.randomizer(),
+            &mut game.bag,
+            &mut game.rng,
+        )
+    }
+
+    // Called every frame while playing, before the block falls. Returns
+    // Lost if something the mode did topped the player out.
+    fn on_frame(&self, _game: &mut game::Game) -> Outcome {
+        Outcome::Playing
+    }
+
+    // Called once the block has been painted on the board, before full
+    // lines are removed.
+    fn on_lock(&self, _game: &mut game::Game) {}
+
+    // Called after full lines are removed, even when there were none.
+    fn on_line_clear(&self, game: &mut game::Game, lines: i32) {
+        game.score += lines;
+        game.lines += lines;
+        game.level = game.lines / game::LINES_PER_LEVEL + 1;
+        stats::record_clear(&mut game.stats, lines);
+    }
+
+    // Milliseconds before the block drops a row at the level.
+    fn gravity(&self, _level: i32) -> u128 {
+        game::DEFAULT_FALL_RATE
+    }
+
+    fn soft_drop(&self) -> bool {
+        true
+    }
+
+    fn rotate(&self) -> bool {
+        true
+    }
+
+    // Checked every frame while playing. Being unable to spawn a block
+    // always loses.
+    fn outcome(&self, _game: &game::Game) -> Outcome {
+        Outcome::Playing
+    }
+
+    fn ranking(&self) -> Ranking {
+        Ranking::Score
+    }
+
+    // Time drawn under the board, if the mode has a clock.
+    fn timer(&self, _game: &game::Game) -> Option<time::Duration> {
+        None
+    }
+
+    // How far the player is from the goal, drawn above the board.
+    fn progress(&self, _game: &game::Game) -> Option<String> {
+        None
+    }
+
+    // Whether the game can be saved from the pause menu to continue
+    // later.
+    fn can_save(&self) -> bool {
+        true
+    }
+
+    // Whether the player can play on after winning.
+    fn endless(&self) -> bool {
+        false
+    }
+
+    // Counted down before the first block falls.
+    fn countdown(&self) -> time::Duration {
+        time::Duration::from_secs(0)
+    }
+}
+
+// Modes are shared so a hook can be called with the game borrowed
+// mutably.
+pub type Mode = Rc<dyn GameMode>;
+
+// Co-op is always played by the Metal rules, its board is wide enough
+// for two.
+pub fn coop_mode() -> Mode {
+    Rc::new(metal::Metal)
+}
+
+// The built in modes followed by any the player has defined.
+pub fn initialise_modes() -> Vec<Mode> {
+    let mut modes = built_in_modes();
+    for mode in custom::load_user_modes() {
+        if modes.iter().any(|m| m.label() == mode.label()) {
+            eprintln!("Ignoring mode {}, the name is already used", mode.label());
+            continue;
+        }
+        modes.push(Rc::new(mode));
+    }
+    modes
+}
+
+// The modes that come with the game, the same wherever it's played.
+pub fn built_in_modes() -> Vec<Mode> {
+    vec![
+        Rc::new(bane::Bane),
+        Rc::new(classic::Classic),
+        Rc::new(chill::Chill),
+        Rc::new(metal::Metal),
+        Rc::new(marathon::Marathon),
+        Rc::new(sprint::Sprint),
+        Rc::new(ultra::Ultra),
+        Rc::new(dig::Dig),
+        Rc::new(survival::Survival),
+    ]
+}
@@ -0,0 +1,24 @@
+use crate::block::Position;
+use crate::game;
+use crate::mode::GameMode;
+use crate::sound;
+
+pub struct Bane;
+
+impl GameMode for Bane {
+    fn label(&self) -> &str {
+        "Bane"
+    }
+
+    fn board_size(&self) -> Position {
+        game::BANE_BOARD_SIZE
+    }
+
+    fn pieces(&self) -> &str {
+        "bane"
+    }
+
+    fn music(&self) -> &str {
+        sound::BANE_MUSIC_PATH
+    }
+}
@@ -0,0 +1,24 @@
+use crate::block::Position;
+use crate::game;
+use crate::mode::GameMode;
+use crate::sound;
+
+pub struct Chill;
+
+impl GameMode for Chill {
+    fn label(&self) -> &str {
+        "Chill"
+    }
+
+    fn board_size(&self) -> Position {
+        game::CLASSIC_BOARD_SIZE
+    }
+
+    fn pieces(&self) -> &str {
+        "chill"
+    }
+
+    fn music(&self) -> &str {
+        sound::CHILL_MUSIC_PATH
+    }
+}
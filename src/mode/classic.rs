@@ -0,0 +1,24 @@
+use crate::block::Position;
+use crate::game;
+use crate::mode::GameMode;
+use crate::sound;
+
+pub struct Classic;
+
+impl GameMode for Classic {
+    fn label(&self) -> &str {
+        "Classic"
+    }
+
+    fn board_size(&self) -> Position {
+        game::CLASSIC_BOARD_SIZE
+    }
+
+    fn pieces(&self) -> &str {
+        "classic"
+    }
+
+    fn music(&self) -> &str {
+        sound::CLASSIC_MUSIC_PATH
+    }
+}
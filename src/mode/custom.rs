@@ -0,0 +1,236 @@
+/*
+
+A mode defined by the player in a file in the modes directory of the
+config directory, e.g. ~/.config/tetris-bane/modes/tiny.txt:
+
+  name = Tiny
+  board = 8, 16            # columns, rows
+  pieces = classic         # a file in assets/pieces or the user pieces dir
+  randomizer = bag         # random or bag
+  gravity = 800, 650, 500  # milliseconds per row for level 1, 2, 3...
+  music = assets/music/tetrismetal.ogg
+  soft_drop = true         # holding down makes the block fall faster
+  rotate = true            # the block can be rotated
+
+Only the name is required, and it can't contain '|'. Everything else
+defaults to Classic. The board has to be big enough for the pieces to
+spawn and turn in, and the music file has to exist.
+
+*/
+
+use std::fs;
+use std::path::Path;
+
+use crate::block;
+use crate::block::Position;
+use crate::config;
+use crate::game;
+use crate::mode::{GameMode, Randomizer};
+use crate::sound;
+
+const USER_MODES_DIR: &str = "modes";
+
+pub struct Custom {
+    label: String,
+    board_size: Position,
+    pieces: String,
+    randomizer: Randomizer,
+    // The last entry is used for all levels after it.
+    gravity: Vec<u128>,
+    music: String,
+    soft_drop: bool,
+    rotate: bool,
+}
+
+impl GameMode for Custom {
+    fn label(&self) -> &str {
+        &self.label
+    }
+
+    fn board_size(&self) -> Position {
+        self.board_size
+    }
+
+    fn pieces(&self) -> &str {
+        &self.pieces
+    }
+
+    fn music(&self) -> &str {
+        &self.music
+    }
+
+    fn randomizer(&self) -> Randomizer {
+        self.randomizer
+    }
+
+    fn gravity(&self, level: i32) -> u128 {
+        let index = (level.max(1) as usize - 1).min(self.gravity.len() - 1);
+        self.gravity[index]
+    }
+
+    fn soft_drop(&self) -> bool {
+        self.soft_drop
+    }
+
+    fn rotate(&self) -> bool {
+        self.rotate
+    }
+}
+
+pub fn load_user_modes() -> Vec<Custom> {
+    let dir = match config::file_path(USER_MODES_DIR) {
+        Some(dir) => dir,
+        None => return vec![],
+    };
+    let mut paths: Vec<_> = match fs::read_dir(&dir) {
+        Ok(entries) => entries
+            .filter_map(|entry| entry.ok())
+            .map(|entry| entry.path())
+            .filter(|path| path.extension() == Some("txt".as_ref()))
+            .collect(),
+        Err(_) => return vec![],
+    };
+    paths.sort();
+
+    paths
+        .iter()
+        .filter_map(|path| {
+            let parsed = fs::read_to_string(path)
+                .map_err(|error| error.to_string())
+                .and_then(|contents| parse_mode(&contents));
+            match parsed {
+                Ok(mode) => Some(mode),
+                Err(error) => {
+                    eprintln!("Ignoring {}: {}", path.display(), error);
+                    None
+                }
+            }
+        })
+        .collect()
+}
+
+pub fn parse_mode(contents: &str) -> Result<Custom, String> {
+    let mut mode = Custom {
+        label: String::new(),
+        board_size: game::CLASSIC_BOARD_SIZE,
+        pieces: "classic".to_string(),
+        randomizer: Randomizer::Random,
+        gravity: vec![game::DEFAULT_FALL_RATE],
+        music: sound::CLASSIC_MUSIC_PATH.to_string(),
+        soft_drop: true,
+        rotate: true,
+    };
+
+    for (key, value) in config::parse_pairs(contents) {
+        // Allow comments after values.
+        let value = value.split('#').next().unwrap_or("").trim();
+        let invalid = || format!("invalid value for {}: \"{}\"", key, value);
+        match key.as_str() {
+            // Scores and history are stored split by '|', see highscore.rs.
+            "name" if value.contains('|') => return Err(invalid()),
+            "name" => mode.label = value.to_string(),
+            "board" => {
+                let size: Vec<i32> = parse_list(value).ok_or_else(invalid)?;
+                match size.as_slice() {
+                    &[x, y] if x >= 4 && y >= 4 => {
+                        mode.board_size = Position { x: x, y: y + 1 };
+                    }
+                    _ => return Err(invalid()),
+                }
+            }
+            "pieces" => mode.pieces = value.to_string(),
+            "randomizer" => {
+                mode.randomizer = match value {
+                    "random" => Randomizer::Random,
+                    "bag" => Randomizer::Bag,
+                    _ => return Err(invalid()),
+                }
+            }
+            "gravity" => {
+                mode.gravity = parse_list(value).ok_or_else(invalid)?;
+                if mode.gravity.is_empty() {
+                    return Err(invalid());
+                }
+            }
+            "music" => mode.music = value.to_string(),
+            "soft_drop" => mode.soft_drop = value.parse().map_err(|_| invalid())?,
+            "rotate" => mode.rotate = value.parse().map_err(|_| invalid())?,
+            _ => return Err(format!("unknown key \"{}\"", key)),
+        }
+    }
+
+    if mode.label.is_empty() {
+        return Err("a mode needs a name".to_string());
+    }
+    check_board(&mode)?;
+    if !Path::new(&mode.music).is_file() {
+        return Err(format!("no music file at \"{}\"", mode.music));
+    }
+    Ok(mode)
+}
+
+// The mode's pieces have to load and fit on its board, the same way
+// whichever orientation they're turned to at the top.
+fn check_board(mode: &Custom) -> Result<(), String> {
+    let blocks = block::find_blocks(&mode.pieces)?;
+    let area = block::spawn_area(&blocks);
+    // The board's hidden top row isn't counted in the file.
+    if area.x > mode.board_size.x || area.y > mode.board_size.y {
+        return Err(format!(
+            "the {} pieces need a board of at least {}, {}",
+            mode.pieces,
+            area.x,
+            (area.y - 1).max(4)
+        ));
+    }
+    Ok(())
+}
+
+fn parse_list<T: std::str::FromStr>(value: &str) -> Option<Vec<T>> {
+    value.split(',').map(|v| v.trim().parse().ok()).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_a_mode() {
+        let mode = parse_mode(
+            "name = Tiny\n\
+             board = 8, 16            # columns, rows\n\
+             randomizer = bag\n\
+             gravity = 800, 650\n\
+             soft_drop = false\n",
+        )
+        .unwrap();
+        assert_eq!(mode.label(), "Tiny");
+        assert!(mode.board_size() == Position { x: 8, y: 17 });
+        assert_eq!(mode.pieces(), "classic");
+        assert!(mode.randomizer() == Randomizer::Bag);
+        assert_eq!(mode.gravity(1), 800);
+        assert_eq!(mode.gravity(9), 650);
+        assert!(!mode.soft_drop());
+        assert!(mode.rotate());
+    }
+
+    #[test]
+    fn rejects_bad_modes() {
+        assert!(parse_mode("board = 8, 16\n").is_err());
+        assert!(parse_mode("name = A|B\n").is_err());
+        assert!(parse_mode("name = A\nboard = 3, 16\n").is_err());
+        assert!(parse_mode("name = A\ngravity =\n").is_err());
+        assert!(parse_mode("name = A\nrandomizer = fair\n").is_err());
+        assert!(parse_mode("name = A\nspeed = 3\n").is_err());
+        assert!(parse_mode("name = A\nmusic = assets/music/nope.ogg\n").is_err());
+    }
+
+    #[test]
+    fn rejects_pieces_that_dont_fit() {
+        let error = |mode: &str| parse_mode(mode).err().unwrap();
+        assert!(error("name = A\nboard = 4, 16\n").contains("at least 7"));
+        assert!(error("name = A\nboard = 8, 16\npieces = bane\n").contains("at least"));
+        assert!(parse_mode("name = A\nboard = 7, 16\n").is_ok());
+        assert!(parse_mode("name = A\npieces = nope\n").is_err());
+    }
+}
@@ -0,0 +1,69 @@
+/*
+
+Dig down through DIG_ROWS rows of garbage as quickly as possible. The
+game is won once every garbage row has been cleared and the high score
+table is ordered by time.
+
+*/
+
+use std::time;
+
+use crate::block::Position;
+use crate::game;
+use crate::garbage;
+use crate::mode::{GameMode, Outcome, Randomizer, Ranking};
+use crate::sound;
+
+const DIG_ROWS: usize = 10;
+const COUNTDOWN_SECONDS: u64 = 3;
+
+pub struct Dig;
+
+impl GameMode for Dig {
+    fn label(&self) -> &str {
+        "Dig"
+    }
+
+    fn board_size(&self) -> Position {
+        game::CLASSIC_BOARD_SIZE
+    }
+
+    fn pieces(&self) -> &str {
+        "classic"
+    }
+
+    fn music(&self) -> &str {
+        sound::CLASSIC_MUSIC_PATH
+    }
+
+    fn randomizer(&self) -> Randomizer {
+        Randomizer::Bag
+    }
+
+    fn on_start(&self, game: &mut game::Game) {
+        garbage::fill(&mut game.board, DIG_ROWS, &mut game.rng);
+    }
+
+    fn outcome(&self, game: &game::Game) -> Outcome {
+        if garbage::count_rows(&game.board) == 0 {
+            return Outcome::Won;
+        }
+        Outcome::Playing
+    }
+
+    fn ranking(&self) -> Ranking {
+        Ranking::Time
+    }
+
+    fn progress(&self, game: &game::Game) -> Option<String> {
+        Some(format!("{} lines left", garbage::count_rows(&game.board)))
+    }
+
+    fn timer(&self, game: &game::Game) -> Option<time::Duration> {
+        Some(game.time_played)
+    }
+
+    fn countdown(&self) -> time::Duration {
+        time::Duration::from_secs(COUNTDOWN_SECONDS)
+    }
+}
@@ -0,0 +1,78 @@
+/*
+
+Play through the levels as the blocks fall faster and faster. Clearing
+MARATHON_LINES lines, finishing level MARATHON_LEVELS, wins.
+
+After winning the player can carry on in endless play. The speed keeps
+rising until KILL_SCREEN_LEVEL where blocks drop a row every frame and
+it's only a matter of time.
+
+*/
+
+use std::time;
+
+use crate::block::Position;
+use crate::game;
+use crate::mode::{GameMode, Outcome, Randomizer};
+use crate::sound;
+
+const MARATHON_LEVELS: i32 = 15;
+const MARATHON_LINES: i32 = MARATHON_LEVELS * game::LINES_PER_LEVEL;
+const KILL_SCREEN_LEVEL: i32 = 30;
+
+const FIRST_LEVEL_FALL_RATE: f64 = 800.; // milliseconds
+
+// Each level takes this fraction of the time of the last to drop a row.
+const FALL_RATE_PER_LEVEL: f64 = 0.86;
+const KILL_SCREEN_FALL_RATE: u128 = 0; // milliseconds
+
+pub struct Marathon;
+
+impl GameMode for Marathon {
+    fn label(&self) -> &str {
+        "Marathon"
+    }
+
+    fn board_size(&self) -> Position {
+        game::CLASSIC_BOARD_SIZE
+    }
+
+    fn pieces(&self) -> &str {
+        "classic"
+    }
+
+    fn music(&self) -> &str {
+        sound::CLASSIC_MUSIC_PATH
+    }
+
+    fn randomizer(&self) -> Randomizer {
+        Randomizer::Bag
+    }
+
+    fn gravity(&self, level: i32) -> u128 {
+        if level >= KILL_SCREEN_LEVEL {
+            return KILL_SCREEN_FALL_RATE;
+        }
+        let level = level.max(1) - 1;
+        (FIRST_LEVEL_FALL_RATE * FALL_RATE_PER_LEVEL.powi(level)) as u128
+    }
+
+    fn outcome(&self, game: &game::Game) -> Outcome {
+        if game.lines >= MARATHON_LINES {
+            return Outcome::Won;
+        }
+        Outcome::Playing
+    }
+
+    fn progress(&self, game: &game::Game) -> Option<String> {
+        Some(format!("Level {}", game.level))
+    }
+
+    fn timer(&self, game: &game::Game) -> Option<time::Duration> {
+        Some(game.time_played)
+    }
+
+    fn endless(&self) -> bool {
+        true
+    }
+}
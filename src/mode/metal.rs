@@ -0,0 +1,24 @@
+use crate::block::Position;
+use crate::game;
+use crate::mode::GameMode;
+use crate::sound;
+
+pub struct Metal;
+
+impl GameMode for Metal {
+    fn label(&self) -> &str {
+        "Metal"
+    }
+
+    fn board_size(&self) -> Position {
+        game::METAL_BOARD_SIZE
+    }
+
+    fn pieces(&self) -> &str {
+        "metal"
+    }
+
+    fn music(&self) -> &str {
+        sound::METAL_MUSIC_PATH
+    }
+}
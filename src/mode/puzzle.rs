@@ -0,0 +1,120 @@
+/*
+
+Plays a puzzle, see puzzle.rs. The board starts with the puzzle's
+drawing and blocks are dealt from its sequence instead of at random.
+The puzzle is lost once the last block has locked without reaching the
+goal. Practice boards have no goal and deal random blocks after the
+sequence instead.
+
+*/
+
+use crate::block;
+use crate::block::{Block, Position};
+use crate::game;
+use crate::mode::{GameMode, Outcome, Randomizer, Ranking};
+use crate::puzzle;
+use crate::puzzle::{Goal, Puzzle};
+use crate::sound;
+use crate::stats;
+
+pub struct PuzzleMode {
+    puzzle: Puzzle,
+}
+
+impl PuzzleMode {
+    pub fn new(puzzle: Puzzle) -> PuzzleMode {
+        PuzzleMode { puzzle: puzzle }
+    }
+
+    fn goal_reached(&self, game: &game::Game) -> bool {
+        match self.puzzle.goal {
+            Goal::Lines(n) => game.lines >= n,
+            Goal::PerfectClear => {
+                game.lines > 0 && game.board.iter().flatten().all(|cell| cell.is_none())
+            }
+            Goal::Practice => false,
+        }
+    }
+}
+
+// Blocks that have locked. The block in play was the last one dealt.
+fn pieces_placed(game: &game::Game) -> usize {
+    (stats::pieces(&game.stats) as usize).saturating_sub(1)
+}
+
+impl GameMode for PuzzleMode {
+    fn label(&self) -> &str {
+        &self.puzzle.name
+    }
+
+    fn board_size(&self) -> Position {
+        self.puzzle.board_size
+    }
+
+    fn pieces(&self) -> &str {
+        &self.puzzle.pieces
+    }
+
+    fn music(&self) -> &str {
+        sound::CHILL_MUSIC_PATH
+    }
+
+    fn on_start(&self, game: &mut game::Game) {
+        let top = game.board.len() - self.puzzle.rows.len();
+        game.board.splice(top.., self.puzzle.rows.iter().cloned());
+    }
+
+    // Once the sequence runs out the last block is dealt again. The
+    // puzzle is over by then so it never falls.
+    fn next_block(&self, game: &mut game::Game) -> Block {
+        let sequence = &self.puzzle.sequence;
+        let dealt = stats::pieces(&game.stats) as usize;
+        if dealt >= sequence.len() && self.puzzle.goal == Goal::Practice {
+            return block::spawn(&game.blocks, Randomizer::Bag, &mut game.bag, &mut game.rng);
+        }
+        let name = &sequence[dealt.min(sequence.len() - 1)];
+        game.blocks
+            .iter()
+            .find(|block| block.label.name() == name.as_str())
+            .unwrap()
+            .clone()
+    }
+
+    fn outcome(&self, game: &game::Game) -> Outcome {
+        if self.goal_reached(game) {
+            return Outcome::Won;
+        }
+        let practice = self.puzzle.goal == Goal::Practice;
+        if !practice && pieces_placed(game) >= self.puzzle.sequence.len() {
+            return Outcome::Lost;
+        }
+        Outcome::Playing
+    }
+
+    fn ranking(&self) -> Ranking {
+        Ranking::Unranked
+    }
+
+    fn progress(&self, game: &game::Game) -> Option<String> {
+        if self.puzzle.goal == Goal::Practice {
+            return None;
+        }
+        let pieces_left = self
+            .puzzle
+            .sequence
+            .len()
+            .saturating_sub(pieces_placed(game));
+        let goal = match self.puzzle.goal {
+            Goal::Lines(n) => format!("{} lines", (n - game.lines).max(0)),
+            goal => puzzle::goal_label(goal),
+        };
+        match pieces_left {
+            1 => Some(format!("{}, 1 piece", goal)),
+            n => Some(format!("{}, {} pieces", goal, n)),
+        }
+    }
+
+    fn can_save(&self) -> bool {
+        false
+    }
+}
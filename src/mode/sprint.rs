@@ -0,0 +1,64 @@
+/*
+
+Clear SPRINT_LINES lines as quickly as possible. The high score table
+is ordered by time.
+
+*/
+
+use std::time;
+
+use crate::block::Position;
+use crate::game;
+use crate::mode::{GameMode, Outcome, Randomizer, Ranking};
+use crate::sound;
+
+const SPRINT_LINES: i32 = 40;
+const COUNTDOWN_SECONDS: u64 = 3;
+
+pub struct Sprint;
+
+impl GameMode for Sprint {
+    fn label(&self) -> &str {
+        "Sprint"
+    }
+
+    fn board_size(&self) -> Position {
+        game::CLASSIC_BOARD_SIZE
+    }
+
+    fn pieces(&self) -> &str {
+        "classic"
+    }
+
+    fn music(&self) -> &str {
+        sound::CLASSIC_MUSIC_PATH
+    }
+
+    fn randomizer(&self) -> Randomizer {
+        Randomizer::Bag
+    }
+
+    fn outcome(&self, game: &game::Game) -> Outcome {
+        if game.lines >= SPRINT_LINES {
+            return Outcome::Won;
+        }
+        Outcome::Playing
+    }
+
+    fn ranking(&self) -> Ranking {
+        Ranking::Time
+    }
+
+    fn progress(&self, game: &game::Game) -> Option<String> {
+        let left = (SPRINT_LINES - game.lines).max(0);
+        Some(format!("{} lines left", left))
+    }
+
+    fn timer(&self, game: &game::Game) -> Option<time::Duration> {
+        Some(game.time_played)
+    }
+
+    fn countdown(&self) -> time::Duration {
+        time::Duration::from_secs(COUNTDOWN_SECONDS)
+    }
+}
@@ -0,0 +1,74 @@
+/*
+
+Survive as long as possible while garbage rows are pushed in from the
+bottom of the board. The first row comes after FIRST_INTERVAL and each
+one after that comes sooner, down to MIN_INTERVAL.
+
+*/
+
+use std::time;
+
+use rand::Rng;
+
+use crate::block::Position;
+use crate::game;
+use crate::garbage;
+use crate::mode::{GameMode, Outcome, Randomizer};
+use crate::sound;
+
+const FIRST_INTERVAL: u64 = 10_000; // milliseconds
+const INTERVAL_STEP: u64 = 400; // milliseconds
+const MIN_INTERVAL: u64 = 2_000; // milliseconds
+
+pub struct Survival;
+
+impl GameMode for Survival {
+    fn label(&self) -> &str {
+        "Survival"
+    }
+
+    fn board_size(&self) -> Position {
+        game::CLASSIC_BOARD_SIZE
+    }
+
+    fn pieces(&self) -> &str {
+        "classic"
+    }
+
+    fn music(&self) -> &str {
+        sound::CLASSIC_MUSIC_PATH
+    }
+
+    fn randomizer(&self) -> Randomizer {
+        Randomizer::Bag
+    }
+
+    fn on_frame(&self, game: &mut game::Game) -> Outcome {
+        if game.time_played < next_row_due(game.garbage_inserted) {
+            return Outcome::Playing;
+        }
+        let width = game.board[0].len();
+        let hole = game.rng.gen_range(0, width);
+        game.garbage_inserted += 1;
+        if !game::insert_garbage(game, vec![garbage::row(width, hole)]) {
+            return Outcome::Lost;
+        }
+        Outcome::Playing
+    }
+
+    fn timer(&self, game: &game::Game) -> Option<time::Duration> {
+        Some(game.time_played)
+    }
+}
+
+// Time played when the next row is pushed in, after `inserted` rows.
+fn next_row_due(inserted: u32) -> time::Duration {
+    let millis: u64 = (0..=inserted as u64)
+        .map(|n| {
+            FIRST_INTERVAL
+                .saturating_sub(n * INTERVAL_STEP)
+                .max(MIN_INTERVAL)
+        })
+        .sum();
+    time::Duration::from_millis(millis)
+}
@@ -0,0 +1,60 @@
+/*
+
+Score as much as possible before ULTRA_DURATION runs out. The timer
+counts down.
+
+*/
+
+use std::time;
+
+use crate::block::Position;
+use crate::game;
+use crate::mode::{GameMode, Outcome, Randomizer};
+use crate::sound;
+
+const ULTRA_DURATION: time::Duration = time::Duration::from_secs(2 * 60);
+const COUNTDOWN_SECONDS: u64 = 3;
+
+pub struct Ultra;
+
+impl GameMode for Ultra {
+    fn label(&self) -> &str {
+        "Ultra"
+    }
+
+    fn board_size(&self) -> Position {
+        game::CLASSIC_BOARD_SIZE
+    }
+
+    fn pieces(&self) -> &str {
+        "classic"
+    }
+
+    fn music(&self) -> &str {
+        sound::CLASSIC_MUSIC_PATH
+    }
+
+    fn randomizer(&self) -> Randomizer {
+        Randomizer::Bag
+    }
+
+    // Time running out ends the game, the score is what counts.
+    fn outcome(&self, game: &game::Game) -> Outcome {
+        if game.time_played >= ULTRA_DURATION {
+            return Outcome::Won;
+        }
+        Outcome::Playing
+    }
+
+    fn timer(&self, game: &game::Game) -> Option<time::Duration> {
+        Some(
+            ULTRA_DURATION
+                .checked_sub(game.time_played)
+                .unwrap_or_default(),
+        )
+    }
+
+    fn countdown(&self) -> time::Duration {
+        time::Duration::from_secs(COUNTDOWN_SECONDS)
+    }
+}
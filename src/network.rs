@@ -0,0 +1,370 @@
+/*
+
+Versus over TCP, see versus.rs. Each side plays its own game and sends
+the other what it needs to draw it rather than its inputs, so the two
+games never have to be kept in step. Garbage and the end of a game are
+sent as they happen.
+
+Messages are lines of text with words split by spaces:
+
+    hello 1                    protocol version, sent first by both
+    start 1234 Sprint          the seed of the joining player and the
+                               mode, sent by the host
+    board 120 4 53000 18 ...   score, lines, time played in ms, width of
+                               the board and its cells with the block in
+                               play, "-" or a hex colour, split by commas
+    garbage 2 5                rows to push in and the column of the hole,
+                               at most MAX_GARBAGE_ROWS rows a message
+    over won                   or lost, once the game has finished
+
+Players with a different protocol version can't play each other, so
+the format can change without old games misreading it.
+
+*/
+
+use std::convert::TryFrom;
+use std::io::{ErrorKind, Read, Write};
+use std::net::{TcpListener, TcpStream, ToSocketAddrs};
+use std::time::Duration;
+
+use crate::block;
+use crate::game;
+use crate::game::Board;
+
+pub const PROTOCOL_VERSION: u32 = 1;
+pub const DEFAULT_PORT: u16 = 7777;
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(3);
+// More rows than any board is tall, larger attacks are split.
+pub const MAX_GARBAGE_ROWS: usize = 64;
+// Text kept waiting for the end of a message. A board message for the
+// biggest built in board is a few KB.
+const MAX_RECEIVED: usize = 1 << 20;
+
+pub enum Message {
+    Hello {
+        version: u32,
+    },
+    Start {
+        seed: u64,
+        mode: String,
+    },
+    Board {
+        score: i32,
+        lines: i32,
+        time_played: u128,
+        board: Board,
+    },
+    Garbage {
+        rows: usize,
+        hole: usize,
+    },
+    Over {
+        won: bool,
+    },
+}
+
+// A connection to the other player. Neither reading or writing waits,
+// so the game keeps running while messages are on their way.
+pub struct Link {
+    stream: TcpStream,
+    // Text of a message that hasn't fully arrived yet.
+    received: String,
+    // Bytes the connection wasn't ready to take yet.
+    unsent: Vec<u8>,
+}
+
+pub fn listen(port: u16) -> Result<TcpListener, String> {
+    let listener = TcpListener::bind(("0.0.0.0", port))
+        .map_err(|error| format!("Can't host on port {}: {}", port, error))?;
+    listener
+        .set_nonblocking(true)
+        .map_err(|error| error.to_string())?;
+    Ok(listener)
+}
+
+// Returns the link once a player has joined.
+pub fn accept(listener: &TcpListener) -> Result<Option<Link>, String> {
+    match listener.accept() {
+        Ok((stream, _)) => open_link(stream).map(Some),
+        Err(error) if error.kind() == ErrorKind::WouldBlock => Ok(None),
+        Err(error) => Err(error.to_string()),
+    }
+}
+
+// Connects to a host given as "address" or "address:port".
+pub fn connect(address: &str) -> Result<Link, String> {
+    let address = match address.contains(':') {
+        true => address.to_string(),
+        false => format!("{}:{}", address, DEFAULT_PORT),
+    };
+    let error = |error: std::io::Error| format!("Can't join {}: {}", address, error);
+    let socket_address = address
+        .to_socket_addrs()
+        .map_err(error)?
+        .next()
+        .ok_or(format!("Can't find {}", address))?;
+    let stream = TcpStream::connect_timeout(&socket_address, CONNECT_TIMEOUT).map_err(error)?;
+    open_link(stream)
+}
+
+fn open_link(stream: TcpStream) -> Result<Link, String> {
+    stream
+        .set_nonblocking(true)
+        .map_err(|error| error.to_string())?;
+    // Messages are small and wanted straight away.
+    stream
+        .set_nodelay(true)
+        .map_err(|error| error.to_string())?;
+    Ok(Link {
+        stream: stream,
+        received: "".to_string(),
+        unsent: vec![],
+    })
+}
+
+pub fn send(link: &mut Link, message: &Message) -> Result<(), String> {
+    link.unsent.extend(encode(message).bytes());
+    link.unsent.push(b'\n');
+    flush(link)
+}
+
+// Writes as much of the unsent text as the connection will take.
+fn flush(link: &mut Link) -> Result<(), String> {
+    while !link.unsent.is_empty() {
+        match link.stream.write(&link.unsent) {
+            Ok(0) => return Err("The other player left".to_string()),
+            Ok(n) => {
+                link.unsent.drain(..n);
+            }
+            Err(error) if error.kind() == ErrorKind::WouldBlock => return Ok(()),
+            Err(error) => return Err(error.to_string()),
+        }
+    }
+    Ok(())
+}
+
+// Returns the messages that have arrived since the last call. Fails if
+// the other player has left or sent something that can't be read.
+pub fn receive(link: &mut Link) -> Result<Vec<Message>, String> {
+    flush(link)?;
+    let mut buffer = [0; 4096];
+    // The rest is read next time, once the messages so far are taken.
+    while link.received.len() <= MAX_RECEIVED {
+        match link.stream.read(&mut buffer) {
+            Ok(0) => return Err("The other player left".to_string()),
+            Ok(n) => link
+                .received
+                .push_str(&String::from_utf8_lossy(&buffer[..n])),
+            Err(error) if error.kind() == ErrorKind::WouldBlock => break,
+            Err(error) => return Err(error.to_string()),
+        }
+    }
+    let mut messages = vec![];
+    while let Some(end) = link.received.find('\n') {
+        let line: String = link.received.drain(..=end).collect();
+        messages.push(decode(line.trim())?);
+    }
+    if link.received.len() > MAX_RECEIVED {
+        return Err("The other player sent a message that's too long".to_string());
+    }
+    Ok(messages)
+}
+
+// What the other player needs to draw the game.
+pub fn board_message(game: &game::Game) -> Message {
+    let mut board = game.board.clone();
+    game.block
+        .positions
+        .iter()
+        .for_each(|p| board[p.y as usize][p.x as usize] = Some(game.block.color));
+    Message::Board {
+        score: game.score,
+        lines: game.lines,
+        time_played: game.time_played.as_millis(),
+        board: board,
+    }
+}
+
+pub fn encode(message: &Message) -> String {
+    match message {
+        Message::Hello { version } => format!("hello {}", version),
+        Message::Start { seed, mode } => format!("start {} {}", seed, mode),
+        Message::Board {
+            score,
+            lines,
+            time_played,
+            board,
+        } => {
+            let cells: Vec<String> = board
+                .iter()
+                .flatten()
+                .map(|cell| match cell {
+                    Some(color) => color.to_hex(),
+                    None => "-".to_string(),
+                })
+                .collect();
+            format!(
+                "board {} {} {} {} {}",
+                score,
+                lines,
+                time_played,
+                board[0].len(),
+                cells.join(",")
+            )
+        }
+        Message::Garbage { rows, hole } => format!("garbage {} {}", rows, hole),
+        Message::Over { won: true } => "over won".to_string(),
+        Message::Over { won: false } => "over lost".to_string(),
+    }
+}
+
+pub fn decode(line: &str) -> Result<Message, String> {
+    let error = || format!("Can't read message from the other player: {}", line);
+    let (kind, rest) = line.split_once(' ').unwrap_or((line, ""));
+    let numbers: Vec<u128> = rest
+        .split(' ')
+        .map_while(|word| word.parse().ok())
+        .collect();
+    let number = |i: usize| numbers.get(i).copied().ok_or_else(error);
+    let size = |i: usize| number(i).and_then(|n| usize::try_from(n).map_err(|_| error()));
+    match kind {
+        "hello" => Ok(Message::Hello {
+            version: number(0)? as u32,
+        }),
+        "start" => {
+            let (_, mode) = rest.split_once(' ').ok_or_else(error)?;
+            Ok(Message::Start {
+                seed: number(0)? as u64,
+                mode: mode.to_string(),
+            })
+        }
+        "board" => {
+            let width = number(3)? as usize;
+            let cells = rest.split(' ').nth(4).ok_or_else(error)?;
+            let cells: Option<Vec<Option<block::Color>>> = cells
+                .split(',')
+                .map(|cell| match cell {
+                    "-" => Some(None),
+                    hex => block::Color::from_hex(hex).map(Some),
+                })
+                .collect();
+            let cells = cells.ok_or_else(error)?;
+            if width == 0 || cells.is_empty() || cells.len() % width != 0 {
+                return Err(error());
+            }
+            Ok(Message::Board {
+                score: number(0)? as i32,
+                lines: number(1)? as i32,
+                time_played: number(2)?,
+                board: cells.chunks(width).map(|row| row.to_vec()).collect(),
+            })
+        }
+        // The hole is checked against the board by the receiver.
+        "garbage" => match (size(0)?, size(1)?) {
+            (rows, hole) if rows <= MAX_GARBAGE_ROWS => Ok(Message::Garbage {
+                rows: rows,
+                hole: hole,
+            }),
+            _ => Err(error()),
+        },
+        "over" => match rest {
+            "won" => Ok(Message::Over { won: true }),
+            "lost" => Ok(Message::Over { won: false }),
+            _ => Err(error()),
+        },
+        _ => Err(error()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn round_trip(message: &Message) -> String {
+        let line = encode(message);
+        assert_eq!(encode(&decode(&line).unwrap()), line);
+        line
+    }
+
+    #[test]
+    fn messages_round_trip() {
+        round_trip(&Message::Hello { version: 1 });
+        let line = round_trip(&Message::Start {
+            seed: u64::MAX,
+            mode: "Tiny Sprint".to_string(),
+        });
+        assert_eq!(line, "start 18446744073709551615 Tiny Sprint");
+        round_trip(&Message::Garbage { rows: 3, hole: 9 });
+        round_trip(&Message::Over { won: true });
+        round_trip(&Message::Over { won: false });
+
+        let color = block::Color { r: 1, g: 2, b: 3 };
+        let line = round_trip(&Message::Board {
+            score: 120,
+            lines: 4,
+            time_played: 53_000,
+            board: vec![vec![None, Some(color)], vec![Some(color), None]],
+        });
+        assert_eq!(line, "board 120 4 53000 2 -,010203,010203,-");
+    }
+
+    #[test]
+    fn rejects_bad_messages() {
+        assert!(decode("garbage 18446744073709551615 0").is_err());
+        assert!(decode("garbage 65 0").is_err());
+        assert!(decode("garbage 2 340282366920938463463374607431768211455").is_err());
+        assert!(decode("garbage 2").is_err());
+        assert!(decode("board 1 2 3 0 -").is_err());
+        assert!(decode("board 1 2 3 2 -,-,-").is_err());
+        assert!(decode("board 1 2 3 2 -,zzzzzz").is_err());
+        assert!(decode("over maybe").is_err());
+        assert!(decode("shout hi").is_err());
+    }
+
+    // Waits for the next messages, the links don't block.
+    fn wait_for(link: &mut Link) -> Vec<Message> {
+        for _ in 0..500 {
+            let messages = receive(link).unwrap();
+            if !messages.is_empty() {
+                return messages;
+            }
+            std::thread::sleep(Duration::from_millis(10));
+        }
+        panic!("nothing arrived");
+    }
+
+    #[test]
+    fn plays_over_loopback() {
+        let listener = listen(0).unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let mut joined = connect(&format!("127.0.0.1:{}", port)).unwrap();
+        let mut host = (0..500)
+            .find_map(|_| {
+                std::thread::sleep(Duration::from_millis(10));
+                accept(&listener).unwrap()
+            })
+            .unwrap();
+
+        send(
+            &mut joined,
+            &Message::Hello {
+                version: PROTOCOL_VERSION,
+            },
+        )
+        .unwrap();
+        let messages = wait_for(&mut host);
+        assert!(matches!(
+            messages[..],
+            [Message::Hello {
+                version: PROTOCOL_VERSION
+            }]
+        ));
+
+        send(&mut host, &Message::Garbage { rows: 2, hole: 5 }).unwrap();
+        let messages = wait_for(&mut joined);
+        assert!(matches!(
+            messages[..],
+            [Message::Garbage { rows: 2, hole: 5 }]
+        ));
+    }
+}
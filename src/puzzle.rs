@@ -0,0 +1,483 @@
+/*
+
+Puzzles start from a hand drawn board and give the player a fixed list
+of pieces to reach a goal with. They come in packs, one pack per file in
+assets/puzzles or the puzzles directory of the config directory:
+
+  pack = First Steps
+
+  puzzle = Tetris
+  sequence = I             # dealt in this order
+  goal = lines 4           # "lines N", "perfect" to empty the board or
+                           # "practice" to play on with random blocks
+  pieces = classic         # optional, the piece file the names are from
+  board = 10, 20           # optional, columns and rows
+  color = A 3661fe         # optional, a letter for a coloured cell
+
+  .........X
+  .........A
+  .........A
+  .........X
+
+The drawing is the bottom of the board, 'X' is a garbage cell and '.'
+is empty. Each "puzzle =" starts the next puzzle of the pack.
+
+Puzzles made in the editor are added to a pack in the user puzzles
+directory.
+
+*/
+
+use std::fs;
+use std::rc::Rc;
+
+use crate::block;
+use crate::block::Position;
+use crate::config;
+use crate::game;
+use crate::menu;
+use crate::mode;
+
+const PUZZLES_DIR: &str = "puzzles"; // in the assets directory
+const USER_PUZZLES_DIR: &str = "puzzles";
+const EDITOR_PACK_FILE: &str = "editor.txt";
+const EDITOR_PACK_NAME: &str = "My Puzzles";
+const GARBAGE_CELL: char = 'X';
+const EMPTY_CELL: char = '.';
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Goal {
+    Lines(i32),
+    // Every cell of the board is empty after a clear.
+    PerfectClear,
+    // No goal. Once the sequence is used up blocks are dealt at random
+    // until the player tops out.
+    Practice,
+}
+
+#[derive(Clone)]
+pub struct Puzzle {
+    pub name: String,
+    // Includes the hidden row at the top of the board.
+    pub board_size: Position,
+    // The bottom rows of the board, top to bottom.
+    pub rows: game::Board,
+    pub pieces: String,
+    // Names of the blocks dealt, in order.
+    pub sequence: Vec<String>,
+    pub goal: Goal,
+}
+
+pub struct Pack {
+    pub name: String,
+    pub puzzles: Vec<Puzzle>,
+}
+
+// State of the puzzle select screen.
+pub struct Select {
+    pub packs: Vec<Pack>,
+    pub pack_selected: usize,
+    pub puzzle_selected: usize,
+}
+
+pub fn initialise_select() -> Select {
+    Select {
+        packs: load_packs(),
+        pack_selected: 0,
+        puzzle_selected: 0,
+    }
+}
+
+// Built in packs followed by the player's, each sorted by file name.
+pub fn load_packs() -> Vec<Pack> {
+    let mut dirs = vec![config::asset_path(PUZZLES_DIR)];
+    if let Some(dir) = config::file_path(USER_PUZZLES_DIR) {
+        dirs.push(dir);
+    }
+
+    dirs.iter()
+        .flat_map(|dir| {
+            let mut paths: Vec<_> = match fs::read_dir(dir) {
+                Ok(entries) => entries
+                    .filter_map(|entry| entry.ok())
+                    .map(|entry| entry.path())
+                    .filter(|path| path.extension() == Some("txt".as_ref()))
+                    .collect(),
+                Err(_) => vec![],
+            };
+            paths.sort();
+            paths
+        })
+        .filter_map(|path| {
+            let name = path.file_stem()?.to_string_lossy().to_string();
+            let parsed = fs::read_to_string(&path)
+                .map_err(|error| error.to_string())
+                .and_then(|contents| parse_pack(&contents, &name));
+            match parsed {
+                Ok(pack) => Some(pack),
+                Err(error) => {
+                    eprintln!("Ignoring {}: {}", path.display(), error);
+                    None
+                }
+            }
+        })
+        .collect()
+}
+
+fn empty_puzzle(name: &str) -> Puzzle {
+    Puzzle {
+        name: name.to_string(),
+        board_size: game::CLASSIC_BOARD_SIZE,
+        rows: vec![],
+        pieces: "classic".to_string(),
+        sequence: vec![],
+        goal: Goal::PerfectClear,
+    }
+}
+
+// Parses a pack file. The pack is named after the file unless it says
+// otherwise. Errors name the line they were found on.
+pub fn parse_pack(contents: &str, file_name: &str) -> Result<Pack, String> {
+    let mut pack = Pack {
+        name: file_name.to_string(),
+        puzzles: vec![],
+    };
+    // Letters given a colour by the puzzle being read.
+    let mut colors: Vec<(char, block::Color)> = vec![];
+
+    for (i, line) in contents.lines().enumerate() {
+        // Allow comments after values.
+        let line = line.split('#').next().unwrap_or("").trim();
+        let error = |message: String| format!("line {}: {}", i + 1, message);
+        if line.is_empty() {
+            continue;
+        }
+
+        if let Some((key, value)) = config::parse_pairs(line).pop() {
+            let invalid = || error(format!("invalid value for {}: \"{}\"", key, value));
+            match key.as_str() {
+                "pack" => pack.name = value,
+                "puzzle" => {
+                    pack.puzzles.push(empty_puzzle(&value));
+                    colors.clear();
+                }
+                _ if pack.puzzles.is_empty() => {
+                    return Err(error("expected \"puzzle = <name>\" first".to_string()))
+                }
+                "pieces" => pack.puzzles.last_mut().unwrap().pieces = value,
+                "sequence" => {
+                    pack.puzzles.last_mut().unwrap().sequence = value
+                        .split(',')
+                        .map(|name| name.trim().to_string())
+                        .filter(|name| !name.is_empty())
+                        .collect();
+                }
+                "goal" => {
+                    let words: Vec<&str> = value.split_whitespace().collect();
+                    pack.puzzles.last_mut().unwrap().goal = match words.as_slice() {
+                        ["perfect"] => Goal::PerfectClear,
+                        ["practice"] => Goal::Practice,
+                        ["lines", n] => match n.parse() {
+                            Ok(n) if n > 0 => Goal::Lines(n),
+                            _ => return Err(invalid()),
+                        },
+                        _ => return Err(invalid()),
+                    };
+                }
+                "board" => {
+                    let size: Vec<i32> = value
+                        .split(',')
+                        .map(|v| v.trim().parse().ok())
+                        .collect::<Option<Vec<i32>>>()
+                        .ok_or_else(invalid)?;
+                    match size.as_slice() {
+                        &[x, y] if x >= 4 && y >= 4 => {
+                            pack.puzzles.last_mut().unwrap().board_size =
+                                Position { x: x, y: y + 1 };
+                        }
+                        _ => return Err(invalid()),
+                    }
+                }
+                "color" => {
+                    let mut parts = value.split_whitespace();
+                    let letter = parts.next().and_then(|l| {
+                        let mut chars = l.chars();
+                        match (chars.next(), chars.next()) {
+                            (Some(c), None) if c.is_ascii_alphabetic() && c != GARBAGE_CELL => {
+                                Some(c)
+                            }
+                            _ => None,
+                        }
+                    });
+                    let color = parts.next().and_then(block::Color::from_hex);
+                    match (letter, color, parts.next()) {
+                        (Some(letter), Some(color), None) => colors.push((letter, color)),
+                        _ => return Err(invalid()),
+                    }
+                }
+                _ => return Err(error(format!("unknown key \"{}\"", key))),
+            }
+        } else if line
+            .chars()
+            .all(|c| c.is_ascii_alphabetic() || c == EMPTY_CELL)
+        {
+            let puzzle = match pack.puzzles.last_mut() {
+                Some(puzzle) => puzzle,
+                None => return Err(error("board before \"puzzle = <name>\"".to_string())),
+            };
+            let row = line
+                .chars()
+                .map(|c| match c {
+                    EMPTY_CELL => Ok(None),
+                    GARBAGE_CELL => Ok(Some(block::GARBAGE_COLOR)),
+                    c => match colors.iter().find(|(letter, _)| *letter == c) {
+                        Some(&(_, color)) => Ok(Some(color)),
+                        None => Err(error(format!("no color given for '{}'", c))),
+                    },
+                })
+                .collect::<Result<Vec<_>, String>>()?;
+            puzzle.rows.push(row);
+        } else {
+            return Err(error("boards can only contain letters and '.'".to_string()));
+        }
+    }
+
+    if pack.puzzles.is_empty() {
+        return Err("no puzzles defined".to_string());
+    }
+    for puzzle in &pack.puzzles {
+        check_puzzle(puzzle).map_err(|error| format!("puzzle {}: {}", puzzle.name, error))?;
+    }
+    Ok(pack)
+}
+
+pub fn check_puzzle(puzzle: &Puzzle) -> Result<(), String> {
+    let size = puzzle.board_size;
+    if puzzle.rows.iter().any(|row| row.len() != size.x as usize) {
+        return Err(format!("board rows should be {} cells wide", size.x));
+    }
+    // The top row of the board is hidden and where blocks spawn.
+    if puzzle.rows.len() >= size.y as usize - 1 {
+        return Err("the board drawing is too tall".to_string());
+    }
+    if puzzle.sequence.is_empty() && puzzle.goal != Goal::Practice {
+        return Err("the sequence needs at least one piece".to_string());
+    }
+    let blocks = block::find_blocks(&puzzle.pieces)?;
+    let area = block::spawn_area(&blocks);
+    if area.x > size.x {
+        return Err(format!(
+            "the {} pieces don't fit on the board",
+            puzzle.pieces
+        ));
+    }
+    // Blocks have to spawn and turn above the drawn rows.
+    if puzzle.rows.len() as i32 + area.y > size.y {
+        return Err(format!(
+            "the board drawing leaves no room for the {} pieces to spawn",
+            puzzle.pieces
+        ));
+    }
+    let labels = block::labels(&blocks);
+    match puzzle
+        .sequence
+        .iter()
+        .find(|name| !labels.iter().any(|label| label.name() == name.as_str()))
+    {
+        Some(name) => Err(format!("no piece called {} in {}", name, puzzle.pieces)),
+        None => Ok(()),
+    }
+}
+
+pub fn goal_label(goal: Goal) -> String {
+    match goal {
+        Goal::Lines(1) => "Clear 1 line".to_string(),
+        Goal::Lines(n) => format!("Clear {} lines", n),
+        Goal::PerfectClear => "Perfect clear".to_string(),
+        Goal::Practice => "Practice".to_string(),
+    }
+}
+
+// The puzzle as it is written in a pack file.
+pub fn format_puzzle(puzzle: &Puzzle) -> String {
+    let goal = match puzzle.goal {
+        Goal::Lines(n) => format!("lines {}", n),
+        Goal::PerfectClear => "perfect".to_string(),
+        Goal::Practice => "practice".to_string(),
+    };
+    let mut lines = vec![
+        format!("puzzle = {}", puzzle.name),
+        format!("sequence = {}", puzzle.sequence.join(", ")),
+        format!("goal = {}", goal),
+        format!("pieces = {}", puzzle.pieces),
+        format!(
+            "board = {}, {}",
+            puzzle.board_size.x,
+            puzzle.board_size.y - 1
+        ),
+    ];
+
+    // Colours other than garbage are given letters in the order they
+    // appear.
+    let mut colors: Vec<block::Color> = vec![];
+    puzzle.rows.iter().flatten().for_each(|cell| match cell {
+        Some(color) if *color != block::GARBAGE_COLOR && !colors.contains(color) => {
+            colors.push(*color)
+        }
+        _ => {}
+    });
+    let letters: Vec<char> = ('A'..='Z').filter(|&c| c != GARBAGE_CELL).collect();
+    colors.iter().zip(&letters).for_each(|(color, letter)| {
+        lines.push(format!("color = {} {}", letter, color.to_hex()));
+    });
+
+    lines.push("".to_string());
+    puzzle.rows.iter().for_each(|row| {
+        lines.push(
+            row.iter()
+                .map(|cell| match cell {
+                    None => EMPTY_CELL,
+                    Some(color) if *color == block::GARBAGE_COLOR => GARBAGE_CELL,
+                    Some(color) => match colors.iter().position(|c| c == color) {
+                        Some(i) if i < letters.len() => letters[i],
+                        // Out of letters, the rest become garbage.
+                        _ => GARBAGE_CELL,
+                    },
+                })
+                .collect(),
+        );
+    });
+    lines.push("".to_string());
+    lines.join("\n")
+}
+
+// Adds the puzzle to the editor's pack in the user puzzles directory
+// and returns the name it was given.
+pub fn save_to_editor_pack(puzzle: &Puzzle) -> String {
+    let path = format!("{}/{}", USER_PUZZLES_DIR, EDITOR_PACK_FILE);
+    let mut contents =
+        config::read_file(&path).unwrap_or_else(|| format!("pack = {}\n", EDITOR_PACK_NAME));
+    let count = parse_pack(&contents, EDITOR_PACK_NAME).map_or(0, |pack| pack.puzzles.len());
+    let mut puzzle = puzzle.clone();
+    puzzle.name = format!("{} {}", goal_label(puzzle.goal), count + 1);
+
+    contents.push('\n');
+    contents.push_str(&format_puzzle(&puzzle));
+    config::write_file(&path, &contents);
+    puzzle.name
+}
+
+pub fn update(event: &Option<game::Input>, world: &mut game::World) {
+    let select = &mut world.puzzle_select;
+    let n_packs = select.packs.len();
+    if let Some(event) = event {
+        match event {
+            game::Input::LeftKeyDown if n_packs > 0 => {
+                select.pack_selected = (select.pack_selected + n_packs - 1) % n_packs;
+                select.puzzle_selected = 0;
+            }
+            game::Input::RightKeyDown if n_packs > 0 => {
+                select.pack_selected = (select.pack_selected + 1) % n_packs;
+                select.puzzle_selected = 0;
+            }
+            game::Input::UpKeyDown if n_packs > 0 => {
+                let n_puzzles = select.packs[select.pack_selected].puzzles.len();
+                select.puzzle_selected = (select.puzzle_selected + n_puzzles - 1) % n_puzzles;
+            }
+            game::Input::DownKeyDown if n_packs > 0 => {
+                let n_puzzles = select.packs[select.pack_selected].puzzles.len();
+                select.puzzle_selected = (select.puzzle_selected + 1) % n_puzzles;
+            }
+            game::Input::ReturnKeyDown | game::Input::SpaceKeyDown if n_packs > 0 => {
+                let puzzle = &select.packs[select.pack_selected].puzzles[select.puzzle_selected];
+                let mode = Rc::new(mode::puzzle::PuzzleMode::new(puzzle.clone()));
+                menu::start_game_in_mode(world, mode);
+            }
+            game::Input::EscKeyDown | game::Input::ReturnKeyDown | game::Input::SpaceKeyDown => {
+                world.state = game::State::Menu;
+            }
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PACK: &str = "
+pack = Test Pack
+
+puzzle = Tetris
+sequence = I, O
+goal = lines 4
+color = A 3661fe
+
+XXXXXXXXX.
+XXXXXXXXX.
+AAAAXXXXX.
+XXXXXXXXX.
+
+puzzle = Free Play
+sequence =
+goal = practice
+board = 12, 16
+";
+
+    #[test]
+    fn parses_a_pack() {
+        let pack = parse_pack(PACK, "test").unwrap();
+        assert_eq!(pack.name, "Test Pack");
+        assert_eq!(pack.puzzles.len(), 2);
+
+        let tetris = &pack.puzzles[0];
+        assert_eq!(tetris.sequence, vec!["I", "O"]);
+        assert!(tetris.goal == Goal::Lines(4));
+        assert_eq!(tetris.rows.len(), 4);
+        assert!(tetris.rows[2][0] == block::Color::from_hex("3661fe"));
+        assert!(tetris.rows[2][5] == Some(block::GARBAGE_COLOR));
+        assert!(tetris.rows[2][9].is_none());
+
+        let free_play = &pack.puzzles[1];
+        assert!(free_play.goal == Goal::Practice);
+        assert!(free_play.board_size == Position { x: 12, y: 17 });
+    }
+
+    #[test]
+    fn puzzle_round_trip() {
+        let pack = parse_pack(PACK, "test").unwrap();
+        let text: String = pack.puzzles.iter().map(format_puzzle).collect();
+        let again = parse_pack(&text, "test").unwrap();
+        assert_eq!(again.name, "test");
+        pack.puzzles.iter().zip(&again.puzzles).for_each(|(a, b)| {
+            assert_eq!(a.name, b.name);
+            assert_eq!(a.sequence, b.sequence);
+            assert!(a.goal == b.goal);
+            assert!(a.board_size == b.board_size);
+            assert!(a.rows == b.rows);
+        });
+    }
+
+    #[test]
+    fn rejects_bad_puzzles() {
+        let error = |contents: &str| parse_pack(contents, "test").err().unwrap();
+        assert!(error("sequence = I\n").starts_with("line 1:"));
+        assert!(error("puzzle = A\nsequence = I\n\nXXB\n").contains("no color"));
+        assert!(error("puzzle = A\ngoal = lines 0\n").contains("invalid"));
+        assert!(error("puzzle = A\nsequence = I\nXXX\n").contains("wide"));
+        assert!(error("puzzle = A\nsequence = Q\n").contains("no piece called Q"));
+        assert!(error("puzzle = A\ngoal = perfect\n").contains("at least one"));
+        assert!(error("puzzle = A\nsequence = I\nboard = 5, 10\n").contains("fit"));
+        assert!(error("pack = Empty\n").contains("no puzzles"));
+        let crowded =
+            "puzzle = A\nsequence = I\nboard = 10, 4\n\nXXXXXXXXX.\nXXXXXXXXX.\nXXXXXXXXX.\n";
+        assert!(error(crowded).contains("no room"));
+        assert!(parse_pack(&crowded.replace("10, 4", "10, 5"), "test").is_ok());
+    }
+
+    #[test]
+    fn built_in_packs_load() {
+        let path = config::asset_path(PUZZLES_DIR).join("first-steps.txt");
+        let contents = fs::read_to_string(path).unwrap();
+        assert!(parse_pack(&contents, "first-steps").is_ok());
+    }
+}
@@ -0,0 +1,51 @@
+/*
+
+A small deterministic random number generator (SplitMix64).
+
+Unlike thread_rng its whole state is a single u64, so it can be saved
+along with a game and the same sequence of blocks can be reproduced
+from a seed. It implements rand's RngCore so it works with the rand
+helpers such as SliceRandom::choose.
+
+*/
+
+use rand::RngCore;
+
+#[derive(Clone)]
+pub struct Rng {
+    pub state: u64,
+}
+
+pub fn from_seed(seed: u64) -> Rng {
+    Rng { state: seed }
+}
+
+pub fn random_seed() -> u64 {
+    rand::thread_rng().next_u64()
+}
+
+impl RngCore for Rng {
+    fn next_u32(&mut self) -> u32 {
+        (self.next_u64() >> 32) as u32
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        dest.chunks_mut(8).for_each(|chunk| {
+            let bytes = self.next_u64().to_le_bytes();
+            chunk.copy_from_slice(&bytes[..chunk.len()]);
+        });
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), rand::Error> {
+        self.fill_bytes(dest);
+        Ok(())
+    }
+}
@@ -0,0 +1,369 @@
+/*
+
+Every game is recorded as a replay so the best run of each mode can be
+raced as a ghost. A replay is the board the game started with and each
+change made to the board since, with the time played when it happened:
+the blocks as they locked and the garbage rows pushed in. Playing them
+back onto the starting board gives exactly the boards of the game, so
+a ghost never drifts from the run the way replaying key presses
+against the clock could, and it can be advanced alongside any game.
+
+When a game ends better than the mode's ghost, by the mode's high score
+ranking, its replay is saved in the config directory as the new ghost,
+one file per mode, e.g. ghost-Sprint.txt. The file uses the "key =
+value" format of the settings:
+
+    score = 1200                 how the run ended
+    lines = 40
+    level = 5
+    time_played_ms = 61234
+    timestamp = 1700000000
+    row = . . ff0000 ...         the starting board, top to bottom
+    lock = 1500 10 1 ff0000 3,20 4,20 5,20 4,21
+                                 time played in ms, score and lines
+                                 after the lock, colour and positions
+    garbage = 30000 808080 . ...  a row pushed in from the bottom
+
+*/
+
+use std::time;
+
+use crate::block;
+use crate::block::Position;
+use crate::config;
+use crate::game;
+use crate::game::Board;
+use crate::highscore;
+use crate::mode;
+use crate::mode::Ranking;
+use crate::save;
+
+const VERSION: u32 = 1;
+
+pub enum Event {
+    Lock {
+        at: time::Duration,
+        positions: Vec<Position>,
+        color: block::Color,
+        score: i32, // after lines were cleared
+        lines: i32,
+    },
+    Garbage {
+        at: time::Duration,
+        row: Vec<Option<block::Color>>,
+    },
+}
+
+pub struct Replay {
+    pub start_board: Board,
+    pub events: Vec<Event>,
+}
+
+// A saved replay being played back next to a game.
+pub struct Ghost {
+    pub result: highscore::Entry, // how the run ended
+    pub board: Board,
+    pub score: i32,
+    pub lines: i32,
+    events: Vec<Event>,
+    next_event: usize,
+}
+
+// Starts recording once the mode has set up the board.
+pub fn initialise(board: &Board) -> Replay {
+    Replay {
+        start_board: board.clone(),
+        events: vec![],
+    }
+}
+
+// Called once full lines have been removed after the block locked.
+pub fn record_lock(game: &mut game::Game, locked: &block::Block) {
+    let event = Event::Lock {
+        at: game.time_played,
+        positions: locked.positions.clone(),
+        color: locked.color,
+        score: game.score,
+        lines: game.lines,
+    };
+    if let Some(replay) = &mut game.replay {
+        replay.events.push(event);
+    }
+}
+
+pub fn record_garbage(game: &mut game::Game, rows: &[Vec<Option<block::Color>>]) {
+    let at = game.time_played;
+    if let Some(replay) = &mut game.replay {
+        replay.events.extend(rows.iter().map(|row| Event::Garbage {
+            at: at,
+            row: row.clone(),
+        }));
+    }
+}
+
+fn file_name(mode: &mode::Mode) -> String {
+    // Custom mode names can have characters a file name can't.
+    let name: String = mode
+        .label()
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect();
+    format!("ghost-{}.txt", name)
+}
+
+// Saves the game's replay as the mode's ghost if it beats the one saved.
+pub fn save_if_best(game: &game::Game) {
+    let replay = match &game.replay {
+        Some(replay) => replay,
+        None => return,
+    };
+    let mode = game::current_mode(game);
+    let ranking = mode.ranking();
+    let eligible = match ranking {
+        Ranking::Score => game.score > 0,
+        Ranking::Time => game.outcome == mode::Outcome::Won,
+        Ranking::Unranked => false,
+    };
+    let result = highscore::entry_from_game(game, "");
+    let better = match load_ghost(&mode) {
+        Some(ghost) => {
+            highscore::compare_entries(ranking, &result, &ghost.result) == std::cmp::Ordering::Less
+        }
+        None => true,
+    };
+    if eligible && better {
+        config::write_file(&file_name(&mode), &encode(replay, &result));
+    }
+}
+
+fn encode(replay: &Replay, result: &highscore::Entry) -> String {
+    let mut lines = vec![
+        "# Tetris Bane ghost".to_string(),
+        format!("version = {}", VERSION),
+        format!("score = {}", result.score),
+        format!("lines = {}", result.lines),
+        format!("level = {}", result.level),
+        format!("time_played_ms = {}", result.duration_millis),
+        format!("timestamp = {}", result.timestamp),
+    ];
+    replay
+        .start_board
+        .iter()
+        .for_each(|row| lines.push(format!("row = {}", save::row_to_string(row))));
+    replay.events.iter().for_each(|event| {
+        lines.push(match event {
+            Event::Lock {
+                at,
+                positions,
+                color,
+                score,
+                lines,
+            } => format!(
+                "lock = {} {} {} {} {}",
+                at.as_millis(),
+                score,
+                lines,
+                color.to_hex(),
+                save::positions_to_string(positions)
+            ),
+            Event::Garbage { at, row } => {
+                format!("garbage = {} {}", at.as_millis(), save::row_to_string(row))
+            }
+        })
+    });
+    lines.push("".to_string());
+    lines.join("\n")
+}
+
+// Returns None if the mode has no ghost or it can't be read.
+pub fn load_ghost(mode: &mode::Mode) -> Option<Ghost> {
+    parse_ghost(&config::read_file(&file_name(mode))?, mode.board_size())
+}
+
+// Returns None if the ghost can't be read or isn't for a board of the
+// given size.
+fn parse_ghost(contents: &str, size: Position) -> Option<Ghost> {
+    let pairs = config::parse_pairs(contents);
+    let value = |key: &str| {
+        pairs
+            .iter()
+            .find(|(k, _)| k == key)
+            .map(|(_, v)| v.as_str())
+    };
+    if value("version")?.parse::<u32>().ok()? != VERSION {
+        return None;
+    }
+    let result = highscore::Entry {
+        name: "".to_string(),
+        score: value("score")?.parse().ok()?,
+        lines: value("lines")?.parse().ok()?,
+        level: value("level")?.parse().ok()?,
+        duration_millis: value("time_played_ms")?.parse().ok()?,
+        timestamp: value("timestamp")?.parse().ok()?,
+    };
+
+    let board = pairs
+        .iter()
+        .filter(|(k, _)| k == "row")
+        .map(|(_, row)| save::parse_row(row))
+        .collect::<Option<Board>>()?;
+    // A ghost from a mode whose board has since changed can't be shown.
+    if board.len() != size.y as usize || board.iter().any(|row| row.len() != size.x as usize) {
+        return None;
+    }
+
+    let events = pairs
+        .iter()
+        .filter(|(k, _)| k == "lock" || k == "garbage")
+        .map(|(k, v)| parse_event(k, v))
+        .collect::<Option<Vec<Event>>>()?;
+    let on_board = |p: &Position| p.x >= 0 && p.x < size.x && p.y >= 0 && p.y < size.y;
+    let fits = events.iter().all(|event| match event {
+        Event::Lock { positions, .. } => positions.iter().all(on_board),
+        Event::Garbage { row, .. } => row.len() == size.x as usize,
+    });
+    if !fits {
+        return None;
+    }
+
+    Some(Ghost {
+        result: result,
+        board: board,
+        score: 0,
+        lines: 0,
+        events: events,
+        next_event: 0,
+    })
+}
+
+fn parse_event(key: &str, value: &str) -> Option<Event> {
+    let (at, rest) = value.split_once(' ')?;
+    let at = time::Duration::from_millis(at.parse().ok()?);
+    match key {
+        "lock" => {
+            let mut words = rest.splitn(4, ' ');
+            let score = words.next()?.parse().ok()?;
+            let lines = words.next()?.parse().ok()?;
+            let color = block::Color::from_hex(words.next()?)?;
+            let positions = save::parse_positions(words.next()?)?;
+            Some(Event::Lock {
+                at: at,
+                positions: positions,
+                color: color,
+                score: score,
+                lines: lines,
+            })
+        }
+        _ => Some(Event::Garbage {
+            at: at,
+            row: save::parse_row(rest)?,
+        }),
+    }
+}
+
+// Plays the ghost's run up to the time played.
+pub fn advance(ghost: &mut Ghost, time_played: time::Duration) {
+    while let Some(event) = ghost.events.get(ghost.next_event) {
+        match event {
+            Event::Lock {
+                at,
+                positions,
+                color,
+                score,
+                lines,
+            } if *at <= time_played => {
+                let board = &mut ghost.board;
+                positions
+                    .iter()
+                    .for_each(|p| board[p.y as usize][p.x as usize] = Some(*color));
+                ghost.board = game::delete_full_lines(&ghost.board).0;
+                ghost.score = *score;
+                ghost.lines = *lines;
+            }
+            Event::Garbage { at, row } if *at <= time_played => {
+                ghost.board.remove(0);
+                ghost.board.push(row.clone());
+            }
+            _ => break,
+        }
+        ghost.next_event += 1;
+    }
+}
+
+pub fn is_finished(ghost: &Ghost, time_played: time::Duration) -> bool {
+    time_played.as_millis() >= ghost.result.duration_millis
+}
+
+// How the game compares with the ghost at the same time, drawn under
+// the ghost's board.
+pub fn race_line(ghost: &Ghost, game: &game::Game) -> String {
+    let ranking = game::current_mode(game).ranking();
+    if is_finished(ghost, game.time_played) {
+        return match ranking {
+            Ranking::Time => format!(
+                "Best {}",
+                highscore::format_precise_duration(ghost.result.duration_millis)
+            ),
+            _ => format!("Best {}", ghost.result.score),
+        };
+    }
+    let (difference, unit) = match ranking {
+        // Timed goals are mostly lines, e.g. Sprint.
+        Ranking::Time => (game.lines - ghost.lines, "lines"),
+        _ => (game.score - ghost.score, "points"),
+    };
+    match difference {
+        0 => "Level with best".to_string(),
+        d if d > 0 => format!("Ahead by {} {}", d, unit),
+        d => format!("Behind by {} {}", -d, unit),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A classic game dealt from a fixed seed, with the block dropped
+    // straight down in different columns.
+    fn played_game() -> game::Game {
+        game::played_game(7, 3000, |frame| match frame % 11 {
+            0 if frame % 3 == 0 => Some(game::Input::LeftKeyDown),
+            0 => Some(game::Input::RightKeyDown),
+            5 => Some(game::Input::DownKeyDown),
+            _ => None,
+        })
+    }
+
+    #[test]
+    fn ghost_plays_back_the_recorded_game() {
+        let game = played_game();
+        let replay = game.replay.as_ref().unwrap();
+        assert!(replay.events.len() > 3);
+
+        let result = highscore::entry_from_game(&game, "");
+        let mut ghost = parse_ghost(&encode(replay, &result), game.mode.board_size()).unwrap();
+        assert_eq!(ghost.result.score, game.score);
+        assert_eq!(ghost.result.duration_millis, game.time_played.as_millis());
+
+        advance(&mut ghost, game.time_played);
+        assert!(ghost.board == game.board);
+        assert_eq!(ghost.score, game.score);
+        assert_eq!(ghost.lines, game.lines);
+        assert!(is_finished(&ghost, game.time_played));
+    }
+
+    #[test]
+    fn ghost_needs_a_matching_board() {
+        let game = played_game();
+        let result = highscore::entry_from_game(&game, "");
+        let text = encode(game.replay.as_ref().unwrap(), &result);
+        assert!(parse_ghost(&text, game::BANE_BOARD_SIZE).is_none());
+        assert!(parse_ghost(
+            &text.replace("version = 1", "version = 2"),
+            game::CLASSIC_BOARD_SIZE
+        )
+        .is_none());
+        let garbage = format!("{}garbage = 10 . .\n", text);
+        assert!(parse_ghost(&garbage, game::CLASSIC_BOARD_SIZE).is_none());
+    }
+}
@@ -0,0 +1,355 @@
+/*
+
+An in progress game can be saved from the pause menu and continued
+later from the main menu. The save is removed once it is continued so
+there is only ever one.
+
+The file uses the same "key = value" format as the settings. The board
+is stored as one "row" line per board row, top to bottom, with each
+cell either "." when empty or the hex colour of the cell.
+
+*/
+
+use std::collections::HashMap;
+use std::fs;
+use std::time;
+
+use crate::block;
+use crate::block::Position;
+use crate::config;
+use crate::game;
+use crate::mode;
+use crate::random;
+use crate::stats;
+
+const SAVE_FILE: &str = "savegame.cfg";
+const VERSION: u32 = 1;
+const EMPTY_CELL: &str = ".";
+
+pub fn exists() -> bool {
+    match config::file_path(SAVE_FILE) {
+        Some(path) => path.exists(),
+        None => false,
+    }
+}
+
+pub fn delete() {
+    if let Some(path) = config::file_path(SAVE_FILE) {
+        if path.exists() {
+            if let Err(error) = fs::remove_file(&path) {
+                eprintln!("Unable to remove {}: {}", path.display(), error);
+            }
+        }
+    }
+}
+
+pub fn save(game: &game::Game) {
+    config::write_file(SAVE_FILE, &to_text(game));
+}
+
+// The text of a save file, as read by parse.
+pub fn to_text(game: &game::Game) -> String {
+    let stats = &game.stats;
+    let mut lines = vec![
+        "# Tetris Bane saved game".to_string(),
+        format!("version = {}", VERSION),
+        format!("mode = {}", game.mode.label()),
+        format!("score = {}", game.score),
+        format!("lines = {}", game.lines),
+        format!("level = {}", game.level),
+        format!("time_played_ms = {}", game.time_played.as_millis()),
+        format!(
+            "drop_elapsed_ms = {}",
+            game::elapsed(game, game.block_drop_clock).as_millis()
+        ),
+        format!("rng = {}", game.rng.state),
+        format!("bag = {}", join(&game.bag, ",")),
+        format!("garbage_inserted = {}", game.garbage_inserted),
+        format!("outcome = {}", outcome_to_string(game.outcome)),
+        format!("endless = {}", game.endless),
+        format!("block_label = {}", game.block.label.name()),
+        format!("block_color = {}", game.block.color.to_hex()),
+        format!("block_orientation = {}", game.block_orientation),
+        format!(
+            "block_positions = {}",
+            positions_to_string(&game.block.positions)
+        ),
+        format!("stats_pieces = {}", piece_counts_to_string(stats)),
+        format!("stats_clears = {}", join(&stats.clears, ",")),
+        format!("stats_holes = {}", stats.holes_created),
+        format!("stats_max_stack_height = {}", stats.max_stack_height),
+        format!("stats_inputs = {}", stats.inputs),
+        format!(
+            "stats_piece_spawned_at_ms = {}",
+            stats.piece_spawned_at.as_millis()
+        ),
+        format!(
+            "stats_slowest_piece_ms = {}",
+            stats.slowest_piece.as_millis()
+        ),
+    ];
+    game.board
+        .iter()
+        .for_each(|row| lines.push(format!("row = {}", row_to_string(row))));
+    lines.push("".to_string());
+    lines.join("\n")
+}
+
+// Returns None if there is no save or it can't be read.
+pub fn load() -> Option<game::Game> {
+    parse(&config::read_file(SAVE_FILE)?)
+}
+
+// Returns None if the save can't be read or doesn't fit its mode.
+pub fn parse(contents: &str) -> Option<game::Game> {
+    let pairs = config::parse_pairs(contents);
+    let value = |key: &str| {
+        pairs
+            .iter()
+            .find(|(k, _)| k == key)
+            .map(|(_, v)| v.as_str())
+    };
+    let number = |key: &str| value(key)?.parse::<u64>().ok();
+
+    if value("version")?.parse::<u32>().ok()? != VERSION {
+        return None;
+    }
+
+    let mode = value("mode")?;
+    let mode_selected = mode::initialise_modes()
+        .iter()
+        .position(|m| m.label() == mode)?;
+    let mut game = game::initialise_game(mode_selected);
+    // A continued game starts paused rather than counting down.
+    game.countdown = time::Duration::from_millis(0);
+    // Only games recorded from the start can become the mode's ghost.
+    game.replay = None;
+
+    game.score = value("score")?.parse().ok()?;
+    game.lines = value("lines")?.parse().ok()?;
+    game.level = value("level")?.parse().ok()?;
+    game.time_played = time::Duration::from_millis(number("time_played_ms")?);
+    let drop_elapsed = time::Duration::from_millis(number("drop_elapsed_ms")?);
+    game.block_drop_clock = time::Instant::now()
+        .checked_sub(drop_elapsed)
+        .unwrap_or_else(time::Instant::now);
+    game.rng = random::from_seed(number("rng")?);
+    game.bag = parse_list(value("bag")?, ',')?;
+    if game.bag.iter().any(|&i| i >= game.blocks.len()) {
+        return None;
+    }
+    game.garbage_inserted = value("garbage_inserted")?.parse().ok()?;
+    // A game won and played on mustn't be won again, see game::update.
+    game.outcome = parse_outcome(value("outcome")?)?;
+    game.endless = value("endless")?.parse().ok()?;
+
+    game.board = pairs
+        .iter()
+        .filter(|(k, _)| k == "row")
+        .map(|(_, row)| parse_row(row))
+        .collect::<Option<game::Board>>()?;
+    let width = game.board.first()?.len();
+    if width == 0 || game.board.iter().any(|row| row.len() != width) {
+        return None;
+    }
+
+    let label = value("block_label")?;
+    let mut block = game
+        .blocks
+        .iter()
+        .find(|b| b.label.name() == label)?
+        .clone();
+    block.color = block::Color::from_hex(value("block_color")?)?;
+    block.positions = parse_positions(value("block_positions")?)?;
+    game.block = block;
+    game.block_orientation = value("block_orientation")?.parse().ok()?;
+    if game.block_orientation as usize >= block::orientation_count(&game.block) {
+        return None;
+    }
+    let on_board = |p: &Position| {
+        (0..width as i32).contains(&p.x) && (0..game.board.len() as i32).contains(&p.y)
+    };
+    if !game.block.positions.iter().all(on_board) {
+        return None;
+    }
+
+    let stats = &mut game.stats;
+    stats.piece_counts = parse_piece_counts(value("stats_pieces")?)?;
+    stats.clears = parse_list(value("stats_clears")?, ',')?;
+    stats.holes_created = value("stats_holes")?.parse().ok()?;
+    stats.max_stack_height = value("stats_max_stack_height")?.parse().ok()?;
+    stats.inputs = value("stats_inputs")?.parse().ok()?;
+    stats.piece_spawned_at = time::Duration::from_millis(number("stats_piece_spawned_at_ms")?);
+    stats.slowest_piece = time::Duration::from_millis(number("stats_slowest_piece_ms")?);
+
+    Some(game)
+}
+
+fn outcome_to_string(outcome: mode::Outcome) -> &'static str {
+    match outcome {
+        mode::Outcome::Playing => "playing",
+        mode::Outcome::Won => "won",
+        mode::Outcome::Lost => "lost",
+    }
+}
+
+fn parse_outcome(outcome: &str) -> Option<mode::Outcome> {
+    match outcome {
+        "playing" => Some(mode::Outcome::Playing),
+        "won" => Some(mode::Outcome::Won),
+        "lost" => Some(mode::Outcome::Lost),
+        _ => None,
+    }
+}
+
+fn join<T: ToString>(values: &[T], separator: &str) -> String {
+    values
+        .iter()
+        .map(|v| v.to_string())
+        .collect::<Vec<String>>()
+        .join(separator)
+}
+
+fn parse_list<T: std::str::FromStr>(list: &str, separator: char) -> Option<Vec<T>> {
+    list.split(separator)
+        .filter(|v| !v.trim().is_empty())
+        .map(|v| v.trim().parse().ok())
+        .collect()
+}
+
+pub fn row_to_string(row: &[Option<block::Color>]) -> String {
+    row.iter()
+        .map(|cell| match cell {
+            Some(color) => color.to_hex(),
+            None => EMPTY_CELL.to_string(),
+        })
+        .collect::<Vec<String>>()
+        .join(" ")
+}
+
+pub fn parse_row(row: &str) -> Option<Vec<Option<block::Color>>> {
+    row.split_whitespace()
+        .map(|cell| match cell {
+            EMPTY_CELL => Some(None),
+            hex => block::Color::from_hex(hex).map(Some),
+        })
+        .collect()
+}
+
+// Positions are stored as "x,y" pairs separated by spaces.
+pub fn positions_to_string(positions: &[Position]) -> String {
+    positions
+        .iter()
+        .map(|p| format!("{},{}", p.x, p.y))
+        .collect::<Vec<String>>()
+        .join(" ")
+}
+
+pub fn parse_positions(positions: &str) -> Option<Vec<Position>> {
+    positions
+        .split_whitespace()
+        .map(|p| {
+            let xy: Vec<i32> = parse_list(p, ',')?;
+            match xy.as_slice() {
+                [x, y] => Some(Position { x: *x, y: *y }),
+                _ => None,
+            }
+        })
+        .collect()
+}
+
+// Label names can contain spaces so counts are separated by ';'.
+fn piece_counts_to_string(stats: &stats::Stats) -> String {
+    let mut counts: Vec<String> = stats
+        .piece_counts
+        .iter()
+        .map(|(label, n)| format!("{}:{}", label.name(), n))
+        .collect();
+    counts.sort();
+    counts.join(";")
+}
+
+fn parse_piece_counts(counts: &str) -> Option<HashMap<block::Label, u32>> {
+    counts
+        .split(';')
+        .filter(|c| !c.trim().is_empty())
+        .map(|c| {
+            let mut parts = c.splitn(2, ':');
+            let label = block::Label::new(parts.next()?.trim());
+            let n = parts.next()?.trim().parse().ok()?;
+            Some((label, n))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A classic game part way through, with a block locked on the board.
+    fn played_game() -> game::Game {
+        game::played_game(42, 2000, |frame| match frame % 7 {
+            0 => Some(game::Input::LeftKeyDown),
+            3 => Some(game::Input::UpKeyDown),
+            _ => None,
+        })
+    }
+
+    #[test]
+    fn saved_game_round_trip() {
+        let game = played_game();
+        assert!(game.board.iter().flatten().any(|cell| cell.is_some()));
+
+        let loaded = parse(&to_text(&game)).unwrap();
+        assert!(loaded.board == game.board);
+        assert!(loaded.block.positions == game.block.positions);
+        assert!(loaded.block.label == game.block.label);
+        assert_eq!(loaded.block_orientation, game.block_orientation);
+        assert_eq!(loaded.score, game.score);
+        assert_eq!(loaded.lines, game.lines);
+        assert_eq!(loaded.level, game.level);
+        assert_eq!(loaded.rng.state, game.rng.state);
+        assert_eq!(loaded.bag, game.bag);
+        assert_eq!(loaded.time_played.as_millis(), game.time_played.as_millis());
+        assert!(loaded.stats.piece_counts == game.stats.piece_counts);
+        assert!(loaded.outcome == game.outcome);
+        assert_eq!(loaded.endless, game.endless);
+        assert_eq!(
+            to_text(&loaded).lines().count(),
+            to_text(&game).lines().count()
+        );
+    }
+
+    #[test]
+    fn broken_saves_are_not_loaded() {
+        let text = to_text(&played_game());
+        assert!(parse(&text.replace("version = 1", "version = 0")).is_none());
+        assert!(parse(&text.replace("mode = Classic", "mode = Nope")).is_none());
+        let block = text
+            .lines()
+            .find(|l| l.starts_with("block_positions"))
+            .unwrap();
+        let off_board = text.replace(block, "block_positions = 99,0 0,0 1,0 2,0");
+        assert!(parse(&off_board).is_none());
+        assert!(parse(&text.replace("garbage_inserted", "garbage")).is_none());
+    }
+
+    #[test]
+    fn won_game_goes_on_after_loading() {
+        let mut game = played_game();
+        game.outcome = mode::Outcome::Won;
+        game.endless = true;
+        let loaded = parse(&to_text(&game)).unwrap();
+        assert!(loaded.outcome == mode::Outcome::Won);
+        assert!(loaded.endless);
+    }
+
+    #[test]
+    fn row_round_trip() {
+        let color = block::Color { r: 1, g: 2, b: 255 };
+        let row = vec![None, Some(color), None];
+        assert_eq!(row_to_string(&row), ". 0102ff .");
+        assert!(parse_row(&row_to_string(&row)) == Some(row));
+        assert!(parse_row(". 01 .").is_none());
+    }
+}
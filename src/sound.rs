@@ -1,15 +1,10 @@
-use crate::game;
+use crate::mode;
 
-const BANE_MUSIC_PATH: &str = "assets/music/Kevin_MacLeod_-_Ghost_Dance.ogg";
-const CHILL_MUSIC_PATH: &str = "assets/music/a-very-brady-special-by-kevin-macleod.ogg";
-const CLASSIC_MUSIC_PATH: &str = "assets/music/midnight-tale-by-kevin-macleod.ogg";
-const METAL_MUSIC_PATH: &str = "assets/music/tetrismetal.ogg";
+pub const BANE_MUSIC_PATH: &str = "assets/music/Kevin_MacLeod_-_Ghost_Dance.ogg";
+pub const CHILL_MUSIC_PATH: &str = "assets/music/a-very-brady-special-by-kevin-macleod.ogg";
+pub const CLASSIC_MUSIC_PATH: &str = "assets/music/midnight-tale-by-kevin-macleod.ogg";
+pub const METAL_MUSIC_PATH: &str = "assets/music/tetrismetal.ogg";
 
-pub fn music_path(mode: &game::Mode) -> String {
-    match mode {
-        game::Mode::Bane { .. } => BANE_MUSIC_PATH.to_string(),
-        game::Mode::Chill { .. } => CHILL_MUSIC_PATH.to_string(),
-        game::Mode::Classic { .. } => CLASSIC_MUSIC_PATH.to_string(),
-        game::Mode::Metal { .. } => METAL_MUSIC_PATH.to_string(),
-    }
+pub fn music_path(mode: &mode::Mode) -> String {
+    mode.music().to_string()
 }
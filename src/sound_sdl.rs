@@ -17,7 +17,9 @@ pub fn handle_music(music: &Music, state: &game::State, toggle: bool, volume: i3
         // Game is paused and music is not playing and toggle is on
         (game::State::Paused, false, true) => music.fade_in(-1, FADE_DURATION).unwrap(),
         // Game is playing, music is not playing and toggle is on
-        (game::State::Play, false, true) => music.fade_in(-1, FADE_DURATION).unwrap(),
+        (game::State::Play, false, true)
+        | (game::State::Versus, false, true)
+        | (game::State::Coop, false, true) => music.fade_in(-1, FADE_DURATION).unwrap(),
         _ => {}
     }
 }
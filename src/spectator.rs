@@ -0,0 +1,338 @@
+/*
+
+A live feed of the game for overlays, dashboards or another program
+drawing it, turned on with the --spectate command line flag, see
+main.rs. Anything connecting to the socket is sent one line of JSON
+each frame:
+
+    {"frame":812,"state":"Play",
+     "games":[{"mode":"Classic","score":12,"lines":3,"level":1,
+               "time_played_ms":40123,
+               "board":[[null,"ff0000",...],...],
+               "blocks":[{"name":"T","color":"a000f0",
+                          "cells":[[4,1],[5,1],[6,1],[5,2]]}]}],
+     "events":[{"type":"lock","game":0},
+               {"type":"clear","game":0,"lines":2}]}
+
+The board is the rows from the top, including the hidden top row, with
+null for an empty cell or the hex colour of the cell. There is a game
+for each board on screen: one when playing alone, one per player in
+versus, and one with both players' blocks in co-op. The menus and other
+screens send no games.
+
+Events are what happened since the last frame: "state" when the screen
+changes, with the new state, "lock" when a block locked and "clear" when
+lines were cleared.
+
+The socket is TCP, on 127.0.0.1 unless an address is given, or on Unix
+a socket file given as "unix:<path>". Nothing is read from spectators,
+and one that falls too far behind is dropped rather than slowing the
+game down.
+
+*/
+
+use std::io::{ErrorKind, Write};
+use std::net::{TcpListener, TcpStream};
+#[cfg(unix)]
+use std::os::unix::fs::FileTypeExt;
+#[cfg(unix)]
+use std::os::unix::net::{UnixListener, UnixStream};
+
+use crate::block;
+use crate::game;
+use crate::stats;
+
+pub const DEFAULT_PORT: u16 = 7778;
+// Bytes waiting for a spectator before it's dropped. A frame of the
+// Classic board is one or two KB, so this is around ten seconds.
+const MAX_UNSENT: usize = 1 << 20;
+
+enum Listener {
+    Tcp(TcpListener),
+    #[cfg(unix)]
+    Unix(UnixListener),
+}
+
+struct Client {
+    stream: Box<dyn Write>,
+    unsent: Vec<u8>,
+}
+
+pub struct Spectator {
+    listener: Listener,
+    clients: Vec<Client>,
+    frame: u64,
+    // Kept from the last frame to find the events.
+    last_state: Option<String>,
+    last_games: Vec<(u32, i32)>, // pieces dealt and lines of each game
+}
+
+// Listens on "port", "address:port" or "unix:<path>".
+pub fn listen(address: &str) -> Result<Spectator, String> {
+    let listener = match address.strip_prefix("unix:") {
+        Some(path) => listen_unix(path)?,
+        None => {
+            let address = match address.parse::<u16>() {
+                Ok(port) => format!("127.0.0.1:{}", port),
+                Err(_) => address.to_string(),
+            };
+            let listener = TcpListener::bind(&address)
+                .map_err(|error| format!("Can't stream on {}: {}", address, error))?;
+            listener
+                .set_nonblocking(true)
+                .map_err(|error| error.to_string())?;
+            Listener::Tcp(listener)
+        }
+    };
+    Ok(Spectator {
+        listener: listener,
+        clients: vec![],
+        frame: 0,
+        last_state: None,
+        last_games: vec![],
+    })
+}
+
+#[cfg(unix)]
+fn listen_unix(path: &str) -> Result<Listener, String> {
+    // A socket file left by a game that didn't exit cleanly. Anything
+    // else at the path is left alone and binding fails.
+    let is_socket = std::fs::symlink_metadata(path)
+        .map(|metadata| metadata.file_type().is_socket())
+        .unwrap_or(false);
+    if is_socket {
+        let _ = std::fs::remove_file(path);
+    }
+    let listener =
+        UnixListener::bind(path).map_err(|error| format!("Can't stream on {}: {}", path, error))?;
+    listener
+        .set_nonblocking(true)
+        .map_err(|error| error.to_string())?;
+    Ok(Listener::Unix(listener))
+}
+
+#[cfg(not(unix))]
+fn listen_unix(_path: &str) -> Result<Listener, String> {
+    Err("Unix sockets aren't available here, give a port instead".to_string())
+}
+
+fn accept(spectator: &mut Spectator) {
+    loop {
+        let stream: Box<dyn Write> = match &spectator.listener {
+            Listener::Tcp(listener) => match listener.accept() {
+                Ok((stream, _)) => match open_tcp(stream) {
+                    Some(stream) => Box::new(stream),
+                    None => continue,
+                },
+                Err(_) => return,
+            },
+            #[cfg(unix)]
+            Listener::Unix(listener) => match listener.accept() {
+                Ok((stream, _)) => match open_unix(stream) {
+                    Some(stream) => Box::new(stream),
+                    None => continue,
+                },
+                Err(_) => return,
+            },
+        };
+        spectator.clients.push(Client {
+            stream: stream,
+            unsent: vec![],
+        });
+    }
+}
+
+fn open_tcp(stream: TcpStream) -> Option<TcpStream> {
+    stream.set_nonblocking(true).ok()?;
+    stream.set_nodelay(true).ok()?;
+    Some(stream)
+}
+
+#[cfg(unix)]
+fn open_unix(stream: UnixStream) -> Option<UnixStream> {
+    stream.set_nonblocking(true).ok()?;
+    Some(stream)
+}
+
+// Sends the frame to every spectator, called once each frame.
+pub fn publish(spectator: &mut Spectator, world: &game::World) {
+    accept(spectator);
+    let line = frame_json(spectator, world);
+    spectator.frame += 1;
+    if spectator.clients.is_empty() {
+        return;
+    }
+    spectator.clients.retain_mut(|client| {
+        client.unsent.extend(line.bytes());
+        client.unsent.push(b'\n');
+        flush(client) && client.unsent.len() < MAX_UNSENT
+    });
+}
+
+// Writes what the spectator will take. Returns false once it has gone.
+fn flush(client: &mut Client) -> bool {
+    while !client.unsent.is_empty() {
+        match client.stream.write(&client.unsent) {
+            Ok(0) => return false,
+            Ok(n) => {
+                client.unsent.drain(..n);
+            }
+            Err(error) if error.kind() == ErrorKind::WouldBlock => return true,
+            Err(_) => return false,
+        }
+    }
+    true
+}
+
+// The boards on screen, each with its blocks in play.
+fn games_on_screen(world: &game::World) -> Vec<(&game::Game, Vec<&block::Block>)> {
+    match world.state {
+        game::State::Play
+        | game::State::Countdown
+        | game::State::Paused
+        | game::State::GameOver
+        | game::State::Victory => vec![(&world.game, vec![&world.game.block])],
+        game::State::Versus => world
+            .versus
+            .players
+            .iter()
+            .map(|player| (&player.game, vec![&player.game.block]))
+            .collect(),
+        game::State::Coop => {
+            let game = &world.coop.game;
+            vec![(game, vec![&game.block, &world.coop.seat.block])]
+        }
+        _ => vec![],
+    }
+}
+
+fn frame_json(spectator: &mut Spectator, world: &game::World) -> String {
+    let state = state_name(&world.state).to_string();
+    let games = games_on_screen(world);
+    let mut events = vec![];
+    if spectator.last_state.as_ref() != Some(&state) {
+        events.push(format!(
+            "{{\"type\":\"state\",\"state\":{}}}",
+            json_string(&state)
+        ));
+    }
+    let counts: Vec<(u32, i32)> = games
+        .iter()
+        .map(|(game, _)| (stats::pieces(&game.stats), game.lines))
+        .collect();
+    // Counts from another screen's games aren't compared.
+    if counts.len() == spectator.last_games.len() && spectator.last_state.as_ref() == Some(&state) {
+        counts
+            .iter()
+            .zip(&spectator.last_games)
+            .enumerate()
+            .for_each(|(i, (&(pieces, lines), &(last_pieces, last_lines)))| {
+                if pieces > last_pieces {
+                    events.push(format!("{{\"type\":\"lock\",\"game\":{}}}", i));
+                }
+                if lines > last_lines {
+                    events.push(format!(
+                        "{{\"type\":\"clear\",\"game\":{},\"lines\":{}}}",
+                        i,
+                        lines - last_lines
+                    ));
+                }
+            });
+    }
+    spectator.last_state = Some(state.clone());
+    spectator.last_games = counts;
+
+    let games: Vec<String> = games
+        .iter()
+        .map(|(game, blocks)| game_json(game, blocks))
+        .collect();
+    format!(
+        "{{\"frame\":{},\"state\":{},\"games\":[{}],\"events\":[{}]}}",
+        spectator.frame,
+        json_string(&state),
+        games.join(","),
+        events.join(",")
+    )
+}
+
+fn game_json(game: &game::Game, blocks: &[&block::Block]) -> String {
+    let blocks: Vec<String> = blocks.iter().map(|block| block_json(block)).collect();
+    format!(
+        "{{\"mode\":{},\"score\":{},\"lines\":{},\"level\":{},\"time_played_ms\":{},\"board\":{},\"blocks\":[{}]}}",
+        json_string(game.mode.label()),
+        game.score,
+        game.lines,
+        game.level,
+        game.time_played.as_millis(),
+        board_json(&game.board),
+        blocks.join(",")
+    )
+}
+
+// The rows from the top, each cell null or a hex colour.
+pub fn board_json(board: &game::Board) -> String {
+    let rows: Vec<String> = board
+        .iter()
+        .map(|row| {
+            let cells: Vec<String> = row
+                .iter()
+                .map(|cell| match cell {
+                    Some(color) => json_string(&color.to_hex()),
+                    None => "null".to_string(),
+                })
+                .collect();
+            format!("[{}]", cells.join(","))
+        })
+        .collect();
+    format!("[{}]", rows.join(","))
+}
+
+pub fn block_json(block: &block::Block) -> String {
+    let cells: Vec<String> = block
+        .positions
+        .iter()
+        .map(|p| format!("[{},{}]", p.x, p.y))
+        .collect();
+    format!(
+        "{{\"name\":{},\"color\":{},\"cells\":[{}]}}",
+        json_string(block.label.name()),
+        json_string(&block.color.to_hex()),
+        cells.join(",")
+    )
+}
+
+// Mode and piece names come from files so may need escaping.
+pub fn json_string(text: &str) -> String {
+    let mut json = "\"".to_string();
+    text.chars().for_each(|c| match c {
+        '"' => json.push_str("\\\""),
+        '\\' => json.push_str("\\\\"),
+        c if (c as u32) < 0x20 => json.push_str(&format!("\\u{:04x}", c as u32)),
+        c => json.push(c),
+    });
+    json.push('"');
+    json
+}
+
+pub fn state_name(state: &game::State) -> &'static str {
+    match state {
+        game::State::Play => "Play",
+        game::State::Countdown => "Countdown",
+        game::State::Menu => "Menu",
+        game::State::Quit => "Quit",
+        game::State::GameOver => "GameOver",
+        game::State::Victory => "Victory",
+        game::State::Paused => "Paused",
+        game::State::NameEntry => "NameEntry",
+        game::State::HighScores => "HighScores",
+        game::State::Summary => "Summary",
+        game::State::PuzzleSelect => "PuzzleSelect",
+        game::State::Editor => "Editor",
+        game::State::VersusSetup => "VersusSetup",
+        game::State::VersusConnecting => "VersusConnecting",
+        game::State::Versus => "Versus",
+        game::State::VersusResult => "VersusResult",
+        game::State::Coop => "Coop",
+        game::State::CoopResult => "CoopResult",
+    }
+}
@@ -0,0 +1,219 @@
+/*
+
+Statistics recorded while a game is played. They are updated live by
+game::update, can be drawn next to the board and are appended to a
+history file in the config directory when the game ends, one game per
+line:
+
+  timestamp|mode|score|lines|level|duration_ms|pieces|inputs|holes|max_height|clears
+
+clears is a comma separated count of clears by size, starting with
+single line clears, e.g. "12,3,1,0".
+
+*/
+
+use std::collections::HashMap;
+use std::time;
+
+use crate::block;
+use crate::config;
+use crate::game;
+use crate::highscore;
+
+const HISTORY_FILE: &str = "history.txt";
+
+pub struct Stats {
+    pub piece_counts: HashMap<block::Label, u32>,
+    // clears[n] is the number of times n + 1 lines were cleared at once.
+    pub clears: Vec<u32>,
+    pub holes_created: u32,
+    pub max_stack_height: u32,
+    // Moves and rotations made by the player.
+    pub inputs: u32,
+    // Time played when the current block spawned.
+    pub piece_spawned_at: time::Duration,
+    pub slowest_piece: time::Duration,
+}
+
+pub fn initialise() -> Stats {
+    Stats {
+        piece_counts: HashMap::new(),
+        clears: vec![],
+        holes_created: 0,
+        max_stack_height: 0,
+        inputs: 0,
+        piece_spawned_at: time::Duration::from_millis(0),
+        slowest_piece: time::Duration::from_millis(0),
+    }
+}
+
+pub fn record_spawn(stats: &mut Stats, label: &block::Label, time_played: time::Duration) {
+    *stats.piece_counts.entry(label.clone()).or_insert(0) += 1;
+    stats.piece_spawned_at = time_played;
+}
+
+pub fn record_input(stats: &mut Stats) {
+    stats.inputs += 1;
+}
+
+// Called once a block has been painted on the board, before full lines
+// are removed.
+pub fn record_lock(
+    stats: &mut Stats,
+    board_before: &game::Board,
+    board_after: &game::Board,
+    time_played: time::Duration,
+) {
+    let holes_before = count_holes(board_before);
+    let holes_after = count_holes(board_after);
+    stats.holes_created += holes_after.saturating_sub(holes_before);
+    stats.max_stack_height = stats.max_stack_height.max(stack_height(board_after));
+
+    let piece_time = time_played.checked_sub(stats.piece_spawned_at);
+    if let Some(piece_time) = piece_time {
+        stats.slowest_piece = stats.slowest_piece.max(piece_time);
+    }
+}
+
+pub fn record_clear(stats: &mut Stats, lines: i32) {
+    if lines <= 0 {
+        return;
+    }
+    let index = lines as usize - 1;
+    if stats.clears.len() <= index {
+        stats.clears.resize(index + 1, 0);
+    }
+    stats.clears[index] += 1;
+}
+
+pub fn pieces(stats: &Stats) -> u32 {
+    stats.piece_counts.values().sum()
+}
+
+pub fn inputs_per_piece(stats: &Stats) -> f32 {
+    match pieces(stats) {
+        0 => 0.,
+        n => stats.inputs as f32 / n as f32,
+    }
+}
+
+// A hole is an empty cell with a filled cell somewhere above it.
+pub fn count_holes(board: &game::Board) -> u32 {
+    let width = board.first().map_or(0, |row| row.len());
+    (0..width)
+        .map(|x| {
+            board
+                .iter()
+                .skip_while(|row| row[x].is_none())
+                .filter(|row| row[x].is_none())
+                .count() as u32
+        })
+        .sum()
+}
+
+// Height of the highest filled cell measured from the bottom of the board.
+pub fn stack_height(board: &game::Board) -> u32 {
+    board
+        .iter()
+        .position(|row| row.iter().any(|cell| cell.is_some()))
+        .map_or(0, |top| (board.len() - top) as u32)
+}
+
+// Lines of text for the side panel.
+pub fn panel_lines(game: &game::Game) -> Vec<String> {
+    let stats = &game.stats;
+    let mut lines = vec![
+        format!("Lines     {:>6}", game.lines),
+        format!("Level     {:>6}", game.level),
+        format!("Pieces    {:>6}", pieces(stats)),
+        format!("Inputs/pc {:>6.1}", inputs_per_piece(stats)),
+        format!("Holes     {:>6}", stats.holes_created),
+        format!("Max stack {:>6}", stats.max_stack_height),
+        format!(
+            "Slowest   {:>5.1}s",
+            stats.slowest_piece.as_millis() as f32 / 1000.
+        ),
+    ];
+    stats.clears.iter().enumerate().for_each(|(i, n)| {
+        lines.push(format!("{}-line    {:>6}", i + 1, n));
+    });
+    lines
+}
+
+pub fn append_history(game: &game::Game) {
+    let stats = &game.stats;
+    let clears: Vec<String> = stats.clears.iter().map(|n| n.to_string()).collect();
+    let line = format!(
+        "{}|{}|{}|{}|{}|{}|{}|{}|{}|{}|{}\n",
+        highscore::now_timestamp(),
+        game.mode.label(),
+        game.score,
+        game.lines,
+        game.level,
+        game.time_played.as_millis(),
+        pieces(stats),
+        stats.inputs,
+        stats.holes_created,
+        stats.max_stack_height,
+        clears.join(",")
+    );
+    config::append_file(HISTORY_FILE, &line);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A board drawn like a piece file, 'X' is filled.
+    fn board(rows: &[&str]) -> game::Board {
+        let color = block::Color { r: 9, g: 9, b: 9 };
+        rows.iter()
+            .map(|row| {
+                row.chars()
+                    .map(|c| Some(color).filter(|_| c == 'X'))
+                    .collect()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn counts_clears_by_size() {
+        let mut stats = initialise();
+        record_clear(&mut stats, 0);
+        assert!(stats.clears.is_empty());
+        record_clear(&mut stats, 2);
+        record_clear(&mut stats, 1);
+        record_clear(&mut stats, 2);
+        assert_eq!(stats.clears, vec![1, 2]);
+        record_clear(&mut stats, 4);
+        assert_eq!(stats.clears, vec![1, 2, 0, 1]);
+    }
+
+    #[test]
+    fn counts_holes_under_the_stack() {
+        assert_eq!(count_holes(&board(&["....", "....", "...."])), 0);
+        assert_eq!(count_holes(&board(&["....", ".X..", "XXXX"])), 0);
+        assert_eq!(count_holes(&board(&[".X..", "....", "X.XX"])), 2);
+        assert_eq!(count_holes(&board(&["XX..", "X...", "..X."])), 3);
+        assert_eq!(count_holes(&vec![]), 0);
+    }
+
+    #[test]
+    fn measures_the_stack_from_the_bottom() {
+        assert_eq!(stack_height(&board(&["....", "....", "...."])), 0);
+        assert_eq!(stack_height(&board(&["....", "....", "..X."])), 1);
+        assert_eq!(stack_height(&board(&["....", "X...", "...."])), 2);
+        assert_eq!(stack_height(&board(&["...X", "....", "...."])), 3);
+    }
+
+    #[test]
+    fn locks_record_new_holes_and_height() {
+        let mut stats = initialise();
+        let before = board(&["....", "....", "X..X"]);
+        let after = board(&["....", "XX..", "X..X"]);
+        record_lock(&mut stats, &before, &after, time::Duration::from_secs(3));
+        assert_eq!(stats.holes_created, 1);
+        assert_eq!(stats.max_stack_height, 2);
+        assert_eq!(stats.slowest_piece, time::Duration::from_secs(3));
+    }
+}
@@ -0,0 +1,180 @@
+/*
+
+The summary screen is shown after a game has ended. It shows how the
+run went compared to the player's best and lets them retry straight
+away or return to the menu.
+
+*/
+
+use crate::block;
+use crate::game;
+use crate::highscore;
+use crate::menu;
+use crate::mode;
+use crate::mode::Ranking;
+use crate::stats;
+
+pub enum Item {
+    Retry,
+    // Only offered when the game made the high score table.
+    HighScores,
+    Menu,
+}
+
+pub struct Summary {
+    // The player's best in the mode before this game was played, found
+    // by the name they last entered.
+    pub personal_best: Option<highscore::Entry>,
+    // Rank in the high score table if the game made it in.
+    pub rank: Option<usize>,
+    pub items: Vec<Item>,
+    pub item_selected: usize,
+}
+
+pub fn initialise(personal_best: Option<highscore::Entry>) -> Summary {
+    Summary {
+        personal_best: personal_best,
+        rank: None,
+        items: vec![Item::Retry, Item::Menu],
+        item_selected: 0,
+    }
+}
+
+// Records where the game placed in the high score table.
+pub fn set_rank(summary: &mut Summary, rank: Option<usize>) {
+    summary.rank = rank;
+    if rank.is_some() {
+        summary.items = vec![Item::Retry, Item::HighScores, Item::Menu];
+    }
+}
+
+pub fn update(event: &Option<game::Input>, world: &mut game::World) {
+    let summary = &mut world.summary;
+    if let Some(event) = event {
+        match event {
+            game::Input::UpKeyDown | game::Input::LeftKeyDown => {
+                summary.item_selected =
+                    (summary.item_selected + summary.items.len() - 1) % summary.items.len();
+            }
+            game::Input::DownKeyDown | game::Input::RightKeyDown => {
+                summary.item_selected = (summary.item_selected + 1) % summary.items.len();
+            }
+            game::Input::ReturnKeyDown | game::Input::SpaceKeyDown => {
+                match summary.items[summary.item_selected] {
+                    Item::Retry => menu::retry_game(world),
+                    // The view still highlights the entry just added.
+                    Item::HighScores => world.state = game::State::HighScores,
+                    Item::Menu => world.state = game::State::Menu,
+                }
+            }
+            game::Input::EscKeyDown => world.state = game::State::Menu,
+            _ => {}
+        }
+    }
+}
+
+pub fn item_label(item: &Item) -> &'static str {
+    match item {
+        Item::Retry => "Retry",
+        Item::HighScores => "High Scores",
+        Item::Menu => "Menu",
+    }
+}
+
+pub fn pieces_per_second(game: &game::Game) -> f32 {
+    let seconds = game.time_played.as_secs_f32();
+    if seconds <= 0. {
+        return 0.;
+    }
+    stats::pieces(&game.stats) as f32 / seconds
+}
+
+// Lines of text describing the game, drawn top to bottom.
+pub fn stats_lines(game: &game::Game) -> Vec<String> {
+    vec![
+        format!("Score   {:>8}", game.score),
+        format!("Lines   {:>8}", game.lines),
+        format!("Level   {:>8}", game.level),
+        format!(
+            "Time    {:>8}",
+            highscore::format_precise_duration(game.time_played.as_millis())
+        ),
+        format!("Pieces/s{:>8.2}", pieces_per_second(game)),
+    ]
+}
+
+pub fn personal_best_line(summary: &Summary, game: &game::Game) -> String {
+    match game::current_mode(game).ranking() {
+        Ranking::Score => score_best_line(summary, game),
+        Ranking::Time => time_best_line(summary, game),
+        Ranking::Unranked => match game.outcome {
+            mode::Outcome::Won => "Goal complete!".to_string(),
+            _ => "Goal not reached".to_string(),
+        },
+    }
+}
+
+fn score_best_line(summary: &Summary, game: &game::Game) -> String {
+    match (&summary.personal_best, summary.rank) {
+        _ if is_new_best(summary, game) => "New personal best!".to_string(),
+        (Some(best), Some(rank)) => format!("High score #{}  (best {})", rank + 1, best.score),
+        (Some(best), None) => format!(
+            "Personal best {}  ({:+})",
+            best.score,
+            game.score - best.score
+        ),
+        (None, _) => "No personal best yet".to_string(),
+    }
+}
+
+// A game that made the table under the player's name and beat their
+// previous entry, if they had one.
+fn is_new_best(summary: &Summary, game: &game::Game) -> bool {
+    if summary.rank.is_none() {
+        return false;
+    }
+    let ranking = game::current_mode(game).ranking();
+    let entry = highscore::entry_from_game(game, "");
+    match &summary.personal_best {
+        Some(best) => highscore::compare_entries(ranking, &entry, best) == std::cmp::Ordering::Less,
+        None => true,
+    }
+}
+
+// Only a won game has a time to compare.
+fn time_best_line(summary: &Summary, game: &game::Game) -> String {
+    let format = highscore::format_precise_duration;
+    match (&summary.personal_best, summary.rank) {
+        _ if is_new_best(summary, game) => "New personal best!".to_string(),
+        (Some(best), Some(rank)) => format!(
+            "Best time #{}  (best {})",
+            rank + 1,
+            format(best.duration_millis)
+        ),
+        (Some(best), None) if game.outcome == mode::Outcome::Won => {
+            let time = game.time_played.as_millis();
+            let difference = match time.checked_sub(best.duration_millis) {
+                Some(slower) => format!("+{}", format(slower)),
+                None => format!("-{}", format(best.duration_millis - time)),
+            };
+            format!(
+                "Personal best {}  ({})",
+                format(best.duration_millis),
+                difference
+            )
+        }
+        (Some(best), None) => format!("Personal best {}", format(best.duration_millis)),
+        (None, _) => "No personal best yet".to_string(),
+    }
+}
+
+// Count of each block in the mode, in the order of the mode's set.
+pub fn histogram(game: &game::Game) -> Vec<(block::Label, u32)> {
+    block::labels(&game.blocks)
+        .into_iter()
+        .map(|label| {
+            let count = *game.stats.piece_counts.get(&label).unwrap_or(&0);
+            (label, count)
+        })
+        .collect()
+}
@@ -0,0 +1,627 @@
+/*
+
+Two players race side by side in one window, each with their own game,
+keys and blocks. Clearing lines attacks the other player with garbage,
+see attack.rs, and the first to top out loses. Reaching the mode's goal
+first, such as 40 lines in Sprint, also wins the match.
+
+Both games can be dealt the same blocks by starting them from the same
+seed, otherwise each has its own random seed.
+
+The keys of each player are bound in main.rs, which passes the inputs
+of the frame along with the player they belong to.
+
+The second player can be the CPU instead, see cpu.rs.
+
+A match can also be played over the network, see network.rs. The
+local player is then always the first player and the second is drawn
+from what the other game sends. Either set of keys plays.
+
+*/
+
+use std::net::TcpListener;
+use std::time;
+
+use rand::Rng;
+
+use crate::attack;
+use crate::cpu;
+use crate::game;
+use crate::mode;
+use crate::network;
+use crate::network::Message;
+use crate::random;
+use crate::sound;
+
+pub const PLAYERS: usize = 2;
+
+const DEFAULT_ADDRESS: &str = "127.0.0.1";
+const MAX_ADDRESS_LENGTH: usize = 40;
+
+pub enum SetupItem {
+    Start,
+    Opponent,
+    SamePieces,
+    Host,
+    Join,
+    Back,
+}
+
+pub enum ResultItem {
+    Rematch,
+    Menu,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum MatchResult {
+    Won(usize),
+    Draw,
+    // The other player left a networked match before it was over.
+    Disconnected,
+}
+
+pub struct Player {
+    pub game: game::Game,
+    // Countdown or Play while in the match, GameOver while the player
+    // soaks in their defeat, then Menu. Victory if they won the mode.
+    pub state: game::State,
+    pub garbage_sent: usize,
+}
+
+pub struct Versus {
+    pub setup_items: Vec<SetupItem>,
+    pub setup_selected: usize,
+    pub same_pieces: bool,
+    pub opponent: Option<cpu::Difficulty>, // None when the second player is a person
+    pub address: String,                   // of the host to join
+    // Shown on the setup and connecting screens, e.g. why joining failed.
+    pub message: String,
+    pub players: Vec<Player>,
+    pub cpu: Option<cpu::Cpu>, // playing the second player in a local match
+    pub rng: random::Rng,      // picks the holes in garbage rows
+    pub result: Option<MatchResult>,
+    pub result_items: Vec<ResultItem>,
+    pub result_selected: usize,
+    pub listener: Option<TcpListener>, // while waiting for a player to join
+    pub link: Option<network::Link>,   // to the other player in a networked match
+    pub hosting: bool,
+    pub networked: bool, // whether the match being played is networked
+    pub over_sent: bool,
+    last_board: String, // last board sent, only changes are sent
+}
+
+// No match is being played until one is started from the setup.
+pub fn initialise() -> Versus {
+    Versus {
+        setup_items: vec![
+            SetupItem::Start,
+            SetupItem::Opponent,
+            SetupItem::SamePieces,
+            SetupItem::Host,
+            SetupItem::Join,
+            SetupItem::Back,
+        ],
+        setup_selected: 0,
+        same_pieces: true,
+        opponent: None,
+        address: DEFAULT_ADDRESS.to_string(),
+        message: "".to_string(),
+        players: vec![],
+        cpu: None,
+        rng: random::from_seed(random::random_seed()),
+        result: None,
+        result_items: vec![ResultItem::Rematch, ResultItem::Menu],
+        result_selected: 0,
+        listener: None,
+        link: None,
+        hosting: false,
+        networked: false,
+        over_sent: false,
+        last_board: "".to_string(),
+    }
+}
+
+pub fn setup_label(item: &SetupItem, versus: &Versus) -> String {
+    match item {
+        SetupItem::Start => "Start".to_string(),
+        SetupItem::Opponent => match versus.opponent {
+            Some(difficulty) => format!("Opponent: CPU {}", cpu::difficulty_label(difficulty)),
+            None => "Opponent: Player 2".to_string(),
+        },
+        SetupItem::SamePieces if versus.same_pieces => "Same pieces: On".to_string(),
+        SetupItem::SamePieces => "Same pieces: Off".to_string(),
+        SetupItem::Host => format!("Host on port {}", network::DEFAULT_PORT),
+        SetupItem::Join => format!("Join: {}", versus.address),
+        SetupItem::Back => "Back".to_string(),
+    }
+}
+
+pub fn result_label(item: &ResultItem) -> &'static str {
+    match item {
+        ResultItem::Rematch => "Rematch",
+        ResultItem::Menu => "Menu",
+    }
+}
+
+pub fn is_networked(versus: &Versus) -> bool {
+    versus.networked
+}
+
+pub fn player_label(versus: &Versus, player: usize) -> String {
+    match (is_networked(versus), player) {
+        (true, 0) => "You".to_string(),
+        (true, _) => "Opponent".to_string(),
+        (false, 1) if versus.cpu.is_some() => "CPU".to_string(),
+        (false, player) => format!("Player {}", player + 1),
+    }
+}
+
+pub fn result_title(versus: &Versus) -> String {
+    match (versus.result, is_networked(versus)) {
+        (Some(MatchResult::Won(0)), true) => "You win".to_string(),
+        (Some(MatchResult::Won(_)), true) => "You lose".to_string(),
+        (Some(MatchResult::Won(player)), false) => {
+            format!("{} wins", player_label(versus, player))
+        }
+        (Some(MatchResult::Disconnected), _) => "The other player left".to_string(),
+        _ => "Draw".to_string(),
+    }
+}
+
+fn is_valid_address_character(c: char) -> bool {
+    c.is_ascii_alphanumeric() || ['.', ':', '-', '[', ']'].contains(&c)
+}
+
+pub fn update_setup(event: &Option<game::Input>, world: &mut game::World) {
+    let versus = &mut world.versus;
+    let n_items = versus.setup_items.len();
+    let editing_address = matches!(versus.setup_items[versus.setup_selected], SetupItem::Join);
+    if let Some(event) = event {
+        match event {
+            game::Input::UpKeyDown => {
+                versus.setup_selected = (versus.setup_selected + n_items - 1) % n_items;
+            }
+            game::Input::DownKeyDown => {
+                versus.setup_selected = (versus.setup_selected + 1) % n_items;
+            }
+            // Typed text is enabled on this screen for the address.
+            game::Input::Character(c)
+                if editing_address
+                    && is_valid_address_character(*c)
+                    && versus.address.len() < MAX_ADDRESS_LENGTH =>
+            {
+                versus.address.push(*c);
+            }
+            game::Input::BackspaceKeyDown if editing_address => {
+                versus.address.pop();
+            }
+            game::Input::LeftKeyDown => match versus.setup_items[versus.setup_selected] {
+                SetupItem::Opponent => versus.opponent = change_opponent(versus.opponent, -1),
+                SetupItem::SamePieces => versus.same_pieces = !versus.same_pieces,
+                _ => {}
+            },
+            game::Input::RightKeyDown => match versus.setup_items[versus.setup_selected] {
+                SetupItem::Opponent => versus.opponent = change_opponent(versus.opponent, 1),
+                SetupItem::SamePieces => versus.same_pieces = !versus.same_pieces,
+                _ => {}
+            },
+            game::Input::ReturnKeyDown
+            | game::Input::SpaceKeyDown
+            | game::Input::Character(' ') => match versus.setup_items[versus.setup_selected] {
+                SetupItem::Start => start(world),
+                SetupItem::Opponent => versus.opponent = change_opponent(versus.opponent, 1),
+                SetupItem::SamePieces => versus.same_pieces = !versus.same_pieces,
+                SetupItem::Host => host(versus, &mut world.state),
+                SetupItem::Join => join(versus, &mut world.state),
+                SetupItem::Back => world.state = game::State::Menu,
+            },
+            game::Input::EscKeyDown => world.state = game::State::Menu,
+            _ => {}
+        }
+    }
+}
+
+// Steps through a second player, then the CPU difficulties.
+fn change_opponent(opponent: Option<cpu::Difficulty>, delta: i32) -> Option<cpu::Difficulty> {
+    let mut choices = vec![None];
+    choices.extend(cpu::DIFFICULTIES.iter().map(|&difficulty| Some(difficulty)));
+    let n = choices.len() as i32;
+    let index = choices.iter().position(|&c| c == opponent).unwrap_or(0) as i32;
+    choices[(index + delta).rem_euclid(n) as usize]
+}
+
+fn host(versus: &mut Versus, state: &mut game::State) {
+    match network::listen(network::DEFAULT_PORT) {
+        Ok(listener) => {
+            versus.listener = Some(listener);
+            versus.hosting = true;
+            versus.message = format!(
+                "Waiting for a player to join on port {}",
+                network::DEFAULT_PORT
+            );
+            *state = game::State::VersusConnecting;
+        }
+        Err(error) => versus.message = error,
+    }
+}
+
+fn join(versus: &mut Versus, state: &mut game::State) {
+    match network::connect(&versus.address) {
+        Ok(mut link) => {
+            let hello = Message::Hello {
+                version: network::PROTOCOL_VERSION,
+            };
+            match network::send(&mut link, &hello) {
+                Ok(()) => {
+                    versus.link = Some(link);
+                    versus.hosting = false;
+                    versus.message = format!("Waiting for {} to start", versus.address);
+                    *state = game::State::VersusConnecting;
+                }
+                Err(error) => versus.message = error,
+            }
+        }
+        Err(error) => versus.message = error,
+    }
+}
+
+// Leaves a networked match, or stops waiting for one.
+fn disconnect(versus: &mut Versus) {
+    versus.listener = None;
+    versus.link = None;
+}
+
+// Waits for a player to join the host, then for both to say hello
+// before the host starts the match.
+pub fn update_connecting(event: &Option<game::Input>, world: &mut game::World) {
+    if let Some(game::Input::EscKeyDown) = event {
+        disconnect(&mut world.versus);
+        world.versus.message = "".to_string();
+        world.state = game::State::VersusSetup;
+        return;
+    }
+    match connect_step(world) {
+        Ok(()) => {}
+        Err(error) => {
+            disconnect(&mut world.versus);
+            world.versus.message = error;
+            world.state = game::State::VersusSetup;
+        }
+    }
+}
+
+fn connect_step(world: &mut game::World) -> Result<(), String> {
+    let versus = &mut world.versus;
+    if let Some(listener) = &versus.listener {
+        if let Some(mut link) = network::accept(listener)? {
+            let hello = Message::Hello {
+                version: network::PROTOCOL_VERSION,
+            };
+            network::send(&mut link, &hello)?;
+            versus.link = Some(link);
+            versus.listener = None;
+        }
+    }
+    let messages = match &mut versus.link {
+        Some(link) => network::receive(link)?,
+        None => vec![],
+    };
+    for message in messages {
+        match message {
+            Message::Hello { version } if version != network::PROTOCOL_VERSION => {
+                return Err(format!(
+                    "The other player's game is version {}, this one is {}",
+                    version,
+                    network::PROTOCOL_VERSION
+                ));
+            }
+            Message::Hello { .. } if world.versus.hosting => {
+                let mode = world.game.modes[world.game.mode_selected].clone();
+                let seed = random::random_seed();
+                let other_seed = match world.versus.same_pieces {
+                    true => seed,
+                    false => random::random_seed(),
+                };
+                let start_message = Message::Start {
+                    seed: other_seed,
+                    mode: mode.label().to_string(),
+                };
+                if let Some(link) = &mut world.versus.link {
+                    network::send(link, &start_message)?;
+                }
+                start_match(world, mode, [seed, other_seed]);
+                return Ok(());
+            }
+            Message::Start { seed, mode } => {
+                let mode = world
+                    .game
+                    .modes
+                    .iter()
+                    .find(|m| m.label() == mode)
+                    .cloned()
+                    .ok_or(format!("The host is playing {}, which isn't here", mode))?;
+                start_match(world, mode, [seed, seed]);
+                return Ok(());
+            }
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+// Starts a local match in the mode selected in the menu.
+pub fn start(world: &mut game::World) {
+    let mode = world.game.modes[world.game.mode_selected].clone();
+    let seed = random::random_seed();
+    let other_seed = match world.versus.same_pieces {
+        true => seed,
+        false => random::random_seed(),
+    };
+    start_match(world, mode, [seed, other_seed]);
+}
+
+fn start_match(world: &mut game::World, mode: mode::Mode, seeds: [u64; PLAYERS]) {
+    let mode_selected = world.game.mode_selected;
+    let versus = &mut world.versus;
+    versus.players = seeds
+        .iter()
+        .map(|&seed| {
+            let modes = mode::initialise_modes();
+            let game = game::initialise_seeded_game(modes, mode_selected, mode.clone(), seed);
+            Player {
+                state: game::start_state(&game),
+                game: game,
+                garbage_sent: 0,
+            }
+        })
+        .collect();
+    versus.networked = versus.link.is_some();
+    versus.cpu = match versus.networked {
+        true => None,
+        false => versus.opponent.map(cpu::initialise),
+    };
+    if is_networked(versus) {
+        // The other player's game is only drawn from what they send.
+        let remote = &mut versus.players[1];
+        remote.game.block.positions = vec![];
+        remote.game.countdown = time::Duration::from_millis(0);
+        remote.state = game::State::Play;
+    }
+    versus.result = None;
+    versus.over_sent = false;
+    versus.last_board = "".to_string();
+    versus.message = "".to_string();
+    world.music_file = sound::music_path(&mode);
+    world.state = game::State::Versus;
+}
+
+pub fn update(
+    event: &Option<game::Input>,
+    player_inputs: &[(usize, game::Input)],
+    world: &mut game::World,
+) {
+    if let Some(game::Input::EscKeyDown) = event {
+        disconnect(&mut world.versus);
+        world.state = game::State::Menu;
+        return;
+    }
+
+    let versus = &mut world.versus;
+    let local_players = match is_networked(versus) {
+        true => 1,
+        false => PLAYERS,
+    };
+    // Either set of keys plays when there's only one person playing.
+    let any_keys = local_players == 1 || versus.cpu.is_some();
+    (0..local_players).for_each(|player| {
+        // Like the single player game only one input is taken a frame.
+        let event = match &mut versus.cpu {
+            Some(cpu) if player == 1 => cpu::input(cpu, &versus.players[player].game),
+            _ => player_inputs
+                .iter()
+                .rev()
+                .find(|(p, _)| *p == player || any_keys)
+                .map(|(_, input)| input.clone()),
+        };
+        update_player(&mut versus.players[player], &event);
+        let rows = attack::take_outgoing(&mut versus.players[player].game);
+        if rows > 0 {
+            versus.players[player].garbage_sent += rows;
+            let width = versus.players[player].game.board[0].len();
+            let hole = versus.rng.gen_range(0, width);
+            match &mut versus.link {
+                Some(link) => {
+                    let mut left = rows;
+                    while left > 0 {
+                        let garbage = Message::Garbage {
+                            rows: left.min(network::MAX_GARBAGE_ROWS),
+                            hole: hole,
+                        };
+                        left -= left.min(network::MAX_GARBAGE_ROWS);
+                        // A lost connection is noticed when receiving.
+                        let _ = network::send(link, &garbage);
+                    }
+                }
+                None => {
+                    let opponent = &mut versus.players[(player + 1) % PLAYERS];
+                    receive_garbage(opponent, rows, hole);
+                }
+            }
+        }
+    });
+
+    if is_networked(versus) && exchange(versus).is_err() {
+        disconnect(versus);
+        versus.result = Some(MatchResult::Disconnected);
+    }
+
+    let result = match versus.result {
+        Some(MatchResult::Disconnected) => versus.result,
+        _ => match_result(&versus.players),
+    };
+    if let Some(result) = result {
+        versus.result = Some(result);
+        versus.result_items = match is_networked(versus) {
+            // A rematch has to be hosted again.
+            true => vec![ResultItem::Menu],
+            false => vec![ResultItem::Rematch, ResultItem::Menu],
+        };
+        versus.result_selected = 0;
+        world.state = game::State::VersusResult;
+    }
+}
+
+// Sends the other player the local game and takes what they sent.
+fn exchange(versus: &mut Versus) -> Result<(), String> {
+    let link = match &mut versus.link {
+        Some(link) => link,
+        None => return Ok(()),
+    };
+    let local = &mut versus.players[0];
+
+    let board = network::board_message(&local.game);
+    let encoded = network::encode(&board);
+    if encoded != versus.last_board {
+        network::send(link, &board)?;
+        versus.last_board = encoded;
+    }
+    let finished = [game::State::Menu, game::State::Victory].contains(&local.state);
+    if finished && !versus.over_sent {
+        let won = local.state == game::State::Victory;
+        network::send(link, &Message::Over { won: won })?;
+        versus.over_sent = true;
+    }
+
+    let size = versus.players[1].game.mode.board_size();
+    for message in network::receive(link)? {
+        match message {
+            Message::Board { board, .. }
+                if board.len() != size.y as usize
+                    || board.iter().any(|row| row.len() != size.x as usize) =>
+            {
+                return Err("The other player sent a board of the wrong size".to_string());
+            }
+            Message::Board {
+                score,
+                lines,
+                time_played,
+                board,
+            } => {
+                let remote = &mut versus.players[1].game;
+                remote.score = score;
+                remote.lines = lines;
+                remote.time_played = time::Duration::from_millis(time_played as u64);
+                remote.board = board;
+            }
+            Message::Garbage { hole, .. } if hole >= versus.players[0].game.board[0].len() => {
+                return Err("The other player sent garbage off the board".to_string());
+            }
+            Message::Garbage { rows, hole } => {
+                versus.players[1].garbage_sent += rows;
+                receive_garbage(&mut versus.players[0], rows, hole);
+            }
+            Message::Over { won } => {
+                versus.players[1].state = match won {
+                    true => game::State::Victory,
+                    false => game::State::Menu,
+                };
+            }
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+fn update_player(player: &mut Player, event: &Option<game::Input>) {
+    match player.state {
+        game::State::Countdown => player.state = game::update_countdown(&mut player.game),
+        game::State::Play => {
+            player.state = match game::update(event, &mut player.game, &player.state) {
+                // A match can't be paused by one of the players.
+                game::State::Paused => game::State::Play,
+                state => state,
+            };
+        }
+        game::State::GameOver => {
+            player.state = game::update(&None, &mut player.game, &player.state);
+        }
+        _ => {}
+    }
+}
+
+// The rows of one attack share a hole so they can be cleared together.
+// They wait in the player's meter, see attack.rs.
+fn receive_garbage(player: &mut Player, rows: usize, hole: usize) {
+    if [game::State::Countdown, game::State::Play].contains(&player.state) {
+        attack::receive(&mut player.game, rows, hole);
+    }
+}
+
+// The match is over when a player wins the mode, or once the players
+// that topped out have finished their game over pause.
+fn match_result(players: &[Player]) -> Option<MatchResult> {
+    let winners: Vec<usize> = (0..PLAYERS)
+        .filter(|&p| players[p].state == game::State::Victory)
+        .collect();
+    let losers: Vec<usize> = (0..PLAYERS)
+        .filter(|&p| [game::State::GameOver, game::State::Menu].contains(&players[p].state))
+        .collect();
+    if winners.is_empty() && losers.is_empty() {
+        return None;
+    }
+    if winners.is_empty() && players.iter().any(|p| p.state == game::State::GameOver) {
+        return None;
+    }
+    match (winners.as_slice(), losers.as_slice()) {
+        ([winner], _) => Some(MatchResult::Won(*winner)),
+        ([], [loser]) => Some(MatchResult::Won((loser + 1) % PLAYERS)),
+        _ => Some(MatchResult::Draw),
+    }
+}
+
+pub fn update_result(event: &Option<game::Input>, world: &mut game::World) {
+    let versus = &mut world.versus;
+    let n_items = versus.result_items.len();
+    if let Some(event) = event {
+        match event {
+            game::Input::UpKeyDown | game::Input::LeftKeyDown => {
+                versus.result_selected = (versus.result_selected + n_items - 1) % n_items;
+            }
+            game::Input::DownKeyDown | game::Input::RightKeyDown => {
+                versus.result_selected = (versus.result_selected + 1) % n_items;
+            }
+            game::Input::ReturnKeyDown | game::Input::SpaceKeyDown => {
+                match versus.result_items[versus.result_selected] {
+                    ResultItem::Rematch => start(world),
+                    ResultItem::Menu => {
+                        disconnect(versus);
+                        world.state = game::State::Menu;
+                    }
+                }
+            }
+            game::Input::EscKeyDown => {
+                disconnect(versus);
+                world.state = game::State::Menu;
+            }
+            _ => {}
+        }
+    }
+}
+
+// A line for each player on the result screen.
+pub fn player_lines(versus: &Versus) -> Vec<String> {
+    versus
+        .players
+        .iter()
+        .enumerate()
+        .map(|(i, player)| {
+            format!(
+                "{:<9} Score {:<7} Lines {:<4} Garbage sent {}",
+                player_label(versus, i),
+                player.game.score,
+                player.game.lines,
+                player.garbage_sent
+            )
+        })
+        .collect()
+}
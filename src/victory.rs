@@ -0,0 +1,77 @@
+/*
+
+The victory screen is shown when the player wins a mode. Modes that
+allow it can be carried on in endless play, otherwise the game is
+finished and goes on to the high scores and summary like any other.
+
+*/
+
+use crate::game;
+use crate::highscore;
+use crate::stats;
+
+pub enum Item {
+    KeepPlaying,
+    Finish,
+}
+
+pub struct Victory {
+    pub items: Vec<Item>,
+    pub item_selected: usize,
+}
+
+pub fn initialise(endless: bool) -> Victory {
+    let mut items = vec![];
+    if endless {
+        items.push(Item::KeepPlaying);
+    }
+    items.push(Item::Finish);
+    Victory {
+        items: items,
+        item_selected: 0,
+    }
+}
+
+pub fn update(event: &Option<game::Input>, world: &mut game::World) {
+    let victory = &mut world.victory;
+    if let Some(event) = event {
+        match event {
+            game::Input::UpKeyDown | game::Input::LeftKeyDown => {
+                victory.item_selected =
+                    (victory.item_selected + victory.items.len() - 1) % victory.items.len();
+            }
+            game::Input::DownKeyDown | game::Input::RightKeyDown => {
+                victory.item_selected = (victory.item_selected + 1) % victory.items.len();
+            }
+            game::Input::ReturnKeyDown | game::Input::SpaceKeyDown => {
+                match victory.items[victory.item_selected] {
+                    Item::KeepPlaying => keep_playing(world),
+                    Item::Finish => finish(world),
+                }
+            }
+            game::Input::EscKeyDown => finish(world),
+            _ => {}
+        }
+    }
+}
+
+pub fn item_label(item: &Item) -> &'static str {
+    match item {
+        Item::KeepPlaying => "Keep Playing",
+        Item::Finish => "Finish",
+    }
+}
+
+fn keep_playing(world: &mut game::World) {
+    let game = &mut world.game;
+    game.endless = true;
+    // Time spent on the victory screen isn't played.
+    game.frame_clock = game::now(game);
+    game.block_drop_clock = game::now(game);
+    world.state = game::State::Play;
+}
+
+fn finish(world: &mut game::World) {
+    stats::append_history(&world.game);
+    world.state = highscore::game_finished(world);
+}